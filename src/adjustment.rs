@@ -0,0 +1,148 @@
+
+use std::fmt;
+use std::str::FromStr;
+
+use gdk_pixbuf::{Pixbuf, PixbufExt};
+
+use crate::errors::AppError;
+
+
+
+/** Tonal adjustments for the rendered images. 0 means "as is" **/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Adjustment {
+    pub brightness: Level,
+    pub contrast: Level,
+    pub gamma: Level,
+    pub grayscale: bool,
+    pub invert: bool,
+    pub saturation: Level,
+}
+
+/** Percentage (-100 - 100). Clamped on update **/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Level(i32);
+
+
+impl Level {
+    pub fn new(value: i32) -> Self {
+        Level(clamp!(-100, value, 100))
+    }
+
+    pub fn value(self) -> i32 {
+        self.0
+    }
+
+    pub fn offset(self, delta: i32) -> Self {
+        Level::new(self.0.saturating_add(delta))
+    }
+}
+
+impl FromStr for Level {
+    type Err = AppError;
+
+    fn from_str(src: &str) -> Result<Self, AppError> {
+        src.parse().map(Level::new).map_err(|it| AppError::InvalidValueWithReason(o!(src), s!(it)))
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+
+impl Adjustment {
+    pub fn is_identity(&self) -> bool {
+        *self == Adjustment::default()
+    }
+
+    pub fn apply(&self, pixbuf: &Pixbuf) {
+        if self.is_identity() || pixbuf.get_n_channels() != 4 {
+            return;
+        }
+
+        let (width, height) = (pixbuf.get_width() as usize, pixbuf.get_height() as usize);
+        let rowstride = pixbuf.get_rowstride() as usize;
+        let pixels: &mut [u8] = unsafe { pixbuf.get_pixels() };
+        self.apply_to_pixels(pixels, width, height, rowstride);
+    }
+
+    fn apply_to_pixels(&self, pixels: &mut [u8], width: usize, height: usize, rowstride: usize) {
+        let table = self.table();
+        let saturation = if self.grayscale { 0.0 } else { f64!(100 + self.saturation.value()) / 100.0 };
+        let to_u8 = |v: f64| min!(max!(v.round(), 0.0), 255.0) as u8;
+
+        for y in 0 .. height {
+            for x in 0 .. width {
+                let offset = y * rowstride + x * 4;
+                let p = &mut pixels[offset .. offset + 3];
+                let mut rgb = [table[usize::from(p[0])], table[usize::from(p[1])], table[usize::from(p[2])]];
+
+                if self.grayscale || self.saturation.value() != 0 {
+                    let luminance = 0.299 * rgb[0] + 0.587 * rgb[1] + 0.114 * rgb[2];
+                    for it in &mut rgb {
+                        *it = luminance + (*it - luminance) * saturation;
+                    }
+                }
+
+                if self.invert {
+                    for it in &mut rgb {
+                        *it = 255.0 - *it;
+                    }
+                }
+
+                for (dest, it) in p.iter_mut().zip(&rgb) {
+                    *dest = to_u8(*it);
+                }
+            }
+        }
+    }
+
+    /** Brightness -> Contrast -> Gamma **/
+    fn table(&self) -> Vec<f64> {
+        let brightness = f64!(self.brightness.value()) * 2.55;
+        let contrast = f64!(100 + self.contrast.value()) / 100.0;
+        let gamma = 2.0_f64.powf(-f64!(self.gamma.value()) / 100.0);
+
+        (0 .. 256).map(|v: i32| {
+            let v = (f64!(v) + brightness - 128.0) * contrast + 128.0;
+            255.0 * min!(max!(v / 255.0, 0.0), 1.0).powf(gamma)
+        }).collect()
+    }
+}
+
+
+#[cfg(test)]#[test]
+fn test_adjustment() {
+    fn adjust(adjustment: Adjustment, rgb: [u8; 3]) -> Vec<u8> {
+        let mut pixels = vec![rgb[0], rgb[1], rgb[2], 255];
+        adjustment.apply_to_pixels(&mut pixels, 1, 1, 4);
+        pixels
+    }
+
+    let base = Adjustment::default();
+
+    assert!(base.is_identity());
+    assert_eq!(adjust(base, [10, 128, 250]), vec![10, 128, 250, 255]);
+    assert_eq!(adjust(Adjustment { invert: true, ..base }, [10, 128, 250]), vec![245, 127, 5, 255]);
+    assert_eq!(adjust(Adjustment { grayscale: true, ..base }, [100, 100, 100]), vec![100, 100, 100, 255]);
+    assert_eq!(adjust(Adjustment { grayscale: true, ..base }, [255, 0, 0]), vec![76, 76, 76, 255]);
+    assert_eq!(adjust(Adjustment { saturation: Level::new(-100), ..base }, [255, 0, 0]), vec![76, 76, 76, 255]);
+    assert_eq!(adjust(Adjustment { brightness: Level::new(100), ..base }, [0, 128, 255]), vec![255, 255, 255, 255]);
+    assert_eq!(adjust(Adjustment { brightness: Level::new(1000), ..base }, [0, 0, 0]), vec![255, 255, 255, 255]);
+    assert_eq!(adjust(Adjustment { contrast: Level::new(-100), ..base }, [0, 200, 255]), vec![128, 128, 128, 255]);
+    assert_eq!(adjust(Adjustment { gamma: Level::new(100), ..base }, [0, 64, 255]), vec![0, 128, 255, 255]);
+}
+
+#[cfg(test)]#[test]
+fn test_level() {
+    assert_eq!("50".parse::<Level>().unwrap(), Level::new(50));
+    assert_eq!("150".parse::<Level>().unwrap(), Level::new(100));
+    assert_eq!("-150".parse::<Level>().unwrap(), Level::new(-100));
+    assert!("x".parse::<Level>().is_err());
+    assert_eq!(Level::new(90).offset(20), Level::new(100));
+    assert_eq!(Level::new(-90).offset(-20), Level::new(-100));
+    assert_eq!(Level::new(100).offset(i32::max_value()), Level::new(100));
+}
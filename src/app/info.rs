@@ -1,7 +1,17 @@
+
+use crate::phash::{PHash, PHashTable};
+use crate::statistics::StatisticsTable;
+
+
+
 #[derive(Default)]
 pub struct AppInfo {
     pub active: bool,
     pub pages: usize,
     pub real_pages: usize,
     pub current_page: Option<usize>,
+    pub current_phash: Option<PHash>,
+    pub phashes: PHashTable,
+    pub similarity_threshold: u32,
+    pub statistics: StatisticsTable,
 }
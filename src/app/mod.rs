@@ -14,6 +14,7 @@ use log::trace;
 use rand::{self, ThreadRng};
 
 use crate::command_line::Initial;
+use crate::compare;
 use crate::config;
 use crate::constant;
 use crate::counter::Counter;
@@ -21,24 +22,31 @@ use crate::entry::image::Imaging;
 use crate::entry::{Entry, EntryContainer, EntryContent, Serial, Key};
 use crate::error_channel;
 use crate::events::EventName;
-use crate::gui::{Gui, Screen};
+use crate::feed;
+use crate::gui::{Gui, Screen, ScrollAnchor};
 use crate::history::History;
+use crate::image::{ImageBuffer, StaticImageBuffer};
 use crate::image_cache::ImageCache;
 use crate::image_fetcher::ImageFetcher;
+use crate::inspector::PixelEnv;
 use crate::logger;
 use crate::mapping::{Mapping, Mapped};
 use crate::operation::option::PreDefinedOptionName;
 use crate::operation::{Operation, QueuedOperation, OperationContext, MappingTarget, MoveBy, Updated};
 use crate::option::user_switch::UserSwitchManager;
+use crate::original_image::OriginalImage;
 use crate::paginator::values::Index;
 use crate::paginator::{self, Paginator, Paging};
-use crate::remote_cache::RemoteCache;
+use crate::remote_cache::{RemoteCache, journal};
 use crate::script;
 use crate::shell::ProcessManager;
-use crate::size::{Size, FitTo, Region};
+use crate::size::{CoordPx, Size, FitTo, Region};
 use crate::sorting_buffer::SortingBuffer;
+use crate::phash::PHashTable;
 use crate::state::{AutoPaging, States, PreFetchState};
+use crate::statistics::{StatisticsTable, self};
 use crate::timer::TimerManager;
+use crate::undo::{EntryDrawing, Snapshot, UndoHistory};
 use crate::util::path::path_to_str;
 use crate::watcher::Watcher;
 
@@ -53,19 +61,23 @@ use self::info::AppInfo;
 pub struct App {
     pub cache: ImageCache,
     pub entries: EntryContainer,
+    pub feeds: feed::History,
     pub gui: Gui,
     pub history: History,
     pub log: logger::memory::Memory,
     pub mapping: Mapping,
     pub marker: HashMap<String, Key>,
     pub paginator: Paginator,
+    pub phashes: PHashTable,
     pub primary_tx: Sender<Operation>,
     pub process_manager: ProcessManager,
     pub query_operation: Option<Vec<String>>,
     pub remote_cache: RemoteCache,
     pub secondary_tx: Sender<Operation>,
     pub states: States,
+    pub statistics: StatisticsTable,
     pub timers: TimerManager,
+    pub undo_history: UndoHistory,
     pub user_switches: UserSwitchManager,
     counter: Counter,
     current_base_scale: Option<f64>, // Scale of first scaled image
@@ -77,11 +89,15 @@ pub struct App {
     fetcher: ImageFetcher,
     found_on: Option<Range<usize>>,
     last_message: Option<String>,
+    original_image: OriginalImage,
+    pixel_env: PixelEnv,
+    pointer_at: Option<CoordPx>,
     pre_fetch_serial: u64,
     rng: ThreadRng,
     search_text: Option<String>,
     sorting_buffer: SortingBuffer<QueuedOperation>,
     watcher: Watcher,
+    zooms: HashMap<Key, FitTo>,
 }
 
 
@@ -114,6 +130,8 @@ impl App {
         let cache = ImageCache::new(cache_limit);
 
         let sorting_buffer = SortingBuffer::new();
+        let phashes = PHashTable::default();
+        let statistics = StatisticsTable::default();
 
         let app = App {
             cache: cache.clone(),
@@ -124,8 +142,9 @@ impl App {
             draw_serial: 0,
             encodings: initial.encodings.clone(),
             entries: EntryContainer::new(),
+            feeds: feed::History::default(),
             error_loop_detector: error_loop_detector::Detector::default(),
-            fetcher: ImageFetcher::new(cache),
+            fetcher: ImageFetcher::new(cache, phashes.clone(), statistics.clone(), secondary_tx.clone()),
             found_on: None,
             gui: Gui::new(&initial.window_role),
             history: History::default(),
@@ -133,7 +152,11 @@ impl App {
             log: logger::memory::Memory::new(),
             mapping: Mapping::new(),
             marker: HashMap::new(),
+            original_image: OriginalImage::default(),
             paginator: Paginator::new(),
+            phashes,
+            pixel_env: PixelEnv::default(),
+            pointer_at: None,
             pre_fetch_serial: 0,
             primary_tx,
             process_manager: ProcessManager::new(secondary_tx.clone()),
@@ -144,14 +167,22 @@ impl App {
             secondary_tx: secondary_tx.clone(),
             sorting_buffer,
             states,
+            statistics,
             timers: TimerManager::new(secondary_tx.clone()),
+            undo_history: UndoHistory::default(),
             user_switches: UserSwitchManager::new(secondary_tx.clone()),
             watcher: Watcher::new(secondary_tx),
+            zooms: HashMap::new(),
         };
 
         if initial.load_config {
             script::load(&app.secondary_tx, &config::get_config_source(initial.config_file.as_ref()), &app.states.path_list);
         }
+        if initial.discard_queue {
+            journal::discard();
+        } else {
+            journal::resume(&app.secondary_tx, &app.states.path_list);
+        }
         app.secondary_tx.send(Operation::InitialProcess(initial.entries, initial.shuffle, initial.stdin_as_binary)).unwrap();
         error_channel::register(app.secondary_tx.clone());
 
@@ -185,15 +216,26 @@ impl App {
         let len = self.entries.len();
         let count = self.counter.peek();
         let before_fly_leaves = self.paginator.fly_leaves();
+        let undo_snapshots = if operation.is_undoable() { Some(self.undo_snapshots()) } else { None };
+
+        if !matches!(operation, PointerMotion(_)) {
+            self.pixel_env.export();
+        }
 
         {
             let operated = match operation {
                 Apng(ref path, length) =>
                     on_apng(self, path, length),
+                AnchorScrolls(ref anchor) =>
+                    on_anchor_scrolls(self, anchor),
                 AppEvent(ref event_name, ref context) =>
                     on_app_event(self, &mut updated, event_name, context),
                 Backward =>
                     on_histoy_go(self, &mut updated, false),
+                Bake(ref file_operation, cell_index) =>
+                    on_bake(self, file_operation, cell_index),
+                CancelDownload(target) =>
+                    on_cancel_download(self, target),
                 Chain(target) =>
                     on_chain(target),
                 ChangeDirectory(ref path) =>
@@ -218,22 +260,30 @@ impl App {
                     on_count(self, &mut updated, count),
                 CountDigit(digit) =>
                     on_count_digit(self, &mut updated, digit),
+                Crop(ref parameter) =>
+                    on_crop(self, parameter, context),
                 DefineUserSwitch(name, values) =>
                     on_define_switch(self, name, values, context),
                 Delete(expr) =>
                     on_delete(self, &mut updated, *expr),
                 DetectEyes(parameter) =>
                     on_detect_eyes(self, parameter),
+                DiscardJournal(path) =>
+                    ok!(self.remote_cache.discard_journal(path)),
                 Draw =>
                     ok!(updated.image = true),
                 Editor(editor_command, files, sessions, comment_out, freeze) =>
                    on_editor(self, editor_command, &files, &sessions, comment_out, freeze),
+                Effect(effect, shape, region, cell_index) =>
+                    on_effect(self, &mut updated, effect, shape, region, cell_index, context),
                 Error(error) =>
                     on_error(self, &mut updated, error),
                 Eval(ref op) =>
                     on_eval(self, op, context),
                 Expand(recursive, ref base) =>
                     on_expand(self, &mut updated, recursive, base.clone()),
+                FetchOffline =>
+                    on_fetch_offline(self, &mut updated),
                 FileChanged(ref path) =>
                     on_file_changed(self, &mut updated, path),
                 Fill(shape, region, color, operator, mask, cell_index) =>
@@ -266,6 +316,8 @@ impl App {
                     on_last(self, &mut updated, count, ignore_views, move_by),
                 LazyDraw(serial, new_to_end) =>
                     on_lazy_draw(self, &mut updated, &mut to_end, serial, new_to_end),
+                Line(line, arrow, cell_index) =>
+                    on_line(self, &mut updated, line, arrow, cell_index, context),
                 LinkAction(ref operation) =>
                     on_link_action(self, &mut updated, operation, context),
                 Load(ref file, search_path) =>
@@ -280,6 +332,8 @@ impl App {
                     on_map(self, target, remain, mapped_operation),
                 Mark(name, key) =>
                     on_mark(self, &mut updated, name, key),
+                MarkDuplicates(delete) =>
+                    on_mark_duplicates(self, &mut updated, delete),
                 Meow =>
                     on_meow(self, &mut updated),
                 Message(message, keep) =>
@@ -296,14 +350,20 @@ impl App {
                     on_operate_file(self, file_operation),
                 Page(page) =>
                     on_page(self, &mut updated, page),
+                Pan(dx, dy) =>
+                    on_pan(self, dx, dy),
                 PdfIndex(r#async, read_operations, search_path, ref command_line, fmt, ref separator) =>
                     on_pdf_index(self, r#async, read_operations, search_path, command_line, fmt, separator.as_ref().map(String::as_str)),
+                PickColor(coord, format, selection) =>
+                    on_pick_color(self, coord, format, selection, context),
                 PreFetch(pre_fetch_serial) =>
                     on_pre_fetch(self, pre_fetch_serial),
                 Previous(count, ignore_views, move_by, wrap, remember) =>
                     on_previous(self, &mut updated, &mut to_end, count, ignore_views, move_by, wrap, remember),
                 Pointer(visibility) =>
                     on_pointer(self, visibility),
+                PointerMotion(coord) =>
+                    on_pointer_motion(self, &mut updated, coord),
                 PopCount =>
                     on_pop_count(self),
                 Pull =>
@@ -318,18 +378,26 @@ impl App {
                     on_push_clipboard(self, selection, as_operation, meta, force, show),
                 PushDirectory(file, meta, force) =>
                     on_push_directory(self, &mut updated, file.expand(), meta, force),
+                PushFeed(source, meta, force, show) =>
+                    on_push_feed(self, source.to_string(), meta, force, show, feed::Format::Feed),
                 PushImage(file, meta, force, show, expand_level) =>
                     on_push_image(self, &mut updated, file.expand(), meta, force, show, expand_level, None),
+                PushList(source, meta, force, show) =>
+                    on_push_feed(self, source.to_string(), meta, force, show, feed::Format::List),
                 PushMessage(message, meta, show) =>
                     on_push_message(self, &mut updated, message, meta, show),
                 PushMemory(buf, meta, show) =>
                     on_push_memory(self, &mut updated, buf, meta, show),
+                PushPage(url, meta, force, show, max_pages) =>
+                    on_push_page(self, url, meta, force, show, max_pages),
                 PushPdf(file, meta, force, show) =>
                     on_push_pdf(self, &mut updated, file.expand(), meta, force, show, None),
                 PushSibling(next, clear, meta, force, show) =>
                     on_push_sibling(self, &mut updated, next, clear, meta, force, show),
-                PushURL(url, meta, force, show, entry_type) =>
-                    on_push_url(self, &mut updated, url, meta, force, show, entry_type),
+                PushURL(url, meta, force, show, entry_type, referer) =>
+                    on_push_url(self, &mut updated, url, meta, force, show, entry_type, referer),
+                PushVideo(file, meta, force, show) =>
+                    on_push_video(self, &mut updated, file.expand(), meta, force, show, None),
                 Query(operation, caption) =>
                     on_query(self, &mut updated, operation, caption),
                 Queue(operation, times) =>
@@ -340,16 +408,24 @@ impl App {
                     on_record(self, minimum_move, position, key),
                 RecordPre(op, minimum_move) =>
                     on_record_pre(self, &op, minimum_move, context),
+                Redo(count) =>
+                    on_redo(self, &mut updated, count),
                 Refresh(image) =>
                     on_refresh(self, &mut updated, image),
+                RemoteCache(sweep, patterns) =>
+                    on_remote_cache(self, sweep, patterns),
                 RemoveEffects =>
                     on_remove_effects(self, &mut updated),
                 ResetFocus =>
                     on_reset_focus(self),
                 ResetScrolls(to_end) =>
                     on_reset_scrolls(self, to_end),
+                RetryFailed =>
+                    on_retry_failed(self),
                 Save(ref path, ref sources, freeze) =>
                     on_save(self, path, sources, freeze),
+                ScrapeRule(ref pattern, ref source, next, regex) =>
+                    on_scrape_rule(self, pattern, source.as_ref().map(String::as_str), next, regex),
                 SearchText(text, backward, color) =>
                     on_search_text(self, &mut updated, text, backward, color),
                 SetEnv(name, value) =>
@@ -364,6 +440,8 @@ impl App {
                     on_show(self, &mut updated, count, ignore_views, move_by),
                 ShowCommandLine(ref initial) =>
                     on_show_command_line(self, initial),
+                ShowStatistics(ref key) =>
+                    on_show_statistics(self, key),
                 Shuffle(fix_current) =>
                     on_shuffle(self, &mut updated, fix_current),
                 Sort(fix_current, sort_key, reverse) =>
@@ -372,6 +450,8 @@ impl App {
                     on_sorter(self, &mut updated, fix_current, command, reverse),
                 TellRegion(left, top, right, bottom, button) =>
                     on_tell_region(self, left, top, right, bottom, &button),
+                Text(text, cell_index) =>
+                    on_text(self, &mut updated, text, cell_index, context),
                 Timer(name, op, interval, repeat, r#async) =>
                     on_timer(self, name, op, interval, repeat, r#async),
                 UIAction(action_type) =>
@@ -388,10 +468,14 @@ impl App {
                     on_unmark(self, &target),
                 Update(new_updated) =>
                     ok!(updated = new_updated),
+                UpdateHashes =>
+                    on_update_hashes(self),
                 UpdateUI =>
                     panic!("WTF"),
                 UpdateOption(ref option_name, ref updater) =>
                     on_update_option(self, &mut updated, option_name, updater),
+                UpdateStatistics =>
+                    on_update_statistics(self),
                 User(ref data) =>
                     on_user(self, data),
                 Views(cols, rows, ignore_views) =>
@@ -406,6 +490,8 @@ impl App {
                     on_with_message(self, &mut updated, message, *op, context),
                 Write(ref path, ref index) =>
                     on_write(self, path, index),
+                Zoom(out, step) =>
+                    on_zoom(self, &mut updated, out, step, context),
             };
             if let Err(err) = operated {
                 puts_error!(err, "operation" => operation_name);
@@ -413,6 +499,10 @@ impl App {
             }
         }
 
+        if let Some(snapshots) = undo_snapshots {
+            self.record_undo(operation_name.trim(), snapshots);
+        }
+
         updated.counter |= count != self.counter.peek();
 
         if before_fly_leaves != self.paginator.fly_leaves() {
@@ -449,6 +539,7 @@ impl App {
         }
 
         if updated.pointer {
+            self.gui.hide_loupe();
             self.send_lazy_draw(None, to_end);
             if !updated.message {
                 self.update_message(None, false);
@@ -473,8 +564,11 @@ impl App {
 
         if updated.image || updated.image_options || updated.size {
             self.fire_event(&EventName::ShowImagePre);
-            let (showed, original_image_size, fit_image_size) = timeit!("show_image" => self.show_image(to_end, updated.target_regions.clone()));
+            let (showed, original_image_size, fit_image_size) = timeit!("show_image" => self.show_image(to_end, updated.target_regions.clone(), updated.scroll_anchor));
             self.on_image_updated(original_image_size, fit_image_size);
+            self.update_statistics_overlay();
+            let keys: Vec<Key> = (0 .. self.gui.len()).filter_map(|delta| self.current_with(delta as isize).map(|(entry, _)| entry.key.clone())).collect();
+            self.original_image.retain(&keys);
             self.update_watcher();
             if showed {
                 self.fire_event(&EventName::ShowImage);
@@ -482,6 +576,7 @@ impl App {
         }
 
         if updated.image || updated.image_options || updated.label || updated.message || updated.remote | updated.size {
+            self.pixel_env.export();
             self.update_label(updated.image, false);
         }
     }
@@ -537,6 +632,10 @@ impl App {
             pages: self.entries.len(),
             real_pages: self.entries.real_len(),
             current_page: self.current().map(|it| it.1 + 1),
+            current_phash: self.current().and_then(|it| self.phashes.get(&it.0.key)),
+            phashes: self.phashes.clone(),
+            similarity_threshold: self.states.similarity_threshold,
+            statistics: self.statistics.clone(),
         }
     }
 
@@ -564,7 +663,52 @@ impl App {
 
     fn get_imaging(&self) -> Imaging {
         let cell_size = self.gui.get_cell_size(&self.states.view);
-        Imaging::new(cell_size, self.states.drawing.clone())
+        let mut drawing = self.states.drawing.clone();
+        drawing.fit_to = self.fit_to();
+        Imaging::new(cell_size, drawing)
+    }
+
+    fn undo_snapshot(&self, key: &Key) -> Snapshot {
+        Snapshot {
+            drawing: EntryDrawing::new(&self.states.drawing, self.zooms.get(key).cloned().filter(|_| self.states.remember_zoom)),
+            modifiers: self.cache.cherenkov_modifiers(key),
+        }
+    }
+
+    /** Snapshots of the entries in the cells **/
+    fn undo_snapshots(&self) -> Vec<(Key, Snapshot)> {
+        (0 .. self.gui.len()).filter_map(|delta| {
+            self.current_with(delta as isize).map(|(entry, _)| (entry.key.clone(), self.undo_snapshot(&entry.key)))
+        }).collect()
+    }
+
+    fn record_undo(&mut self, label: &str, befores: Vec<(Key, Snapshot)>) {
+        for (key, before) in befores {
+            if before != self.undo_snapshot(&key) {
+                self.undo_history.record(key, label, before, self.states.undo_depth);
+            }
+        }
+    }
+
+    fn restore_snapshot(&mut self, key: &Key, snapshot: Snapshot) {
+        let entry_drawing = snapshot.drawing;
+        {
+            let drawing = &mut self.states.drawing;
+            drawing.adjustment = entry_drawing.adjustment;
+            drawing.clipping = entry_drawing.clipping;
+            drawing.horizontal_flip = entry_drawing.horizontal_flip;
+            drawing.rotation = entry_drawing.rotation;
+            drawing.vertical_flip = entry_drawing.vertical_flip;
+        }
+        let remember_zoom = self.states.remember_zoom;
+        if let Some(zoom) = self.zooms.get_mut(key).filter(|_| remember_zoom) {
+            *zoom = entry_drawing.fit_to;
+        } else {
+            self.states.drawing.fit_to = entry_drawing.fit_to;
+        }
+
+        let imaging = self.get_imaging();
+        self.cache.set_cherenkov_modifiers(key, &imaging, snapshot.modifiers);
     }
 
     fn reset_view(&mut self) {
@@ -642,7 +786,7 @@ impl App {
     /**
      * @return (Original size, Fit size)
      */
-    fn show_image(&mut self, to_end: bool, target_regions: Option<Vec<Option<Region>>>) -> (bool, Option<Size>, Option<Size>) {
+    fn show_image(&mut self, to_end: bool, target_regions: Option<Vec<Option<Region>>>, scroll_anchor: Option<ScrollAnchor>) -> (bool, Option<Size>, Option<Size>) {
         let mut original_image_size = None;
         let mut fit_image_size = None;
         let imaging = self.get_imaging();
@@ -655,10 +799,15 @@ impl App {
 
         for (index, cell) in self.gui.cells(self.states.reverse).enumerate() {
             if let Some((entry, _)) = self.current_with(index as isize) {
-                let image_buffer = self.cache.get_image_buffer(&entry, &imaging);
+                self.extract_video_frame(&entry);
+                let image_buffer = match self.cache.get_image_buffer(&entry, &imaging) {
+                    Ok(ImageBuffer::Static(buffer)) if self.states.compare.is_overlay() =>
+                        Ok(ImageBuffer::Static(self.compare_image_buffer(index, buffer, &imaging))),
+                    it => it,
+                };
                 match image_buffer {
                     Ok(image_buffer) => {
-                        let scale = cell.draw(&image_buffer, imaging.cell_size, &self.states.drawing.fit_to);
+                        let scale = cell.draw(&image_buffer, imaging.cell_size, &imaging.drawing.fit_to);
                         if base_scale.is_none() {
                             base_scale = scale;
                         }
@@ -679,10 +828,12 @@ impl App {
 
         self.current_base_scale = base_scale;
 
-        if self.states.drawing.fit_to.is_scrollable() {
+        if imaging.drawing.fit_to.is_scrollable() {
             self.secondary_tx.send(Operation::UpdateUI).unwrap();
             let op = if let Some(target_regions) = target_regions {
                 Operation::MakeVisibles(target_regions)
+            } else if let Some(scroll_anchor) = scroll_anchor {
+                Operation::AnchorScrolls(scroll_anchor)
             } else {
                 Operation::ResetScrolls(to_end)
             };
@@ -706,6 +857,16 @@ impl App {
         (showed, original_image_size, fit_image_size)
     }
 
+    /** `fit-to` for the current entry, with the remembered zoom **/
+    fn fit_to(&self) -> FitTo {
+        if self.states.remember_zoom {
+            if let Some(fit_to) = self.current().and_then(|(entry, _)| self.zooms.get(&entry.key).cloned()) {
+                return fit_to;
+            }
+        }
+        self.states.drawing.fit_to.clone()
+    }
+
     fn update_counter_env(&mut self, do_pop: bool) {
         let count = if do_pop {
             self.counter.take()
@@ -727,6 +888,56 @@ impl App {
         self.current_env_keys = new_keys;
     }
 
+    /** Compare each cell with the first cell. On the single cell view, the next entry is compared **/
+    fn compare_image_buffer(&mut self, index: usize, buffer: StaticImageBuffer, imaging: &Imaging) -> StaticImageBuffer {
+        let single = self.gui.len() == 1;
+        if !single && index == 0 {
+            return buffer;
+        }
+
+        let counterpart = self.current_with(if single { 1 } else { 0 }).and_then(|(entry, _)| {
+            match self.cache.get_image_buffer(&entry, imaging) {
+                Ok(ImageBuffer::Static(it)) => Some(it),
+                _ => None,
+            }
+        });
+        if_let_some!(counterpart = counterpart, buffer);
+
+        let ratio = f64!(self.states.compare_ratio) / 100.0;
+        let (base, other) = if single { (&buffer, &counterpart) } else { (&counterpart, &buffer) };
+        compare::compose(base, other, self.states.compare, self.states.compare_operator, ratio)
+    }
+
+    fn extract_video_frame(&self, entry: &Entry) {
+        if let EntryContent::Video(ref video, index) = entry.content {
+            let (tx, key) = (self.secondary_tx.clone(), entry.key.clone());
+            let result = video.extract(index, &self.process_manager, move || {
+                tx.send(Operation::ClearCacheEntry(key)).unwrap();
+            });
+            if let Err(err) = result {
+                puts_error!(err, "at" => "extract_video_frame", "for" => entry.display_path());
+            }
+        }
+    }
+
+    /** Show the statistics of the current entry. They are computed in background if not yet **/
+    fn update_statistics_overlay(&mut self) {
+        if !self.states.statistics {
+            self.gui.hide_statistics();
+            return;
+        }
+
+        if_let_some!((entry, _) = self.current(), self.gui.hide_statistics());
+
+        if let Some(statistics) = self.statistics.get(&entry.key) {
+            self.gui.show_statistics(&statistics::render(&statistics));
+            return;
+        }
+
+        self.gui.hide_statistics();
+        self.fetcher.new_statistics_target(VecDeque::from(vec![entry]), true);
+    }
+
     /* Returns true if message is updated */
     fn update_message(&mut self, message: Option<String>, keep: bool) -> bool {
         if !self.states.spawned {
@@ -788,7 +999,12 @@ impl App {
                     envs.push((o!("file"), o!(path_to_str(&**pdf_file))));
                     envs.push((o!("archive_page"), s!(index + 1)));
                 },
-                Message(ref message) => {
+                Video(ref video, index) => {
+                    envs.push((o!("type"), o!("video")));
+                    envs.push((o!("file"), o!(path_to_str(&video.path))));
+                    envs.push((o!("archive_page"), s!(index + 1)));
+                },
+                Message(ref message, _) => {
                     envs.push((o!("entry_message"), o!(message)));
                 }
             }
@@ -840,7 +1056,7 @@ impl App {
             envs_sub.push((o!("flags"), {
                 use self::FitTo::*;
                 let mut text = o!("");
-                text.push(match self.states.drawing.fit_to {
+                text.push(match self.fit_to() {
                     Cell => 'c',
                     Height => 'H',
                     Original => 'O',
@@ -12,48 +12,68 @@ use std::sync::Arc;
 use std::thread::spawn;
 use std::time::Duration;
 
+use gdk_pixbuf::PixbufExt;
 use gtk::prelude::*;
 use log::trace;
 use maplit::{convert_args, hashmap};
 use rand::distributions::{Distribution, Uniform};
 
+use crate::app_path;
 use crate::archive;
 use crate::chainer;
 use crate::cherenkov::Operator;
+use crate::cherenkov::annotation;
+use crate::cherenkov::effect::Effect;
 use crate::cherenkov::fill::Shape;
 use crate::clipboard;
 use crate::color::Color;
 use crate::command_line;
 use crate::config::DEFAULT_CONFIG;
 use crate::controller;
+use crate::crop;
 use crate::editor;
 use crate::entry::filter::expression::Expr as FilterExpr;
 use crate::entry::{self, Meta, SearchKey, Entry, EntryContent, EntryType};
-use crate::errors::{AppResultU, AppError};
+use crate::errors::{AppResult, AppResultU, AppError};
 use crate::events::EventName;
+use crate::feed;
 use crate::expandable::{Expandable, expand_all};
 use crate::file_extension::get_entry_type_from_filename;
 use crate::filer;
-use crate::gui::Direction;
+use crate::inspector::{self, ColorFormat};
+use crate::gui::{Direction, ScrollAnchor};
 use crate::key::Key;
 use crate::logger;
+use crate::loupe;
 use crate::operation::option::{OptionName, OptionUpdater};
-use crate::operation::{CherenkovParameter, ClipboardSelection, MappingTarget, MoveBy, Operation, OperationContext, ReadAs, self, SortKey, UIActionType};
+use crate::operation::{CherenkovParameter, ClipboardSelection, CropParameter, MappingTarget, MoveBy, Operation, OperationContext, ReadAs, self, SortKey, UIActionType};
 use crate::option::user_switch::DummySwtich;
+use crate::phash;
 use crate::poppler::{PopplerDocument, self};
+use crate::remote_cache::curl_options::HostOptionName;
+use crate::remote_cache::sweeper;
+use crate::scraper;
 use crate::script;
 use crate::session::{Session, write_sessions};
 use crate::shell_filter;
 use crate::shellexpand_wrapper as sh;
+use crate::size::{Coord, CoordPx};
 use crate::state;
 use crate::util::num::range_contains;
 use crate::util::path::{path_to_str, path_to_string};
 use crate::util::string::prefixed_lines;
+use crate::util::uri::{decode_data_uri, file_uri_to_path, is_data_uri, is_url};
+use crate::video;
 
 use crate::app::*;
 
 
 
+pub fn on_anchor_scrolls(app: &mut App, anchor: &ScrollAnchor) -> AppResultU {
+    app.gui.anchor_scrolls(anchor);
+    Ok(())
+}
+
 pub fn on_app_event(app: &mut App, updated: &mut Updated, event_name: &EventName, context: &HashMap<String, String>) -> AppResultU {
     use self::EventName::*;
 
@@ -91,6 +111,17 @@ pub fn on_apng<T: AsRef<Path>>(app: &mut App, path: &T, length: u8) -> AppResult
     app.cache.generate_animation_png(&entry, &imaging, length, path)
 }
 
+pub fn on_bake(app: &mut App, file_operation: &filer::FileOperation, cell_index: usize) -> AppResultU {
+    if_let_some!((entry, _) = app.current_with(cell_index as isize), Ok(()));
+    let imaging = app.get_imaging();
+    app.cache.bake(&entry, &imaging, file_operation)
+}
+
+pub fn on_cancel_download(app: &mut App, target: String) -> AppResultU {
+    app.remote_cache.cancel(target);
+    Ok(())
+}
+
 pub fn on_chain(target: chainer::Target) -> AppResultU {
     chainer::register(target);
     Ok(())
@@ -159,12 +190,14 @@ pub fn on_clear(app: &mut App, updated: &mut Updated) -> AppResultU {
     app.entries.clear();
     app.paginator.reset();
     app.cache.clear();
+    app.statistics.clear();
     updated.image = true;
     Ok(())
 }
 
 pub fn on_clear_cache_entry(app: &mut App, updated: &mut Updated, key: &entry::Key) -> AppResultU {
     app.cache.clear_each_entry(key);
+    app.statistics.remove(key);
     for (index, _) in app.gui.cells(app.states.reverse).enumerate() {
         if let Some((ref entry, _)) = app.current_with(index as isize) {
             if entry.key == *key {
@@ -208,6 +241,36 @@ pub fn on_count_digit(app: &mut App, updated: &mut Updated, digit: u8) -> AppRes
     Ok(())
 }
 
+pub fn on_crop(app: &mut App, parameter: &CropParameter, context: Option<OperationContext>) -> AppResultU {
+    let (mut region, cell_index) = extract_region_from_context(context)
+        .or_else(|| parameter.region.map(|it| (it, parameter.cell_index)))
+        .unwrap_or_else(|| (Region::full(), parameter.cell_index));
+
+    if let Some(clipping) = app.states.drawing.clipping {
+        region = region.unclipped(&clipping);
+    }
+    let region = region.on_original_image(&app.states.drawing);
+
+    if_let_some!((entry, _) = app.current_with(cell_index as isize), Ok(()));
+
+    // Not scaled, not modified
+    let drawing = state::Drawing { animation: false, fit_to: FitTo::Original, ..state::Drawing::default() };
+    let imaging = Imaging::new(app.gui.get_cell_size(&app.states.view), drawing);
+    let pixbuf = match entry::image::get_image_buffer(&entry.content, &imaging)? {
+        ImageBuffer::Static(buffer) => buffer.get_pixbuf(),
+        _ => return Err(AppError::Fixed("Not static image")),
+    };
+
+    if_let_some!(rect = crop::crop_rect(pixbuf.get_width(), pixbuf.get_height(), &region, parameter.fixed), Err(AppError::Fixed("Empty region")));
+    let cropped = crop::crop(&pixbuf, &rect);
+    let name = crop::file_name(&entry.page_filename());
+    parameter.file_operation.execute_with_writer(&name, |path| {
+        crop::save(&cropped, &path)?;
+        puts_event!("crop", "path" => p!(path));
+        Ok(())
+    })
+}
+
 pub fn on_define_switch(app: &mut App, name: String, values: Vec<Vec<String>>, context: Option<OperationContext>) -> AppResultU {
     let op = app.user_switches.register(name, values)?;
     app.operate(op, context);
@@ -235,9 +298,14 @@ pub fn on_delete(app: &mut App, updated: &mut Updated, expr: FilterExpr) -> AppR
 }
 
 pub fn on_detect_eyes(app: &mut App, parameter: CherenkovParameter) -> AppResultU {
+    use crate::cherenkov::eye_detector::{DEFAULT_CASCADE, detect_eyes};
+
     let mut image = vec![];
     app.gui.save(&mut image, 0)?;
-    crate::cherenkov::eye_detector::detect_eyes(app.secondary_tx.clone(), parameter, image);
+    let config = &app.states.eye_detector;
+    let cascade = config.cascade.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_CASCADE));
+    let cascade = app_path::search_path(&cascade, &app.states.path_list);
+    detect_eyes(app.secondary_tx.clone(), parameter, image, config.backend, cascade, config.command.clone());
     Ok(())
 }
 
@@ -259,6 +327,31 @@ pub fn on_editor(app: &mut App, editor_command: Vec<Expandable>, files: &[Expand
     Ok(())
 }
 
+pub fn on_effect(app: &mut App, updated: &mut Updated, effect: Effect, shape: Shape, region: Option<Region>, cell_index: usize, context: Option<OperationContext>) -> AppResultU {
+    use crate::cherenkov::{Modifier, Che};
+
+    let (mut region, cell_index) = extract_region_from_context(context)
+        .or_else(|| region.map(|it| (it, cell_index)))
+        .unwrap_or_else(|| (Region::full(), cell_index));
+
+    if let Some(clipping) = app.states.drawing.clipping {
+        region = region.unclipped(&clipping);
+    }
+
+    if let Some((entry, _)) = app.current_with(cell_index as isize) {
+        let imaging = app.get_imaging();
+        app.cache.cherenkov1(
+            &entry,
+            &imaging,
+            Modifier {
+                search_highlight: false,
+                che: Che::Effect(effect, shape, region),
+            });
+        updated.image = true;
+    }
+    Ok(())
+}
+
 pub fn on_error(app: &mut App, updated: &mut Updated, error: String) -> AppResultU {
     if app.error_loop_detector.in_loop(&error) {
         return Ok(());
@@ -300,6 +393,32 @@ pub fn on_expand(app: &mut App, updated: &mut Updated, recursive: bool, base: Op
     Ok(())
 }
 
+pub fn on_fetch_offline(app: &mut App, updated: &mut Updated) -> AppResultU {
+    if app.states.offline {
+        return Err(AppError::Fixed("Offline mode is enabled"));
+    }
+
+    let placeholders: Vec<(String, Option<Meta>, Option<EntryType>)> = app.entries.iter().filter_map(|entry| {
+        match (&entry.content, &entry.url) {
+            (EntryContent::Message(_, entry_type), Some(url)) => Some((o!(url.as_str()), entry.meta.clone(), *entry_type)),
+            _ => None,
+        }
+    }).collect();
+
+    if placeholders.is_empty() {
+        return Ok(());
+    }
+
+    app.entries.refetch(placeholders.iter().map(|it| it.0.clone()).collect());
+
+    for (url, meta, entry_type) in placeholders {
+        let buffered = app.remote_cache.fetch(url, meta, false, false, entry_type, None);
+        push_buffered(app, updated, buffered)?;
+    }
+
+    Ok(())
+}
+
 pub fn on_file_changed(app: &mut App, updated: &mut Updated, path: &Path) -> AppResultU {
     env::set_var(constant::env_name("CHANGED_FILE"), path_to_string(&path));
     app.fire_event(&EventName::FileChanged);
@@ -548,12 +667,16 @@ pub fn on_initial_process(app: &mut App, entries: Vec<command_line::Entry>, shuf
 pub fn on_initialized(app: &mut App) -> AppResultU {
     app.secondary_tx.send(Operation::UpdateUI).unwrap();
 
-    app.gui.register_ui_events(app.states.skip_resize_window, app.states.time_to_hide_pointer, &app.primary_tx);
+    app.gui.register_ui_events(app.states.skip_resize_window, app.states.time_to_hide_pointer, app.states.drag_to_pan, app.states.loupe.enabled, app.states.pixel_inspector, &app.primary_tx);
     app.update_style();
     app.update_label(true, true);
     app.gui.show();
     app.update_status_bar_height(); // XXX Must Do after `gui.show`
     app.gui.refresh_status_bar_width();
+
+    if app.states.remote_cache_max_age.is_some() || app.states.remote_cache_max_size.is_some() {
+        app.secondary_tx.send(Operation::RemoteCache(true, vec![])).unwrap();
+    }
     Ok(())
 }
 
@@ -587,6 +710,7 @@ pub fn on_jump(app: &mut App, updated: &mut Updated, name: &str, load: bool) ->
         Image => Operation::PushImage(Expandable::new(path.clone()), None, false, true, None),
         Archive => Operation::PushArchive(Expandable::new(path.clone()), None, false, true),
         PDF => Operation::PushPdf(Expandable::new(path.clone()), None, false, true),
+        Video => Operation::PushVideo(Expandable::new(path.clone()), None, false, true),
         _ => return Err(AppError::Fixed("Entry not found")),
     };
 
@@ -630,6 +754,40 @@ pub fn on_lazy_draw(app: &mut App, updated: &mut Updated, to_end: &mut bool, ser
     Ok(())
 }
 
+pub fn on_line(app: &mut App, updated: &mut Updated, mut line: annotation::Line, arrow: bool, cell_index: usize, context: Option<OperationContext>) -> AppResultU {
+    use crate::cherenkov::{Modifier, Che};
+
+    let cell_index = if let Some((start, end, cell_index)) = extract_line_from_context(context) {
+        line.points = vec![start, end];
+        cell_index
+    } else {
+        cell_index
+    };
+
+    if line.points.len() < 2 {
+        return Err(AppError::Fixed("Two or more points are required"));
+    }
+
+    if let Some(clipping) = app.states.drawing.clipping {
+        for it in &mut line.points {
+            *it = it.unclipped(&clipping);
+        }
+    }
+
+    if let Some((entry, _)) = app.current_with(cell_index as isize) {
+        let imaging = app.get_imaging();
+        app.cache.cherenkov1(
+            &entry,
+            &imaging,
+            Modifier {
+                search_highlight: false,
+                che: if arrow { Che::Arrow(line) } else { Che::Line(line) },
+            });
+        updated.image = true;
+    }
+    Ok(())
+}
+
 pub fn on_link_action(app: &mut App, updated: &mut Updated, operation: &[String], context: Option<OperationContext>) -> AppResultU {
     use crate::entry::EntryContent::*;
 
@@ -724,6 +882,34 @@ pub fn on_mark(app: &mut App, updated: &mut Updated, name: String, key: Option<(
     Ok(())
 }
 
+pub fn on_mark_duplicates(app: &mut App, updated: &mut Updated, delete: bool) -> AppResultU {
+    use crate::entry::filter::expression::{EBool, EBVariable};
+
+    let entries: HashMap<entry::Key, Arc<Entry>> = app.entries.iter().map(|it| (it.key.clone(), Arc::clone(it))).collect();
+    let keys: Vec<entry::Key> = app.entries.iter().map(|it| it.key.clone()).collect();
+    let size_of = |key: &entry::Key| {
+        let entry = &entries[key];
+        entry.info.lazy(&entry.content, |it| (it.dimensions.map(|it| it.dimensions()), it.file_size))
+    };
+
+    let mut duplicates = HashSet::new();
+    for group in app.phashes.groups(&keys, app.states.similarity_threshold) {
+        if let Some(largest) = group.iter().max_by_key(|it| size_of(it)).cloned() {
+            duplicates.extend(group.into_iter().filter(|it| *it != largest));
+        }
+    }
+
+    let message = format!("{} duplicates", duplicates.len());
+    app.phashes.set_duplicates(duplicates);
+
+    if delete {
+        on_delete(app, updated, FilterExpr::Boolean(EBool::Variable(EBVariable::Duplicated)))?;
+    }
+
+    updated.message = app.update_message(Some(message), false);
+    Ok(())
+}
+
 #[allow(unused_variables)]
 pub fn on_meow(app: &mut App, updated: &mut Updated) -> AppResultU {
     Ok(())
@@ -800,7 +986,14 @@ pub fn on_operate_file(app: &mut App, file_operation: &filer::FileOperation) ->
                 let png = PopplerDocument::new_from_file(&**path).nth_page(index).get_png_data(&file_operation.size);
                 file_operation.execute_with_buffer(png.as_ref(), &name)?
             },
-            Message(ref message) =>
+            Video(ref video, index) => {
+                let name = entry.page_filename();
+                let mut content = vec![];
+                let frame = video.frame(index)?.ok_or(AppError::Fixed("The frame is not extracted yet"))?;
+                File::open(frame)?.read_to_end(&mut content)?;
+                file_operation.execute_with_buffer(&content, &name)?
+            },
+            Message(ref message, _) =>
                 return Err(AppError::Standard(o!(message)))
         };
         let text = format!("{:?}", file_operation);
@@ -816,6 +1009,11 @@ pub fn on_page(app: &mut App, updated: &mut Updated, page: usize) -> AppResultU
     Ok(())
 }
 
+pub fn on_pan(app: &mut App, dx: f64, dy: f64) -> AppResultU {
+    app.gui.pan_views(dx, dy);
+    Ok(())
+}
+
 pub fn on_pdf_index(app: &mut App, r#async: bool, read_operations: bool, search_path: bool, command_line: &[Expandable], fmt: poppler::index::Format, separator: Option<&str>) -> AppResultU {
     if_let_some!((entry, _) = app.current(), Ok(()));
     if let EntryContent::Pdf(ref path, _) = entry.content {
@@ -866,11 +1064,56 @@ pub fn on_previous(app: &mut App, updated: &mut Updated, to_end: &mut bool, coun
     Ok(())
 }
 
+pub fn on_pick_color(app: &mut App, coord: Option<CoordPx>, format: ColorFormat, selection: ClipboardSelection, context: Option<OperationContext>) -> AppResultU {
+    // Key inputs have no size
+    let context_coord = context.map(|it| it.mapped).and_then(|it| if let Mapped::Input(coord, _) = it { Some(coord) } else { None }).filter(|it| 0 < it.width);
+
+    if_let_some!(coord = coord.or(context_coord).or(app.pointer_at), Err(AppError::Fixed("No pointer position")));
+    let on_load = Operation::PickColor(Some(coord), format, selection);
+    if_let_some!((pixbuf, position) = get_original_image_at(app, &coord, on_load)?, Err(AppError::Fixed("Not on image")));
+    if_let_some!(pixbuf = pixbuf, Ok(()));
+    if_let_some!(pixel = inspector::pick(&pixbuf, &position), Err(AppError::Fixed("Not on image")));
+
+    app.pixel_env.update(Some(pixel));
+    app.pixel_env.export();
+
+    let text = pixel.format(format);
+    clipboard::store_text(selection, &text);
+    puts_event!("pick_color", "x" => s!(pixel.x), "y" => s!(pixel.y), "color" => text);
+    Ok(())
+}
+
 pub fn on_pointer(app: &mut App, visibility: bool) -> AppResultU {
     app.gui.set_pointer_visibility(visibility);
     Ok(())
 }
 
+pub fn on_pointer_motion(app: &mut App, updated: &mut Updated, coord: Option<CoordPx>) -> AppResultU {
+    if let Some(coord) = coord {
+        app.pointer_at = Some(coord);
+    }
+    if_let_some!(coord = app.pointer_at, Ok(()));
+
+    // The failure has been reported when the image was decoded. Treat it as no image here
+    let found = get_original_image_at(app, &coord, Operation::PointerMotion(None)).ok().flatten();
+    let found = found.and_then(|(pixbuf, position)| pixbuf.map(|it| (it, position)));
+
+    if app.states.loupe.enabled {
+        if let Some((ref pixbuf, position)) = found {
+            let loupe = loupe::render(pixbuf, position, &app.states.drawing, &app.states.loupe);
+            app.gui.show_loupe(&loupe, &coord);
+        } else {
+            app.gui.hide_loupe();
+        }
+    }
+
+    if app.states.pixel_inspector {
+        updated.label |= app.pixel_env.update(found.and_then(|(pixbuf, position)| inspector::pick(&pixbuf, &position)));
+    }
+
+    Ok(())
+}
+
 pub fn on_pop_count(app: &mut App) -> AppResultU {
     app.counter.pop()?;
     Ok(())
@@ -887,8 +1130,8 @@ pub fn on_push_count(app: &mut App) -> AppResultU {
 }
 
 pub fn on_push(app: &mut App, updated: &mut Updated, path: String, meta: Option<Meta>, force: bool, show: bool) -> AppResultU {
-    if is_url(&path) {
-        app.secondary_tx.send(Operation::PushURL(path, meta, force, show, None))?;
+    if is_url(&path) || is_data_uri(&path) {
+        app.secondary_tx.send(Operation::PushURL(path, meta, force, show, None, None))?;
         return Ok(())
     }
 
@@ -913,6 +1156,11 @@ pub fn on_push_directory(app: &mut App, updated: &mut Updated, file: PathBuf, me
     push_buffered(app, updated, buffered)
 }
 
+pub fn on_push_feed(app: &mut App, source: String, meta: Option<Meta>, force: bool, show: bool, format: feed::Format) -> AppResultU {
+    feed::push(app.secondary_tx.clone(), app.feeds.clone(), source, format, app.states.curl_options.clone(), meta, force, show);
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn on_push_image(app: &mut App, updated: &mut Updated, file: PathBuf, meta: Option<Meta>, force: bool, show: bool, expand_level: Option<u8>, url: Option<String>) -> AppResultU {
     let buffered = app.sorting_buffer.push_with_reserve(
@@ -921,7 +1169,7 @@ pub fn on_push_image(app: &mut App, updated: &mut Updated, file: PathBuf, meta:
 }
 
 pub fn on_push_message(app: &mut App, updated: &mut Updated, message: String, meta: Option<Meta>, show: bool) -> AppResultU {
-    let buffered = app.sorting_buffer.push_with_reserve(QueuedOperation::PushMessage(message, meta, show));
+    let buffered = app.sorting_buffer.push_with_reserve(QueuedOperation::PushMessage(message, meta, show, None, None));
     push_buffered(app, updated, buffered)
 }
 
@@ -945,6 +1193,8 @@ pub fn on_push_path<T: AsRef<Path>>(app: &mut App, updated: &mut Updated, path:
                         return on_push_archive(app, &path, meta, force, show, None),
                     EntryType::PDF =>
                         return on_push_pdf(app, updated, path.to_path_buf(), meta, force, show, None),
+                    EntryType::Video =>
+                        return on_push_video(app, updated, path.to_path_buf(), meta, force, show, None),
                     _ =>
                         ()
                 }
@@ -959,6 +1209,11 @@ pub fn on_push_path<T: AsRef<Path>>(app: &mut App, updated: &mut Updated, path:
     }
 }
 
+pub fn on_push_page(app: &mut App, url: String, meta: Option<Meta>, force: bool, show: bool, max_pages: usize) -> AppResultU {
+    scraper::scrape(app.secondary_tx.clone(), app.states.scrape_rules.clone(), url, app.states.curl_options.clone(), meta, force, show, max_pages);
+    Ok(())
+}
+
 pub fn on_push_pdf(app: &mut App, updated: &mut Updated, file: PathBuf, meta: Option<Meta>, force: bool, show: bool, url: Option<String>) -> AppResultU {
     let document = PopplerDocument::new_from_file(&file);
     let n_pages = document.n_pages();
@@ -995,7 +1250,9 @@ pub fn on_push_sibling(app: &mut App, updated: &mut Updated, next: bool, clear:
                 find_sibling(path, next),
             Archive(ref path, _) | Pdf(ref path, _) =>
                 find_sibling(path.as_ref(), next),
-            Memory(_, _) | Message(_) =>
+            Video(ref video, _) =>
+                find_sibling(&video.path, next),
+            Memory(_, _) | Message(_, _) =>
                 None,
         }
     });
@@ -1009,8 +1266,33 @@ pub fn on_push_sibling(app: &mut App, updated: &mut Updated, next: bool, clear:
     Ok(())
 }
 
-pub fn on_push_url(app: &mut App, updated: &mut Updated, url: String, meta: Option<Meta>, force: bool, show: bool, entry_type: Option<EntryType>) -> AppResultU {
-    let buffered = app.remote_cache.fetch(url, meta, force, show, entry_type);
+#[allow(clippy::too_many_arguments)]
+pub fn on_push_url(app: &mut App, updated: &mut Updated, url: String, meta: Option<Meta>, force: bool, show: bool, entry_type: Option<EntryType>, referer: Option<String>) -> AppResultU {
+    if is_data_uri(&url) {
+        let buf = decode_data_uri(&url)?;
+        return on_push_memory(app, updated, buf, meta, show);
+    }
+
+    if let Some(path) = file_uri_to_path(&url) {
+        return match entry_type {
+            Some(EntryType::Archive) => on_push_archive(app, &path, meta, force, show, None),
+            Some(EntryType::PDF) => on_push_pdf(app, updated, path, meta, force, show, None),
+            Some(EntryType::Video) => on_push_video(app, updated, path, meta, force, show, None),
+            Some(EntryType::Image) => on_push_image(app, updated, path, meta, force, show, None, None),
+            _ => on_push_path(app, updated, &path, meta, force, show),
+        };
+    }
+
+    let buffered = app.remote_cache.fetch(url, meta, force, show, entry_type, referer);
+    push_buffered(app, updated, buffered)
+}
+
+pub fn on_push_video(app: &mut App, updated: &mut Updated, file: PathBuf, meta: Option<Meta>, force: bool, show: bool, url: Option<String>) -> AppResultU {
+    let frames = app.states.video_frames;
+    let command = o!(app.states.video_frame_command.source());
+
+    let buffered = app.sorting_buffer.push_with_reserve(
+        QueuedOperation::PushVideoEntries(file, frames, command, meta, force, show, url));
     push_buffered(app, updated, buffered)
 }
 
@@ -1068,6 +1350,10 @@ pub fn on_record_pre(app: &mut App, operation: &[String], minimum_move: usize, c
     Ok(())
 }
 
+pub fn on_redo(app: &mut App, updated: &mut Updated, count: Option<usize>) -> AppResultU {
+    on_undo_or_redo(app, updated, count, true)
+}
+
 pub fn on_refresh(app: &mut App, updated: &mut Updated, image: bool) -> AppResultU {
     if image {
         let len = app.gui.len();
@@ -1083,6 +1369,35 @@ pub fn on_refresh(app: &mut App, updated: &mut Updated, image: bool) -> AppResul
     Ok(())
 }
 
+pub fn on_remote_cache(app: &mut App, sweep: bool, patterns: Vec<String>) -> AppResultU {
+    let limits = if sweep {
+        sweeper::Limits {
+            max_age: app.states.remote_cache_max_age.map(|it| Duration::from_secs(it as u64 * 24 * 60 * 60)),
+            max_size: app.states.remote_cache_max_size.map(|it| it as u64 * 1024 * 1024),
+        }
+    } else {
+        sweeper::Limits::default()
+    };
+
+    let in_use: HashSet<PathBuf> = app.entries.iter().filter(|it| it.url.is_some()).filter_map(|it| it.content.local_file_path()).collect();
+
+    spawn(move || {
+        match sweeper::sweep(&limits, &patterns, &in_use) {
+            Ok(usage) =>
+                puts_event!(
+                    "remote_cache/usage",
+                    "files" => s!(usage.files),
+                    "size" => s!(usage.size),
+                    "deleted_files" => s!(usage.deleted_files),
+                    "deleted_size" => s!(usage.deleted_size)),
+            Err(err) =>
+                puts_error!(err, "at" => "remote_cache/sweep"),
+        }
+    });
+
+    Ok(())
+}
+
 pub fn on_remove_effects(app: &mut App, updated: &mut Updated) -> AppResultU {
     if let Some((entry, _)) = app.current() {
         app.cache.uncherenkov(&entry.key);
@@ -1101,16 +1416,30 @@ pub fn on_reset_scrolls(app: &mut App, to_end: bool) -> AppResultU {
     Ok(())
 }
 
+pub fn on_retry_failed(app: &mut App) -> AppResultU {
+    app.remote_cache.retry_failed();
+    Ok(())
+}
+
 pub fn on_save(app: &mut App, path: &Path, sessions: &[Session], freeze: bool) -> AppResultU {
     let mut file = File::create(path)?;
     file.write_all(with_ouput_string!(out, write_sessions(app, sessions, freeze, out)).as_str().as_bytes())?;
     Ok(())
 }
 
+pub fn on_scrape_rule(app: &mut App, pattern: &str, source: Option<&str>, next: bool, regex: bool) -> AppResultU {
+    if let Some(source) = source {
+        app.states.scrape_rules.set(pattern, source, next, regex)
+    } else {
+        app.states.scrape_rules.unset(pattern);
+        Ok(())
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn on_scroll(app: &mut App, direction: Direction, scroll_size: f64, crush: bool, reset_at_end: bool, operation: &[String], reset_scrolls_1: Option<Direction>, context: Option<OperationContext>) -> AppResultU {
     let saved = app.counter.clone();
-    let scrolled = app.gui.scroll_views(direction, scroll_size, crush, app.counter.take(), reset_scrolls_1);
+    let scrolled = app.gui.scroll_views(direction, scroll_size, crush, app.counter.take(), reset_scrolls_1, app.states.compare != state::Compare::Off, app.states.smooth_scroll);
 
     if !scrolled && !operation.is_empty() {
         let op = Operation::parse_from_vec(operation)?;
@@ -1275,6 +1604,13 @@ pub fn on_show_command_line(app: &mut App, initial: &str) -> AppResultU {
     app.gui.show_command_line(initial, &app.secondary_tx)
 }
 
+pub fn on_show_statistics(app: &mut App, key: &entry::Key) -> AppResultU {
+    if app.current().map_or(false, |(entry, _)| entry.key == *key) {
+        app.update_statistics_overlay();
+    }
+    Ok(())
+}
+
 pub fn on_shuffle(app: &mut App, updated: &mut Updated, fix_current: bool) -> AppResultU {
     let serial = app.store();
     let app_info = app.app_info();
@@ -1297,6 +1633,24 @@ pub fn on_sort(app: &mut App, updated: &mut Updated, fix_current: bool, sort_key
     let serial = app.store();
     let app_info = app.app_info();
 
+    let ranks: Option<HashMap<entry::Key, u32>> = match sort_key {
+        Similarity => {
+            let current = app_info.current_phash.ok_or(AppError::Fixed("Current entry has not been hashed"))?;
+            Some(app.entries.iter().map(|it| {
+                let rank = app.phashes.get(&it.key).map(|hash| phash::distance(current, hash)).unwrap_or(u32::max_value());
+                (it.key.clone(), rank)
+            }).collect())
+        },
+        Duplicates => {
+            let keys: Vec<entry::Key> = app.entries.iter().map(|it| it.key.clone()).collect();
+            let groups = app.phashes.groups(&keys, app.states.similarity_threshold);
+            Some(groups.into_iter().enumerate().flat_map(|(index, group)| {
+                group.into_iter().map(move |key| (key, index as u32))
+            }).collect())
+        },
+        _ => None,
+    };
+
     if sort_key == SortKey::Natural && !reverse {
         app.entries.sort(&app_info);
     } else {
@@ -1305,10 +1659,14 @@ pub fn on_sort(app: &mut App, updated: &mut Updated, fix_current: bool, sort_key
                 return maybe_reverse(reverse, entry::compare_key(&a.key, &b.key));
             }
 
+            if let Some(ref ranks) = ranks {
+                return maybe_reverse(reverse, ranks.get(&a.key).cmp(&ranks.get(&b.key)));
+            }
+
             a.info.lazy(&a.content, |ai| {
                 b.info.lazy(&b.content, |bi| {
                     let result = match sort_key {
-                        Natural => panic!("WTF!"),
+                        Natural | Similarity | Duplicates => panic!("WTF!"),
                         FileSize =>
                             ai.file_size.cmp(&bi.file_size),
                         Created =>
@@ -1441,12 +1799,13 @@ pub fn on_tell_region(app: &mut App, left: f64, top: f64, right: f64, bottom: f6
             let (x2, y2) = (x1 + w, y1 + h);
             if x1 <= mx && mx <= x2 && y1 <= my && my <= y2 {
                 let (w, h) = (f64!(w) , f64!(h));
+                let start = Coord { x: f64!(mx - x1) / w, y: f64!(my - y1) / h };
                 let region = Region::new(
-                    f64!(mx - x1) / w,
-                    f64!(my - y1) / h,
+                    start.x,
+                    start.y,
                     (right - f64!(x1)) / w,
                     (bottom - f64!(y1)) / h);
-                let op = Operation::Fire(Mapped::Region(region, button.clone(), index));
+                let op = Operation::Fire(Mapped::Region(region, button.clone(), index, start));
                 app.secondary_tx.send(op).unwrap();
             }
         }
@@ -1454,6 +1813,38 @@ pub fn on_tell_region(app: &mut App, left: f64, top: f64, right: f64, bottom: f6
     Ok(())
 }
 
+pub fn on_text(app: &mut App, updated: &mut Updated, mut text: annotation::Text, cell_index: usize, context: Option<OperationContext>) -> AppResultU {
+    use crate::cherenkov::{Modifier, Che};
+
+    if text.text.is_empty() {
+        return Err(AppError::Fixed("Empty text"));
+    }
+
+    let cell_index = if let Some((region, cell_index)) = extract_region_from_context(context) {
+        text.position = Coord { x: region.left, y: region.top };
+        cell_index
+    } else {
+        cell_index
+    };
+
+    if let Some(clipping) = app.states.drawing.clipping {
+        text.position = text.position.unclipped(&clipping);
+    }
+
+    if let Some((entry, _)) = app.current_with(cell_index as isize) {
+        let imaging = app.get_imaging();
+        app.cache.cherenkov1(
+            &entry,
+            &imaging,
+            Modifier {
+                search_highlight: false,
+                che: Che::Text(text),
+            });
+        updated.image = true;
+    }
+    Ok(())
+}
+
 pub fn on_timer(app: &mut App, name: Option<String>, op: Vec<String>, interval: Duration, repeat: Option<usize>, r#async: bool) -> AppResultU {
     app.timers.register(name, op, interval, repeat, r#async)
 }
@@ -1494,15 +1885,7 @@ pub fn on_unclip(app: &mut App, updated: &mut Updated) -> AppResultU {
 }
 
 pub fn on_undo(app: &mut App, updated: &mut Updated, count: Option<usize>) -> AppResultU {
-    // `counted` should be evaluated
-    #[allow(clippy::or_fun_call)]
-    let count = count.unwrap_or(app.counter.take());
-
-    if let Some((ref entry, _)) = app.current() {
-        app.cache.undo_cherenkov(&entry.key, count)
-    }
-    updated.image_options = true;
-    Ok(())
+    on_undo_or_redo(app, updated, count, false)
 }
 
 pub fn on_unmap(app: &mut App, target: &MappingTarget) -> AppResultU {
@@ -1534,6 +1917,30 @@ pub fn on_unmark(app: &mut App, target: &Option<String>) -> AppResultU {
     Ok(())
 }
 
+pub fn on_update_hashes(app: &mut App) -> AppResultU {
+    let entries = app.entries.iter().cloned().collect();
+    app.fetcher.new_hash_target(entries);
+    Ok(())
+}
+
+pub fn on_update_statistics(app: &mut App) -> AppResultU {
+    let entries = app.entries.iter().cloned().collect();
+    app.fetcher.new_statistics_target(entries, false);
+    Ok(())
+}
+
+fn on_update_host_option(app: &mut App, pattern: &str, name: HostOptionName, updater: &OptionUpdater) -> AppResultU {
+    use crate::operation::option::OptionUpdater::*;
+
+    match *updater {
+        Set(ref value) => app.states.curl_options.set_host_option(pattern, name, value)?,
+        Unset => app.states.curl_options.unset_host_option(pattern, name),
+        _ => return Err(AppError::NotSupported("Only @set and @unset are supported for the HTTP options")),
+    }
+    app.remote_cache.update_curl_options(app.states.curl_options.clone());
+    Ok(())
+}
+
 pub fn on_update_option(app: &mut App, updated: &mut Updated, option_name: &OptionName, updater: &OptionUpdater) -> AppResultU {
     use crate::option::OptionValue;
     use crate::operation::option::OptionName::*;
@@ -1558,33 +1965,60 @@ pub fn on_update_option(app: &mut App, updated: &mut Updated, option_name: &Opti
                 Animation => &mut app.states.drawing.animation,
                 AutoReload => &mut app.states.auto_reload,
                 AutoPaging => &mut app.states.auto_paging,
+                Brightness => &mut app.states.drawing.adjustment.brightness,
                 Canonicalize => &mut app.states.canonicalize,
+                CherenkovStore => &mut app.states.cherenkov_store,
+                Compare => &mut app.states.compare,
+                CompareOperator => &mut app.states.compare_operator,
+                CompareRatio => &mut app.states.compare_ratio,
+                Contrast => &mut app.states.drawing.adjustment.contrast,
                 CurlConnectTimeout => &mut app.states.curl_options.connect_timeout,
                 CurlFollowLocation => &mut app.states.curl_options.follow_location,
                 CurlLowSpeedLimit => &mut app.states.curl_options.low_speed_limit,
                 CurlLowSpeedTime => &mut app.states.curl_options.low_speed_time,
+                CurlRetries => &mut app.states.curl_options.retries,
+                CurlRetryDelay => &mut app.states.curl_options.retry_delay,
                 CurlTimeout => &mut app.states.curl_options.timeout,
+                DragToPan => &mut app.states.drag_to_pan,
                 EmptyStatusFormat => &mut app.states.empty_status_format,
+                EyeDetector => &mut app.states.eye_detector.backend,
+                EyeDetectorCascade => &mut app.states.eye_detector.cascade,
+                EyeDetectorCommand => &mut app.states.eye_detector.command,
                 FitTo => &mut app.states.drawing.fit_to,
                 Freeze => &mut app.states.freezed,
+                Gamma => &mut app.states.drawing.adjustment.gamma,
+                Grayscale => &mut app.states.drawing.adjustment.grayscale,
                 HistoryFile => &mut app.states.history_file,
                 HorizontalFlip => &mut app.states.drawing.horizontal_flip,
                 HorizontalViews => &mut app.states.view.cols,
                 IdleTime => &mut app.states.idle_time,
                 IgnoreFailures => &mut app.states.ignore_failures,
                 InitialPosition => &mut app.states.initial_position,
+                Invert => &mut app.states.drawing.adjustment.invert,
                 LogFile => &mut app.states.log_file,
+                Loupe => &mut app.states.loupe.enabled,
+                LoupeMagnification => &mut app.states.loupe.magnification,
+                LoupeSize => &mut app.states.loupe.size,
                 MaskOperator => &mut app.states.drawing.mask_operator,
+                Offline => &mut app.states.offline,
                 PathList => &mut app.states.path_list,
+                PixelInspector => &mut app.states.pixel_inspector,
                 PreFetchEnabled => &mut app.states.pre_fetch.enabled,
                 PreFetchLimit => &mut app.states.pre_fetch.limit_of_items,
                 PreFetchPageSize => &mut app.states.pre_fetch.page_size,
                 PreFetchStages => &mut app.states.pre_fetch.cache_stages,
+                RememberZoom => &mut app.states.remember_zoom,
+                RemoteCacheMaxAge => &mut app.states.remote_cache_max_age,
+                RemoteCacheMaxSize => &mut app.states.remote_cache_max_size,
                 Reverse => &mut app.states.reverse,
                 Rotation => &mut app.states.drawing.rotation,
+                Saturation => &mut app.states.drawing.adjustment.saturation,
                 Screen => &mut app.states.screen,
+                SimilarityThreshold => &mut app.states.similarity_threshold,
                 SkipResizeWindow => &mut app.states.skip_resize_window,
+                SmoothScroll => &mut app.states.smooth_scroll,
                 StablePush => &mut app.states.stable_push,
+                Statistics => &mut app.states.statistics,
                 StatusBar => &mut app.states.status_bar,
                 StatusBarAlign => &mut app.states.status_bar_align,
                 StatusBarHeight => &mut app.states.status_bar_height,
@@ -1594,12 +2028,17 @@ pub fn on_update_option(app: &mut App, updated: &mut Updated, option_name: &Opti
                 Style => &mut app.states.style,
                 TimeToHidePointer => &mut app.states.time_to_hide_pointer,
                 TitleFormat => &mut app.states.title_format,
+                UndoDepth => &mut app.states.undo_depth,
                 UpdateCacheAccessTime => &mut app.states.update_cache_atime,
                 VerticalFlip => &mut app.states.drawing.vertical_flip,
                 VerticalViews => &mut app.states.view.rows,
+                VideoFrameCommand => &mut app.states.video_frame_command,
+                VideoFrames => &mut app.states.video_frames,
                 WatchFiles => &mut app.states.watch_files,
                 ColorLink => &mut app.states.drawing.link_color,
             },
+            Host(ref pattern, name) =>
+                return on_update_host_option(app, pattern, name, updater),
             UserDefined(ref option_name) => {
                 if let Some(switch) = app.user_switches.get(option_name) {
                     switch
@@ -1642,6 +2081,27 @@ pub fn on_update_option(app: &mut App, updated: &mut Updated, option_name: &Opti
                 app.update_watcher(),
             AbbrevLength =>
                 updated.label = true,
+            CherenkovStore =>
+                app.cache.set_cherenkov_store(app.states.cherenkov_store),
+            Compare => {
+                app.gui.set_scroll_sync(app.states.compare != state::Compare::Off);
+                updated.image = true;
+            },
+            DragToPan =>
+                app.gui.set_drag_to_pan(app.states.drag_to_pan),
+            Loupe =>
+                app.gui.set_loupe(app.states.loupe.enabled),
+            PixelInspector => {
+                app.gui.set_pixel_inspector(app.states.pixel_inspector);
+                if !app.states.pixel_inspector {
+                    app.pixel_env.update(None);
+                }
+                updated.label = true;
+            },
+            RememberZoom => {
+                app.zooms.clear();
+                updated.image = true;
+            },
             Freeze if freezed && !app.states.freezed =>
                 updated.image = true,
             IgnoreFailures =>
@@ -1654,13 +2114,21 @@ pub fn on_update_option(app: &mut App, updated: &mut Updated, option_name: &Opti
             },
             Screen =>
                 app.update_ui_visibility(),
+            Statistics =>
+                app.update_statistics_overlay(),
             StatusBarAlign =>
                 app.gui.set_status_bar_align(app.states.status_bar_align.0),
             StatusBarHeight => {
                 app.update_status_bar_height();
                 updated.size = true;
             }
-            FitTo | Rotation | HorizontalFlip | VerticalFlip =>
+            FitTo => {
+                if let Some((entry, _)) = app.current() {
+                    app.zooms.remove(&entry.key);
+                }
+                updated.size = true;
+            },
+            Rotation | HorizontalFlip | VerticalFlip =>
                 updated.size = true,
             PreFetchLimit =>
                 app.cache.update_limit(app.states.pre_fetch.limit_of_items),
@@ -1678,6 +2146,8 @@ pub fn on_update_option(app: &mut App, updated: &mut Updated, option_name: &Opti
                 on_update_views(app, updated, false)?,
             UpdateCacheAccessTime =>
                 app.remote_cache.do_update_atime = app.states.update_cache_atime,
+            Offline =>
+                app.remote_cache.offline = app.states.offline,
             _ => ()
         }
     }
@@ -1762,17 +2232,85 @@ pub fn on_write<T: AsRef<Path>>(app: &mut App, path: &T, index: &Option<usize>)
     Ok(())
 }
 
+pub fn on_zoom(app: &mut App, updated: &mut Updated, out: bool, step: usize, context: Option<OperationContext>) -> AppResultU {
+    use crate::operation::option::PreDefinedOptionName;
+    use crate::size::FitTo;
+
+    let coord = context.and_then(|it| if let Mapped::Input(coord, _) = it.mapped { Some(coord) } else { None });
+    let scroll_anchor = app.gui.get_scroll_anchor(coord.filter(|it| 0 < it.width).as_ref());
+
+    let mut fit_to = app.fit_to();
+    let scale = match fit_to {
+        FitTo::Scale(scale) => scale,
+        _ => (app.current_base_scale.unwrap_or(1.0) * 100.0) as usize,
+    };
+    let delta = max!(scale * step / 100, 1);
+    fit_to.set_scale(if out { scale.saturating_sub(delta) } else { scale + delta });
+
+    // Only the current entry is zoomed, the global `fit-to` is kept
+    match app.current() {
+        Some((entry, _)) if app.states.remember_zoom => {
+            app.zooms.insert(entry.key.clone(), fit_to);
+        },
+        _ => app.states.drawing.fit_to = fit_to,
+    }
+
+    app.update_env_for_option(&PreDefinedOptionName::FitTo);
+    updated.image = true;
+    updated.scroll_anchor = scroll_anchor;
+    Ok(())
+}
+
+
+fn on_undo_or_redo(app: &mut App, updated: &mut Updated, count: Option<usize>, redo: bool) -> AppResultU {
+    // `counted` should be evaluated
+    #[allow(clippy::or_fun_call)]
+    let count = count.unwrap_or(app.counter.take());
+
+    if_let_some!((entry, _) = app.current(), Ok(()));
+    for _ in 0 .. count {
+        let current = app.undo_snapshot(&entry.key);
+        let snapshot = if redo {
+            app.undo_history.redo(&entry.key, current)
+        } else {
+            app.undo_history.undo(&entry.key, current, app.states.undo_depth)
+        };
+        if let Some(snapshot) = snapshot {
+            app.restore_snapshot(&entry.key, snapshot);
+        } else {
+            break;
+        }
+    }
+
+    updated.image_options = true;
+    Ok(())
+}
+
+/** The original image of the cell under `coord` and the relative position on it.
+    The image is `None` while it is decoded, and then `on_load` is operated **/
+fn get_original_image_at(app: &mut App, coord: &CoordPx, on_load: Operation) -> AppResult<Option<(Option<gdk_pixbuf::Pixbuf>, Coord)>> {
+    let found = app.gui.get_position_on_image(coord, &app.states.drawing, app.states.reverse);
+    if_let_some!((index, position) = found.filter(|(_, it)| it.x <= 1.0 && it.y <= 1.0), Ok(None));
+    if_let_some!((entry, _) = app.current_with(index as isize), Ok(None));
+    let pixbuf = app.original_image.get(&entry, app.gui.get_cell_size(&app.states.view), &app.secondary_tx, on_load)?;
+    Ok(Some((pixbuf, position)))
+}
 
 fn extract_region_from_context(context: Option<OperationContext>) -> Option<(Region, usize)> {
-    if let Some(Mapped::Region(ref region, _, cell_index)) = context.map(|it| it.mapped) {
+    if let Some(Mapped::Region(ref region, _, cell_index, _)) = context.map(|it| it.mapped) {
         return Some((*region, cell_index));
     }
     None
 }
 
-fn is_url(path: &str) -> bool {
-    if_let_some!(index = path.find("://"), false);
-    index < 10
+/** The line from the drag start to the drag end **/
+fn extract_line_from_context(context: Option<OperationContext>) -> Option<(Coord, Coord, usize)> {
+    if let Some(Mapped::Region(ref region, _, cell_index, start)) = context.map(|it| it.mapped) {
+        let nearer = |v: f64, a: f64, b: f64| if (v - a).abs() <= (v - b).abs() { b } else { a };
+        let end = Coord { x: nearer(start.x, region.left, region.right), y: nearer(start.y, region.top, region.bottom) };
+        return Some((start, end, cell_index));
+    }
+    None
 }
 
 fn on_update_views(app: &mut App, updated: &mut Updated, ignore_views: bool) -> AppResultU {
@@ -1836,11 +2374,20 @@ fn push_buffered(app: &mut App, updated: &mut Updated, ops: Vec<QueuedOperation>
                     app.entries.push_pdf_entry(&app_info, &pdf_path, index, meta.clone(), force, url.clone());
                 }
             },
-            PushMessage(message, meta, show) => {
+            PushVideo(video_path, meta, force, show, url) =>
+                on_push_video(app, updated, video_path, meta, force, show, url)?,
+            PushVideoEntries(video_path, frames, command, meta, force, show, url) => {
+                show_target = gen_target(show, &url, &video_path);
+                let video = Arc::new(video::Video::new(&video_path, frames, command));
+                for index in 0 .. video.frames {
+                    app.entries.push_video_entry(&app_info, &video, index, meta.clone(), force, url.clone());
+                }
+            },
+            PushMessage(message, meta, show, url, entry_type) => {
                 if show {
                     show_target = Some(ShowTarget::Index(len))
                 }
-                app.entries.push_message(&app_info, message, meta);
+                app.entries.push_message(&app_info, message, meta, url, entry_type);
             },
         }
 
@@ -1851,6 +2398,10 @@ fn push_buffered(app: &mut App, updated: &mut Updated, ops: Vec<QueuedOperation>
         updated.label = true;
     }
 
+    if app.entries.gather_refetched(&app_info) {
+        updated.pointer = true;
+    }
+
     app.update_paginator_condition();
     app.remote_cache.update_sorting_buffer_len();
 
@@ -1902,4 +2453,9 @@ fn fix_option_values(app: &mut App) {
 
     non_zero(&mut app.states.view.rows);
     non_zero(&mut app.states.view.cols);
+    non_zero(&mut app.states.video_frames);
+
+    app.states.compare_ratio = min!(app.states.compare_ratio, 100);
+    app.states.loupe.magnification = max!(app.states.loupe.magnification, 100);
+    app.states.loupe.size = max!(app.states.loupe.size, 16);
 }
@@ -29,6 +29,14 @@ pub fn cache_dir(path: &str) -> PathBuf {
     dir
 }
 
+pub fn data_dir(path: &str) -> PathBuf {
+    let dir = get_app_dir(AppDataType::UserData, &APP_INFO, path).unwrap();
+    if !dir.exists() {
+        create_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
 fn config_dir() -> PathBuf {
      get_app_root(AppDataType::UserConfig, &APP_INFO).unwrap()
 }
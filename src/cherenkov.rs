@@ -1,5 +1,5 @@
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::path::Path;
@@ -11,39 +11,51 @@ use gdk::prelude::ContextExt;
 use gdk_pixbuf::{Pixbuf, PixbufExt};
 
 use crate::color::Color;
+use crate::crop;
 use crate::entry::image::Imaging;
 use crate::entry::{Entry, EntryContent, Key, self};
 use crate::errors::{AppResult, AppResultU, AppError};
+use crate::filer::FileOperation;
 use crate::gtk_utils::new_pixbuf_from_surface;
 use crate::image::{ImageBuffer, StaticImageBuffer};
-use crate::size::{Size, Region};
+use crate::size::{FitTo, Size, Region};
 use crate::state::Drawing;
 
+pub mod annotation;
+pub mod effect;
 pub mod eye_detector;
 pub mod fill;
 pub mod modified;
 pub mod nova;
+pub mod store;
 
+use self::effect::Effect;
 use self::fill::Shape;
 use self::modified::Modified;
 
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Modifier {
     pub che: Che,
     pub search_highlight: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Che {
     Nova(nova::Nova),
     Fill(Shape, Region, Color, Option<Operator>, bool),
+    Effect(Effect, Shape, Region),
+    Text(annotation::Text),
+    Line(annotation::Line),
+    Arrow(annotation::Line),
 }
 
 #[derive(Clone)]
 pub struct Cherenkoved {
-    cache: HashMap<Key, CacheEntry>
+    cache: HashMap<Key, CacheEntry>,
+    restored: HashSet<Key>, /* The entries whose store file is already loaded */
+    store: bool,
 }
 
 #[derive(Clone)]
@@ -61,10 +73,16 @@ pub struct Operator(pub cairo::Operator);
 
 impl Cherenkoved {
     pub fn new() -> Cherenkoved {
-        Cherenkoved { cache: HashMap::new() }
+        Cherenkoved { cache: HashMap::new(), restored: HashSet::new(), store: false }
+    }
+
+    pub fn set_store(&mut self, value: bool) {
+        self.store = value;
+        self.restored.clear();
     }
 
     pub fn get_image_buffer(&mut self, entry: &Entry, imaging: &Imaging) -> Option<AppResult<ImageBuffer>> {
+        self.restore(entry, imaging);
         let cache_entry = self.cache.get_mut(&entry.key)?;
         Some(get_image_buffer(cache_entry, &entry.content, imaging))
     }
@@ -180,8 +198,33 @@ impl Cherenkoved {
         Ok(())
     }
 
+    /** Render the modifiers onto the original size image, and save it in the format for the extension **/
+    pub fn bake(&mut self, entry: &Entry, imaging: &Imaging, file_operation: &FileOperation) -> AppResultU {
+        self.restore(entry, imaging);
+        if_let_some!(cache_entry = self.cache.get(&entry.key), Err(AppError::Fixed("Not cherenkoved")));
+        let modifiers: Vec<Modifier> = cache_entry.modifiers.iter().filter(|it| !it.search_highlight).cloned().collect();
+        if modifiers.is_empty() {
+            return Err(AppError::Fixed("Not cherenkoved"));
+        }
+
+        let drawing = Drawing {
+            animation: false,
+            fit_to: FitTo::Original,
+            mask_operator: imaging.drawing.mask_operator,
+            ..Drawing::default()
+        };
+        let image = re_cherenkov(&entry.content, &Imaging::new(imaging.cell_size, drawing), &modifiers)?;
+        let pixbuf = image.get_pixbuf();
+        file_operation.execute_with_writer(&crop::file_name(&entry.page_filename()), |path| {
+            crop::save(&pixbuf, &path)?;
+            puts_event!("cherenkov/bake", "path" => p!(path));
+            Ok(())
+        })
+    }
+
     pub fn remove(&mut self, key: &Key) {
         self.cache.remove(key);
+        self.persist(key, &[]);
     }
 
     pub fn clear_search_highlights(&mut self) -> bool {
@@ -198,13 +241,28 @@ impl Cherenkoved {
         entry.clear_search_highlights()
     }
 
-    pub fn undo(&mut self, key: &Key, count: usize) {
-        if let Some(cache_entry) = self.cache.get_mut(key) {
-            for _ in 0..count {
-                cache_entry.modifiers.pop();
-            }
-            cache_entry.image = None;
+    pub fn modifiers(&self, key: &Key) -> Vec<Modifier> {
+        self.cache.get(key).map(|it| it.modifiers.clone()).unwrap_or_default()
+    }
+
+    /** Replace the modifiers (for undo/redo) **/
+    pub fn set_modifiers(&mut self, key: &Key, imaging: &Imaging, modifiers: Vec<Modifier>) {
+        self.persist(key, &modifiers);
+
+        if modifiers.is_empty() {
+            self.cache.remove(key);
+            return;
         }
+
+        self.cache.insert(
+            key.clone(),
+            CacheEntry {
+                cell_size: imaging.cell_size,
+                drawing: imaging.drawing.clone(),
+                expired: true,
+                image: None,
+                modifiers,
+            });
     }
 
     pub fn cherenkov1(&mut self, entry: &Entry, imaging: &Imaging, modifier: Modifier) {
@@ -212,17 +270,22 @@ impl Cherenkoved {
     }
 
     pub fn reset(&mut self, entry: &Entry) {
-        if_let_some!(entry = self.cache.get_mut(&entry.key));
-        entry.reseed();
+        if_let_some!(cache_entry = self.cache.get_mut(&entry.key));
+        cache_entry.reseed();
+        let modifiers = cache_entry.modifiers.clone();
+        self.persist(&entry.key, &modifiers);
     }
 
     pub fn cherenkov(&mut self, entry: &Entry, imaging: &Imaging, new_modifiers: &[Modifier]) {
+        self.restore(entry, imaging);
+
         let mut modifiers = self.cache.get(&entry.key).map(|it| it.modifiers.clone()).unwrap_or_default();
 
         modifiers.extend_from_slice(new_modifiers);
 
         if_let_ok!(image_buffer = timeit!("re_cherenkov" => re_cherenkov(&entry.content, imaging, &modifiers)), |_| ());
 
+        self.persist(&entry.key, &modifiers);
         self.cache.insert(
             entry.key.clone(),
             CacheEntry {
@@ -233,12 +296,46 @@ impl Cherenkoved {
                 modifiers,
             });
     }
+
+    /** Load the stored modifiers for the entry that is not cherenkoved in this session.
+        The store file is read only once for each entry, since it is changed only by `persist` **/
+    fn restore(&mut self, entry: &Entry, imaging: &Imaging) {
+        if !self.store || self.cache.contains_key(&entry.key) || !self.restored.insert(entry.key.clone()) {
+            return;
+        }
+
+        match store::load(&entry.key) {
+            Ok(Some(modifiers)) => {
+                self.cache.insert(
+                    entry.key.clone(),
+                    CacheEntry {
+                        cell_size: imaging.cell_size,
+                        drawing: imaging.drawing.clone(),
+                        expired: true,
+                        image: None,
+                        modifiers,
+                    });
+            },
+            Ok(None) => (),
+            Err(err) => puts_error!(err, "at" => "cherenkoved/restore"),
+        }
+    }
+
+    fn persist(&mut self, key: &Key, modifiers: &[Modifier]) {
+        if !self.store {
+            return;
+        }
+        self.restored.insert(key.clone());
+        if let Err(err) = store::save(key, modifiers) {
+            puts_error!(err, "at" => "cherenkoved/persist");
+        }
+    }
 }
 
 
 impl CacheEntry {
     pub fn get(&self, cell_size: Size, drawing: &Drawing) -> Option<StaticImageBuffer> {
-        if !self.expired && self.cell_size == cell_size && self.drawing.fit_to == drawing.fit_to && self.drawing.clipping == drawing.clipping && self.drawing.mask_operator == drawing.mask_operator {
+        if !self.expired && self.cell_size == cell_size && self.drawing.fit_to == drawing.fit_to && self.drawing.clipping == drawing.clipping && self.drawing.mask_operator == drawing.mask_operator && self.drawing.adjustment == drawing.adjustment {
             if let Some(ref image) = self.image {
                 return Some(image.clone());
             }
@@ -476,6 +573,14 @@ fn cherenkov_pixbuf(modified: Modified, mask_surface: Option<ImageSurface>, che:
             let mask_surface =  fill::mask(mask_surface, &modified, fill::Parameter { clipping, color, operator, che: region, shape });
             (modified, Some(mask_surface))
         }
+        Che::Effect(effect, shape, ref region) =>
+            (effect::effect(modified, effect::Parameter { clipping, effect, region, shape }), mask_surface),
+        Che::Text(ref text) =>
+            (annotation::text(modified, text, clipping), mask_surface),
+        Che::Line(ref line) =>
+            (annotation::line(modified, line, false, clipping), mask_surface),
+        Che::Arrow(ref line) =>
+            (annotation::line(modified, line, true, clipping), mask_surface),
     }
 }
 
@@ -0,0 +1,159 @@
+
+use std::f64::consts::PI;
+
+use cairo::{Context, FontSlant, FontWeight, LineCap, LineJoin};
+
+use crate::color::Color;
+use crate::size::{Coord, Region};
+
+use crate::cherenkov::modified::Modified;
+
+
+
+const ARROW_HEAD_ANGLE: f64 = PI / 6.0;
+const ARROW_HEAD_SIZE: f64 = 4.0; /* x line width */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub bold: bool,
+    pub color: Color,
+    pub font: String,
+    pub outline: Option<Color>,
+    pub outline_width: f64, /* ratio to the font size */
+    pub position: Coord, /* top left */
+    pub size: f64, /* ratio to the longer side of the image */
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub color: Color,
+    pub points: Vec<Coord>,
+    pub width: f64, /* ratio to the longer side of the image */
+}
+
+struct Canvas<'a> {
+    clipping: &'a Option<Region>,
+    height: f64,
+    width: f64,
+}
+
+
+pub fn text(modified: Modified, text: &Text, clipping: &Option<Region>) -> Modified {
+    let surface = modified.get_image_surface();
+    let context = Context::new(&surface);
+    let canvas = Canvas { clipping, height: f64!(surface.get_height()), width: f64!(surface.get_width()) };
+
+    let size = text.size * canvas.longer_side();
+    let (x, y) = canvas.position(text.position);
+    let weight = if text.bold { FontWeight::Bold } else { FontWeight::Normal };
+
+    context.select_font_face(&text.font, FontSlant::Normal, weight);
+    context.set_font_size(size);
+
+    let extents = context.font_extents();
+    for (index, line) in text.text.lines().enumerate() {
+        context.move_to(x, y + extents.ascent + extents.height * index as f64);
+        context.text_path(line);
+    }
+
+    if let Some(outline) = text.outline {
+        // The inner half of the outline is hidden by the text
+        set_color(&context, outline);
+        context.set_line_width(size * text.outline_width * 2.0);
+        context.set_line_join(LineJoin::Round);
+        context.stroke_preserve();
+    }
+    set_color(&context, text.color);
+    context.fill();
+
+    Modified::S(surface)
+}
+
+pub fn line(modified: Modified, line: &Line, arrow: bool, clipping: &Option<Region>) -> Modified {
+    let surface = modified.get_image_surface();
+    let context = Context::new(&surface);
+    let canvas = Canvas { clipping, height: f64!(surface.get_height()), width: f64!(surface.get_width()) };
+
+    let mut points: Vec<(f64, f64)> = line.points.iter().map(|it| canvas.position(*it)).collect();
+    if points.len() < 2 {
+        return Modified::S(surface);
+    }
+
+    let width = line.width * canvas.longer_side();
+    let head = if arrow {
+        let head = arrow_head(points[points.len() - 2], points[points.len() - 1], width * ARROW_HEAD_SIZE);
+        // Not to stick out of the tip
+        let last = points.len() - 1;
+        points[last] = ((head[1].0 + head[2].0) / 2.0, (head[1].1 + head[2].1) / 2.0);
+        Some(head)
+    } else {
+        None
+    };
+
+    set_color(&context, line.color);
+    context.set_line_width(width);
+    context.set_line_cap(LineCap::Round);
+    context.set_line_join(LineJoin::Round);
+    let (x, y) = points[0];
+    context.move_to(x, y);
+    for &(x, y) in &points[1..] {
+        context.line_to(x, y);
+    }
+    context.stroke();
+
+    if let Some(head) = head {
+        context.move_to(head[0].0, head[0].1);
+        context.line_to(head[1].0, head[1].1);
+        context.line_to(head[2].0, head[2].1);
+        context.close_path();
+        context.fill();
+    }
+
+    Modified::S(surface)
+}
+
+/** [tip, wing, wing] **/
+fn arrow_head(from: (f64, f64), to: (f64, f64), size: f64) -> [(f64, f64); 3] {
+    let angle = (to.1 - from.1).atan2(to.0 - from.0);
+    let wing = |angle: f64| (to.0 - size * angle.cos(), to.1 - size * angle.sin());
+    [to, wing(angle - ARROW_HEAD_ANGLE), wing(angle + ARROW_HEAD_ANGLE)]
+}
+
+fn set_color(context: &Context, color: Color) {
+    let (r, g, b, a) = color.tupled4();
+    context.set_source_rgba(r, g, b, a);
+}
+
+
+impl<'a> Canvas<'a> {
+    fn position(&self, coord: Coord) -> (f64, f64) {
+        let coord = self.clipping.map(|it| coord.clipped(&it)).unwrap_or(coord);
+        (coord.x * self.width, coord.y * self.height)
+    }
+
+    /** The longer side of the whole (not clipped) image in pixels **/
+    fn longer_side(&self) -> f64 {
+        if let Some(clipping) = self.clipping {
+            max!(self.width / clipping.width(), self.height / clipping.height())
+        } else {
+            max!(self.width, self.height)
+        }
+    }
+}
+
+
+
+#[cfg(test)]#[test]
+fn test_arrow_head() {
+    let round = |(x, y): (f64, f64)| ((x * 1000.0).round() / 1000.0, (y * 1000.0).round() / 1000.0);
+
+    let head = arrow_head((0.0, 0.0), (10.0, 0.0), 2.0);
+    assert_eq!(round(head[0]), (10.0, 0.0));
+    assert_eq!(round(head[1]), (8.268, 1.0));
+    assert_eq!(round(head[2]), (8.268, -1.0));
+
+    let head = arrow_head((5.0, 5.0), (5.0, 0.0), 2.0);
+    assert_eq!(round(head[1]), (6.0, 1.732));
+    assert_eq!(round(head[2]), (4.0, 1.732));
+}
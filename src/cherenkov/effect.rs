@@ -0,0 +1,292 @@
+
+use cairo::{Context, self};
+use gdk::prelude::ContextExt;
+use gdk_pixbuf::PixbufExt;
+
+use crate::gtk_utils::new_pixbuf_from_surface;
+use crate::size::Region;
+
+use crate::cherenkov::fill::{path, Shape};
+use crate::cherenkov::modified::Modified;
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Effect {
+    Blur(f64), /* sigma (ratio to the longer side of the image) */
+    Pixelate(f64), /* block size (ratio to the longer side of the image) */
+    Smear(f64), /* horizontal length (ratio to the longer side of the image) */
+}
+
+pub struct Parameter<'a> {
+    pub clipping: &'a Option<Region>,
+    pub effect: Effect,
+    pub region: &'a Region,
+    pub shape: Shape,
+}
+
+#[derive(Debug, PartialEq)]
+struct Bounds {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+type Pixel = [f64; 4];
+
+
+impl Effect {
+    /** Ratio to the longer side of the image **/
+    pub fn size(self) -> f64 {
+        use self::Effect::*;
+
+        match self {
+            Blur(size) | Pixelate(size) | Smear(size) => size,
+        }
+    }
+}
+
+
+pub fn effect(modified: Modified, p: Parameter) -> Modified {
+    let surface = modified.get_image_surface();
+    let (w, h) = (surface.get_width(), surface.get_height());
+    let region = p.clipping.map(|it| p.region.clipped(&it)).unwrap_or(*p.region);
+
+    // The longer side of the whole (not clipped) image, as annotations
+    let longer_side = p.clipping.map_or(f64!(max!(w, h)), |it| max!(f64!(w) / it.width(), f64!(h) / it.height()));
+
+    let effected = new_pixbuf_from_surface(&surface);
+    if effected.get_n_channels() == 4 {
+        let size = max!((p.effect.size() * longer_side).round() as usize, 1);
+        let bounds = Bounds::new(&region, w as usize, h as usize, p.effect, size);
+        let rowstride = effected.get_rowstride() as usize;
+        let pixels: &mut [u8] = unsafe { effected.get_pixels() };
+        apply(p.effect, pixels, rowstride, &bounds, size);
+    }
+
+    let context = Context::new(&surface);
+    context.set_source_pixbuf(&effected, 0.0, 0.0);
+    context.set_operator(cairo::Operator::Source);
+    path(&context, p.shape, &region, w, h);
+    context.fill();
+
+    Modified::S(surface)
+}
+
+fn apply(effect: Effect, pixels: &mut [u8], rowstride: usize, bounds: &Bounds, size: usize) {
+    if bounds.right <= bounds.left || bounds.bottom <= bounds.top {
+        return;
+    }
+
+    let (width, height) = (bounds.right - bounds.left, bounds.bottom - bounds.top);
+    let mut buffer = read(pixels, rowstride, bounds);
+
+    match effect {
+        Effect::Blur(_) => {
+            // Three box blurs approximate a gaussian blur
+            let radius = box_radius(size);
+            for _ in 0 .. 3 {
+                box_blur_horizontal(&mut buffer, width, height, radius);
+                box_blur_vertical(&mut buffer, width, height, radius);
+            }
+        },
+        Effect::Pixelate(_) =>
+            pixelate(&mut buffer, width, height, size),
+        Effect::Smear(_) =>
+            box_blur_horizontal(&mut buffer, width, height, size),
+    }
+
+    write(pixels, rowstride, bounds, &buffer);
+}
+
+/** Premultiplied RGBA **/
+fn read(pixels: &[u8], rowstride: usize, bounds: &Bounds) -> Vec<Pixel> {
+    let mut result = Vec::with_capacity((bounds.right - bounds.left) * (bounds.bottom - bounds.top));
+    for y in bounds.top .. bounds.bottom {
+        for x in bounds.left .. bounds.right {
+            let p = &pixels[y * rowstride + x * 4 ..];
+            let a = f64!(p[3]);
+            let alpha = a / 255.0;
+            result.push([f64!(p[0]) * alpha, f64!(p[1]) * alpha, f64!(p[2]) * alpha, a]);
+        }
+    }
+    result
+}
+
+fn write(pixels: &mut [u8], rowstride: usize, bounds: &Bounds, buffer: &[Pixel]) {
+    let to_u8 = |v: f64| min!(max!(v.round(), 0.0), 255.0) as u8;
+
+    let mut source = buffer.iter();
+    for y in bounds.top .. bounds.bottom {
+        for x in bounds.left .. bounds.right {
+            let [r, g, b, a] = *source.next().unwrap();
+            let p = &mut pixels[y * rowstride + x * 4 ..];
+            let alpha = a / 255.0;
+            if 0.0 < alpha {
+                p[0] = to_u8(r / alpha);
+                p[1] = to_u8(g / alpha);
+                p[2] = to_u8(b / alpha);
+            }
+            p[3] = to_u8(a);
+        }
+    }
+}
+
+/** The radius of the box to approximate the gaussian (sigma) with three passes **/
+fn box_radius(sigma: usize) -> usize {
+    let sigma = sigma as f64;
+    max!(((4.0 * sigma * sigma + 1.0).sqrt() / 2.0 - 0.5).round() as usize, 1)
+}
+
+fn box_blur_horizontal(buffer: &mut [Pixel], width: usize, height: usize, radius: usize) {
+    let mut line = vec![[0.0; 4]; width];
+    for y in 0 .. height {
+        let row = &mut buffer[y * width .. (y + 1) * width];
+        line.copy_from_slice(row);
+        box_blur_line(&line, row, radius);
+    }
+}
+
+fn box_blur_vertical(buffer: &mut [Pixel], width: usize, height: usize, radius: usize) {
+    let mut line = vec![[0.0; 4]; height];
+    let mut blurred = vec![[0.0; 4]; height];
+    for x in 0 .. width {
+        for (y, it) in line.iter_mut().enumerate() {
+            *it = buffer[y * width + x];
+        }
+        box_blur_line(&line, &mut blurred, radius);
+        for (y, it) in blurred.iter().enumerate() {
+            buffer[y * width + x] = *it;
+        }
+    }
+}
+
+/** Moving average (The edge pixels are extended) **/
+fn box_blur_line(source: &[Pixel], output: &mut [Pixel], radius: usize) {
+    let last = source.len() as isize - 1;
+    let at = |i: isize| &source[min!(max!(i, 0), last) as usize];
+    let n = (radius * 2 + 1) as f64;
+    let radius = radius as isize;
+
+    let mut sum = [0.0; 4];
+    for i in -radius ..= radius {
+        for (s, v) in sum.iter_mut().zip(at(i)) {
+            *s += v;
+        }
+    }
+
+    for (x, out) in output.iter_mut().enumerate() {
+        let x = x as isize;
+        for (o, s) in out.iter_mut().zip(&sum) {
+            *o = s / n;
+        }
+        let (next, prev) = (at(x + radius + 1), at(x - radius));
+        for ((s, a), b) in sum.iter_mut().zip(next).zip(prev) {
+            *s += a - b;
+        }
+    }
+}
+
+fn pixelate(buffer: &mut [Pixel], width: usize, height: usize, size: usize) {
+    for top in (0 .. height).step_by(size) {
+        for left in (0 .. width).step_by(size) {
+            let (right, bottom) = (min!(left + size, width), min!(top + size, height));
+            let n = ((right - left) * (bottom - top)) as f64;
+
+            let mut sum = [0.0; 4];
+            for y in top .. bottom {
+                for it in &buffer[y * width + left .. y * width + right] {
+                    for (s, v) in sum.iter_mut().zip(it) {
+                        *s += v;
+                    }
+                }
+            }
+
+            let mut average = sum;
+            for it in &mut average {
+                *it /= n;
+            }
+            for y in top .. bottom {
+                for it in &mut buffer[y * width + left .. y * width + right] {
+                    *it = average;
+                }
+            }
+        }
+    }
+}
+
+
+impl Bounds {
+    /** Pixel bounds with margins to sample the pixels around the region **/
+    fn new(region: &Region, width: usize, height: usize, effect: Effect, size: usize) -> Self {
+        let clamp = |v: f64, limit: usize| min!(max!(v, 0.0), limit as f64) as usize;
+
+        let left = clamp((region.left * width as f64).floor(), width);
+        let top = clamp((region.top * height as f64).floor(), height);
+        let right = clamp((region.right * width as f64).ceil(), width);
+        let bottom = clamp((region.bottom * height as f64).ceil(), height);
+
+        match effect {
+            Effect::Blur(_) => {
+                let margin = box_radius(size) * 3;
+                Bounds {
+                    left: left.saturating_sub(margin),
+                    top: top.saturating_sub(margin),
+                    right: min!(right + margin, width),
+                    bottom: min!(bottom + margin, height),
+                }
+            },
+            Effect::Pixelate(_) => {
+                // Align the blocks to the image
+                let ceil = |v: usize| (v + size - 1) / size * size;
+                Bounds {
+                    left: left / size * size,
+                    top: top / size * size,
+                    right: min!(ceil(right), width),
+                    bottom: min!(ceil(bottom), height),
+                }
+            },
+            Effect::Smear(_) =>
+                Bounds {
+                    left: left.saturating_sub(size),
+                    top,
+                    right: min!(right + size, width),
+                    bottom,
+                },
+        }
+    }
+}
+
+
+
+#[cfg(test)]#[test]
+fn test_effect() {
+    let bounds = |left, top, right, bottom| Bounds { left, top, right, bottom };
+    let region = Region { left: 0.3, top: 0.3, right: 0.5, bottom: 0.5 };
+
+    assert_eq!(Bounds::new(&region, 10, 10, Effect::Pixelate(0.0), 2), bounds(2, 2, 6, 6));
+    assert_eq!(Bounds::new(&region, 10, 10, Effect::Smear(0.0), 4), bounds(0, 3, 9, 5));
+
+    // 4x2 RGBA
+    let mut pixels: Vec<u8> = vec![
+        0, 0, 0, 255,    100, 100, 100, 255,    10, 20, 30, 255,    10, 20, 30, 255,
+        100, 100, 100, 255,    0, 0, 0, 255,    10, 20, 30, 255,    10, 20, 30, 255,
+    ];
+    apply(Effect::Pixelate(0.0), &mut pixels, 16, &bounds(0, 0, 4, 2), 2);
+    assert_eq!(
+        pixels,
+        vec![
+            50, 50, 50, 255,    50, 50, 50, 255,    10, 20, 30, 255,    10, 20, 30, 255,
+            50, 50, 50, 255,    50, 50, 50, 255,    10, 20, 30, 255,    10, 20, 30, 255,
+        ]);
+
+    let original = pixels.clone();
+    apply(Effect::Blur(0.0), &mut pixels, 16, &bounds(2, 0, 4, 2), 1);
+    assert_eq!(pixels, original);
+
+    let mut line = vec![[0.0; 4], [0.0; 4], [30.0; 4]];
+    let source = line.clone();
+    box_blur_line(&source, &mut line, 1);
+    assert_eq!(line, vec![[0.0; 4], [10.0; 4], [20.0; 4]]);
+}
@@ -1,31 +1,44 @@
-
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::mpsc::Sender;
 use std::thread::spawn;
 
-use bytes::Bytes;
-use rusoto_core::Region;
-use rusoto_rekognition::{DetectFacesRequest, DetectFacesResponse, Image, Rekognition, RekognitionClient};
-
+use crate::errors::{AppError, AppResult};
 use crate::operation::CherenkovParameter;
 use crate::operation::Operation;
 use crate::size::Coord;
 
+pub mod cascade;
+pub mod command;
 
 
 
-pub fn detect_eyes(app_tx: Sender<Operation>, parameter: CherenkovParameter, image: Vec<u8>) {
-    let cli = RekognitionClient::new(Region::default());
+pub const DEFAULT_CASCADE: &str = "haarcascade_eye.xml";
 
-    let image = Image { bytes: Some(Bytes::from(image.as_slice())), s3_object: None };
-    let request = DetectFacesRequest {
-        attributes: None,
-        image,
-    };
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Cascade, /* Built-in Haar cascade classifier */
+    Command, /* External program that outputs JSON */
+}
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub backend: Backend,
+    pub cascade: Option<PathBuf>, /* None => `DEFAULT_CASCADE` in `path` */
+    pub command: Option<String>,
+}
+
+/** `cascade` should be resolved in the main thread (with `path` option) **/
+pub fn detect_eyes(app_tx: Sender<Operation>, parameter: CherenkovParameter, image: Vec<u8>, backend: Backend, cascade: PathBuf, command: Option<String>) {
     spawn(move || {
-        match cli.detect_faces(request).sync() {
-            Ok(response) => {
-                let eyes = extract_eyes(response);
+        let eyes = match backend {
+            Backend::Cascade => cascade::detect(&image, &cascade),
+            Backend::Command => command.ok_or(AppError::Fixed("`eye-detector-command` is not set")).and_then(|it| command::detect(&image, &it)),
+        };
+
+        match eyes {
+            Ok(eyes) => {
                 if eyes.is_empty() {
                     puts_error!("Eyes not found", "at" => "cherenkov/detect_eyes");
                     app_tx.send(Operation::Message(Some(o!("Eyes not found")), false)).unwrap();
@@ -47,22 +60,40 @@ pub fn detect_eyes(app_tx: Sender<Operation>, parameter: CherenkovParameter, ima
     });
 }
 
+/** Coordinates in pixels => Ratios (0.0 - 1.0) **/
+fn to_ratio(eyes: Vec<Coord>, width: f64, height: f64) -> Vec<Coord> {
+    eyes.into_iter().map(|it| Coord { x: it.x / width, y: it.y / height }).collect()
+}
+
 
-fn extract_eyes(res: DetectFacesResponse) -> Vec<Coord> {
-    if_let_some!(details = res.face_details, vec![]);
-    let mut result = vec![];
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            backend: Backend::Cascade,
+            cascade: None,
+            command: None,
+        }
+    }
+}
 
-    for landmarks in details.iter().flat_map(|it| &it.landmarks) {
-        for landmark in landmarks {
-            match landmark.type_.as_ref().map(String::as_ref) {
-                Some("eyeRight") | Some("eyeLeft") =>
-                    if let (Some(x), Some(y)) = (landmark.x, landmark.y) {
-                        result.push(Coord { x: f64!(x), y: f64!(y) })
-                    },
-                _ => (),
-            }
+impl FromStr for Backend {
+    type Err = AppError;
+
+    fn from_str(src: &str) -> AppResult<Self> {
+        match src {
+            "cascade" | "builtin" => Ok(Backend::Cascade),
+            "command" => Ok(Backend::Command),
+            _ => Err(AppError::InvalidValue(o!(src))),
         }
     }
+}
 
-    result
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match *self {
+            Backend::Cascade => "cascade",
+            Backend::Command => "command",
+        };
+        write!(f, "{}", result)
+    }
 }
@@ -0,0 +1,418 @@
+
+use std::path::Path;
+
+use gdk_pixbuf::{InterpType, Pixbuf, PixbufExt, PixbufLoader, PixbufLoaderExt};
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::errors::{AppError, AppResult, AppResultU};
+use crate::size::Coord;
+use crate::util::file::read_string;
+
+use super::to_ratio;
+
+
+
+const MAX_SOURCE_SIZE: i32 = 640;
+const MIN_NEIGHBORS: usize = 3;
+const SCALE_FACTOR: f64 = 1.1;
+
+/** Haar cascade classifier in the OpenCV's XML format (e.g. `haarcascade_eye.xml`) **/
+#[derive(Debug, Default)]
+pub struct Cascade {
+    features: Vec<Feature>,
+    height: usize,
+    stages: Vec<Stage>,
+    width: usize,
+}
+
+#[derive(Debug, Default)]
+struct Stage {
+    classifiers: Vec<Classifier>,
+    threshold: f64,
+}
+
+#[derive(Debug, Default)]
+struct Classifier {
+    leaves: Vec<f64>,
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+struct Node {
+    feature: usize,
+    left: i32, /* <= 0 => Leaf index (negated) */
+    right: i32,
+    threshold: f64,
+}
+
+#[derive(Debug, Default)]
+struct Feature {
+    rects: Vec<Rect>,
+}
+
+#[derive(Debug)]
+struct Rect {
+    height: usize,
+    weight: f64,
+    width: usize,
+    x: usize,
+    y: usize,
+}
+
+/** Summed area tables **/
+struct Integral {
+    square: Vec<f64>,
+    sum: Vec<f64>,
+    width: usize, /* Image width + 1 */
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Window {
+    height: f64,
+    width: f64,
+    x: f64,
+    y: f64,
+}
+
+
+pub fn detect<T: AsRef<Path>>(image: &[u8], cascade: &T) -> AppResult<Vec<Coord>> {
+    let cascade = Cascade::load(cascade)?;
+
+    let loader = PixbufLoader::new();
+    loader.write(image)?;
+    loader.close()?;
+    let pixbuf = loader.get_pixbuf().ok_or(AppError::Fixed("Could not load image"))?;
+    let (width, height) = (pixbuf.get_width(), pixbuf.get_height());
+
+    // Work on a small image. The results are ratios
+    let base = max!(width, height);
+    let (width, height) = if MAX_SOURCE_SIZE < base {
+        (width * MAX_SOURCE_SIZE / base, height * MAX_SOURCE_SIZE / base)
+    } else {
+        (width, height)
+    };
+
+    let mut windows = vec![];
+    let mut factor = 1.0;
+    loop {
+        let (w, h) = ((f64!(width) / factor) as i32, (f64!(height) / factor) as i32);
+        if (w as usize) < cascade.width || (h as usize) < cascade.height {
+            break;
+        }
+        let scaled = pixbuf.scale_simple(w, h, InterpType::Bilinear).ok_or(AppError::Fixed("Could not scale image"))?;
+        let integral = Integral::new(&to_gray(&scaled), w as usize, h as usize);
+        let step = if 2.0 < factor { 1 } else { 2 };
+        cascade.scan(&integral, w as usize, h as usize, factor, step, &mut windows);
+        factor *= SCALE_FACTOR;
+    }
+
+    let eyes = group(&windows).into_iter().map(|it| Coord { x: it.x + it.width / 2.0, y: it.y + it.height / 2.0 }).collect();
+    Ok(to_ratio(eyes, f64!(width), f64!(height)))
+}
+
+
+impl Cascade {
+    pub fn load<T: AsRef<Path>>(path: &T) -> AppResult<Self> {
+        let source = read_string(path)?;
+        Cascade::parse(&source).map_err(|it| AppError::InvalidValueWithReason(s!(path.as_ref().display()), s!(it)))
+    }
+
+    fn parse(source: &str) -> AppResult<Self> {
+        fn numbers(text: &str) -> AppResult<Vec<f64>> {
+            text.split_whitespace().map(|it| it.parse().map_err(|_| AppError::InvalidValue(o!(it)))).collect()
+        }
+
+        let mut result = Cascade::default();
+        let mut path: Vec<String> = vec![];
+        let mut text = o!("");
+
+        for event in EventReader::from_str(source) {
+            match event.map_err(|it| AppError::Standard(s!(it)))? {
+                XmlEvent::StartElement { name, .. } => {
+                    match (path.last().map(String::as_str), name.local_name.as_str()) {
+                        (Some("stages"), "_") => result.stages.push(Stage::default()),
+                        (Some("weakClassifiers"), "_") => if let Some(stage) = result.stages.last_mut() {
+                            stage.classifiers.push(Classifier::default());
+                        },
+                        (Some("features"), "_") => result.features.push(Feature::default()),
+                        _ => (),
+                    }
+                    path.push(name.local_name);
+                    text.clear();
+                },
+                XmlEvent::Characters(value) =>
+                    text.push_str(&value),
+                XmlEvent::EndElement { .. } => {
+                    let name = path.pop().unwrap_or_default();
+                    let parent = path.last().map(String::as_str);
+
+                    match (parent, name.as_str()) {
+                        (Some("cascade"), "width") =>
+                            result.width = text.trim().parse()?,
+                        (Some("cascade"), "height") =>
+                            result.height = text.trim().parse()?,
+                        (Some("cascade"), "featureType") if text.trim() != "HAAR" =>
+                            return Err(AppError::InvalidValueWithReason(o!(text.trim()), o!("Only HAAR features are supported"))),
+                        (_, "stageThreshold") => if let Some(stage) = result.stages.last_mut() {
+                            stage.threshold = text.trim().parse().map_err(|_| AppError::InvalidValue(o!(text.trim())))?;
+                        },
+                        (_, "internalNodes") => if let Some(classifier) = result.stages.last_mut().and_then(|it| it.classifiers.last_mut()) {
+                            for node in numbers(&text)?.chunks(4) {
+                                if node.len() < 4 {
+                                    return Err(AppError::InvalidValue(o!(text.trim())));
+                                }
+                                classifier.nodes.push(Node { left: node[0] as i32, right: node[1] as i32, feature: node[2] as usize, threshold: node[3] });
+                            }
+                        },
+                        (_, "leafValues") => if let Some(classifier) = result.stages.last_mut().and_then(|it| it.classifiers.last_mut()) {
+                            classifier.leaves = numbers(&text)?;
+                        },
+                        (Some("rects"), "_") => if let Some(feature) = result.features.last_mut() {
+                            let rect = numbers(&text)?;
+                            if rect.len() != 5 {
+                                return Err(AppError::InvalidValue(o!(text.trim())));
+                            }
+                            feature.rects.push(Rect { x: rect[0] as usize, y: rect[1] as usize, width: rect[2] as usize, height: rect[3] as usize, weight: rect[4] });
+                        },
+                        (_, "tilted") if text.trim() != "0" =>
+                            return Err(AppError::Fixed("Tilted features are not supported")),
+                        _ => (),
+                    }
+                    text.clear();
+                },
+                _ => (),
+            }
+        }
+
+        result.validate()?;
+        Ok(result)
+    }
+
+    fn validate(&self) -> AppResultU {
+        if self.stages.is_empty() || self.width < 3 || self.height < 3 {
+            return Err(AppError::Fixed("No stages (Only the new OpenCV cascade format is supported)"));
+        }
+        for classifier in self.stages.iter().flat_map(|it| &it.classifiers) {
+            if classifier.nodes.is_empty() {
+                return Err(AppError::Fixed("Broken classifier"));
+            }
+            for node in &classifier.nodes {
+                let valid_child = |child: i32| if child <= 0 { (-child as usize) < classifier.leaves.len() } else { (child as usize) < classifier.nodes.len() };
+                if self.features.len() <= node.feature || !valid_child(node.left) || !valid_child(node.right) {
+                    return Err(AppError::Fixed("Broken classifier"));
+                }
+            }
+        }
+        for rect in self.features.iter().flat_map(|it| &it.rects) {
+            if self.width < rect.x + rect.width || self.height < rect.y + rect.height {
+                return Err(AppError::Fixed("Feature is out of the window"));
+            }
+        }
+        Ok(())
+    }
+
+    fn scan(&self, integral: &Integral, width: usize, height: usize, factor: f64, step: usize, result: &mut Vec<Window>) {
+        for y in (0 ..= height - self.height).step_by(step) {
+            for x in (0 ..= width - self.width).step_by(step) {
+                if self.classify(integral, x, y) {
+                    result.push(Window {
+                        x: x as f64 * factor,
+                        y: y as f64 * factor,
+                        width: self.width as f64 * factor,
+                        height: self.height as f64 * factor,
+                    });
+                }
+            }
+        }
+    }
+
+    fn classify(&self, integral: &Integral, x: usize, y: usize) -> bool {
+        // Normalize by the standard deviation in the window (without its border)
+        let (w, h) = (self.width - 2, self.height - 2);
+        let area = (w * h) as f64;
+        let sum = integral.sum(x + 1, y + 1, w, h);
+        let square = integral.square(x + 1, y + 1, w, h);
+        let norm = area * square - sum * sum;
+        let norm = if 0.0 < norm { norm.sqrt() } else { 1.0 };
+
+        self.stages.iter().all(|stage| {
+            let value: f64 = stage.classifiers.iter().map(|it| it.evaluate(&self.features, integral, x, y, norm)).sum();
+            stage.threshold <= value
+        })
+    }
+}
+
+impl Classifier {
+    fn evaluate(&self, features: &[Feature], integral: &Integral, x: usize, y: usize, norm: f64) -> f64 {
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index];
+            let value = features[node.feature].calculate(integral, x, y);
+            let next = if value < node.threshold * norm { node.left } else { node.right };
+            if next <= 0 {
+                return self.leaves[-next as usize];
+            }
+            index = next as usize;
+        }
+    }
+}
+
+impl Feature {
+    fn calculate(&self, integral: &Integral, x: usize, y: usize) -> f64 {
+        self.rects.iter().map(|it| it.weight * integral.sum(x + it.x, y + it.y, it.width, it.height)).sum()
+    }
+}
+
+impl Integral {
+    fn new(gray: &[u8], width: usize, height: usize) -> Self {
+        let w = width + 1;
+        let mut sum = vec![0.0; w * (height + 1)];
+        let mut square = vec![0.0; w * (height + 1)];
+
+        for y in 0 .. height {
+            let (mut row_sum, mut row_square) = (0.0, 0.0);
+            for x in 0 .. width {
+                let v = f64::from(gray[y * width + x]);
+                row_sum += v;
+                row_square += v * v;
+                sum[(y + 1) * w + x + 1] = sum[y * w + x + 1] + row_sum;
+                square[(y + 1) * w + x + 1] = square[y * w + x + 1] + row_square;
+            }
+        }
+
+        Integral { square, sum, width: w }
+    }
+
+    fn sum(&self, x: usize, y: usize, width: usize, height: usize) -> f64 {
+        area_sum(&self.sum, self.width, x, y, width, height)
+    }
+
+    fn square(&self, x: usize, y: usize, width: usize, height: usize) -> f64 {
+        area_sum(&self.square, self.width, x, y, width, height)
+    }
+}
+
+
+fn area_sum(table: &[f64], stride: usize, x: usize, y: usize, width: usize, height: usize) -> f64 {
+    let (right, bottom) = (x + width, y + height);
+    table[bottom * stride + right] - table[y * stride + right] - table[bottom * stride + x] + table[y * stride + x]
+}
+
+/** Merge the similar windows, and drop the windows that have few neighbors (like OpenCV's `groupRectangles`) **/
+fn group(windows: &[Window]) -> Vec<Window> {
+    const EPS: f64 = 0.2;
+
+    let similar = |a: &Window, b: &Window| {
+        let delta = EPS * (a.width.min(b.width) + a.height.min(b.height)) * 0.5;
+        (a.x - b.x).abs() <= delta &&
+            (a.y - b.y).abs() <= delta &&
+            (a.x + a.width - b.x - b.width).abs() <= delta &&
+            (a.y + a.height - b.y - b.height).abs() <= delta
+    };
+
+    let mut labels: Vec<usize> = (0 .. windows.len()).collect();
+    for i in 0 .. windows.len() {
+        for j in 0 .. i {
+            if similar(&windows[i], &windows[j]) {
+                let (from, to) = (labels[i], labels[j]);
+                for label in labels.iter_mut().filter(|it| **it == from) {
+                    *label = to;
+                }
+            }
+        }
+    }
+
+    let mut result = vec![];
+    let mut done = vec![false; windows.len()];
+    for i in 0 .. windows.len() {
+        if done[labels[i]] {
+            continue;
+        }
+        done[labels[i]] = true;
+        let members: Vec<&Window> = windows.iter().zip(&labels).filter(|(_, label)| **label == labels[i]).map(|(it, _)| it).collect();
+        if members.len() <= MIN_NEIGHBORS {
+            continue;
+        }
+        let n = members.len() as f64;
+        result.push(Window {
+            x: members.iter().map(|it| it.x).sum::<f64>() / n,
+            y: members.iter().map(|it| it.y).sum::<f64>() / n,
+            width: members.iter().map(|it| it.width).sum::<f64>() / n,
+            height: members.iter().map(|it| it.height).sum::<f64>() / n,
+        });
+    }
+    result
+}
+
+fn to_gray(pixbuf: &Pixbuf) -> Vec<u8> {
+    let (width, height) = (pixbuf.get_width() as usize, pixbuf.get_height() as usize);
+    let channels = pixbuf.get_n_channels() as usize;
+    let rowstride = pixbuf.get_rowstride() as usize;
+    let pixels: &[u8] = unsafe { pixbuf.get_pixels() };
+
+    let mut result = Vec::with_capacity(width * height);
+    for y in 0 .. height {
+        for x in 0 .. width {
+            let i = y * rowstride + x * channels;
+            let luma = (u32::from(pixels[i]) * 299 + u32::from(pixels[i + 1]) * 587 + u32::from(pixels[i + 2]) * 114) / 1000;
+            result.push(luma as u8);
+        }
+    }
+    result
+}
+
+
+#[cfg(test)]#[test]
+fn test_cascade() {
+    let source = r#"<?xml version="1.0"?>
+        <opencv_storage>
+        <cascade type_id="opencv-cascade-classifier">
+          <stageType>BOOST</stageType>
+          <featureType>HAAR</featureType>
+          <height>4</height>
+          <width>4</width>
+          <stageParams><maxDepth>1</maxDepth></stageParams>
+          <stageNum>1</stageNum>
+          <stages>
+            <_>
+              <maxWeakCount>1</maxWeakCount>
+              <stageThreshold>0.</stageThreshold>
+              <weakClassifiers>
+                <_>
+                  <internalNodes>
+                    0 -1 0 1.0000000000000001e-01</internalNodes>
+                  <leafValues>
+                    -1. 1.</leafValues></_></weakClassifiers></_></stages>
+          <features>
+            <_>
+              <rects>
+                <_>
+                  0 0 4 4 -1.</_>
+                <_>
+                  0 2 4 2 2.</_></rects></_></features></cascade>
+        </opencv_storage>"#;
+
+    let cascade = Cascade::parse(source).unwrap();
+    assert_eq!((cascade.width, cascade.height, cascade.stages.len(), cascade.features.len()), (4, 4, 1, 1));
+
+    // Dark (top) / Bright (bottom) edge at y = 2
+    let (width, height) = (8, 8);
+    let gray: Vec<u8> = (0 .. width * height).map(|i| if i / width < 2 { 0 } else { 255 }).collect();
+    let integral = Integral::new(&gray, width, height);
+    assert_eq!(integral.sum(0, 0, 8, 8), 255.0 * 48.0);
+    assert!(cascade.classify(&integral, 0, 0));
+    assert!(cascade.classify(&integral, 3, 0));
+    assert!(!cascade.classify(&integral, 0, 4));
+
+    let mut windows = vec![];
+    cascade.scan(&integral, width, height, 1.0, 1, &mut windows);
+    assert_eq!(windows.len(), 10); // y = 0, 1
+    assert!(windows.iter().all(|it| it.y <= 1.0));
+
+    let window = |x: f64, y: f64| Window { x, y, width: 20.0, height: 20.0 };
+    let windows = vec![window(10.0, 10.0), window(11.0, 10.0), window(50.0, 50.0), window(10.0, 11.0), window(11.0, 11.0)];
+    assert_eq!(group(&windows), vec![window(10.5, 10.5)]);
+    assert_eq!(group(&windows[0..3]), vec![]);
+
+    assert!(Cascade::parse(&source.replace("0 -1 0", "0 -1 1")).is_err());
+}
@@ -0,0 +1,88 @@
+
+use std::fs::{File, remove_file};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+use crate::app_path::cache_dir;
+use crate::constant::env_name;
+use crate::errors::{AppError, AppResult};
+use crate::size::Coord;
+use crate::util::path::path_to_str;
+
+use super::to_ratio;
+
+
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Output {
+    Points(Vec<Point>),
+    Eyes { eyes: Vec<Point> },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Point {
+    Object { x: f64, y: f64 },
+    Pair(f64, f64),
+}
+
+
+/** Run `sh -c COMMAND sh IMAGE_FILE` and read the eye coordinates (in pixels) from its stdout **/
+pub fn detect(image: &[u8], command: &str) -> AppResult<Vec<Coord>> {
+    let (width, height) = png_size(image).ok_or(AppError::Fixed("Invalid PNG image"))?;
+
+    let mut input = cache_dir("eye_detector");
+    input.push(format!("{}.png", uuid::Uuid::new_v4()));
+    File::create(&input)?.write_all(image)?;
+
+    let output = Command::new("sh")
+        .args(&["-c", command, "sh", path_to_str(&input)])
+        .env(env_name("eye_detector_input"), path_to_str(&input))
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output();
+    let _ = remove_file(&input);
+    let output = output?;
+
+    if !output.status.success() {
+        return Err(AppError::Standard(format!("Command failed: {} ({})", command, output.status)));
+    }
+
+    let eyes = parse_output(&String::from_utf8_lossy(&output.stdout))?;
+    Ok(to_ratio(eyes, f64!(width), f64!(height)))
+}
+
+/** `[{"x": X, "y": Y}, ...]` or `[[X, Y], ...]` (or `{"eyes": <ONE_OF_THEM>}`) **/
+fn parse_output(output: &str) -> AppResult<Vec<Coord>> {
+    let output: Output = serde_json::from_str(output).map_err(|it| AppError::InvalidValueWithReason(output.trim().chars().take(64).collect(), s!(it)))?;
+    let points = match output {
+        Output::Points(points) | Output::Eyes { eyes: points } => points,
+    };
+    Ok(points.into_iter().map(|point| match point {
+        Point::Object { x, y } | Point::Pair(x, y) => Coord { x, y },
+    }).collect())
+}
+
+fn png_size(image: &[u8]) -> Option<(u32, u32)> {
+    if image.len() < 24 || &image[0..8] != b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    let read = |offset: usize| image[offset..offset + 4].iter().fold(0u32, |acc, it| (acc << 8) | u32::from(*it));
+    Some((read(16), read(20)))
+}
+
+
+#[cfg(test)]#[test]
+fn test_parse_output() {
+    assert_eq!(
+        parse_output(r#"[{"x": 10, "y": 20.5, "score": 0.9}, {"y": 3, "x": -4e1}]"#).unwrap(),
+        vec![Coord { x: 10.0, y: 20.5 }, Coord { x: -40.0, y: 3.0 }]);
+    assert_eq!(
+        parse_output(r#"{"eyes": [[1, 2], [3.5, 4]]}"#).unwrap(),
+        vec![Coord { x: 1.0, y: 2.0 }, Coord { x: 3.5, y: 4.0 }]);
+    assert_eq!(parse_output("[]").unwrap(), vec![]);
+    assert!(parse_output("[[1, 2-]]").is_err());
+}
@@ -1,5 +1,6 @@
 
 use std::f64::consts::PI;
+use std::fmt;
 
 use cairo::{Context, ImageSurface, Format};
 
@@ -11,7 +12,7 @@ use crate::cherenkov::modified::Modified;
 
 
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Shape {
     Rectangle,
     Circle,
@@ -38,6 +39,18 @@ struct ContextParamater<'a> {
 }
 
 
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let result = match *self {
+            Shape::Rectangle => "rectangle",
+            Shape::Circle => "circle",
+            Shape::Ellipse => "ellipse",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+
 #[allow(clippy::many_single_char_names)]
 pub fn fill(modified: Modified, p: Parameter) -> Modified {
     let surface = modified.get_image_surface();
@@ -76,7 +89,6 @@ pub fn mask(surface: Option<ImageSurface>, modified: &Modified, p: Parameter) ->
     surface
 }
 
-#[allow(clippy::many_single_char_names)]
 fn context_fill(p: ContextParamater) {
     let (r, g, b, a) = p.color.tupled4();
     p.context.set_source_rgba(r, g, b, a);
@@ -84,21 +96,35 @@ fn context_fill(p: ContextParamater) {
     p.context.save();
 
     let region = p.clipping.map(|it| p.region.clipped(&it)).unwrap_or(*p.region);
+    path(p.context, p.shape, &region, p.w, p.h);
 
-    match p.shape {
+    if let Some(operator) = p.operator {
+        p.context.set_operator(operator.0);
+    }
+    p.context.fill();
+
+    p.context.restore();
+}
+
+/** Make the path of the shape on the surface (`w` x `h`) **/
+#[allow(clippy::many_single_char_names)]
+pub fn path(context: &Context, shape: Shape, region: &Region, w: i32, h: i32) {
+    let (w, h) = (f64!(w), f64!(h));
+
+    context.save();
+
+    match shape {
         Shape::Rectangle => {
-            let (w, h) = (f64!(p.w), f64!(p.h));
-            p.context.rectangle(
+            context.rectangle(
                 region.left * w,
                 region.top * h,
                 (region.right - region.left) * w,
                 (region.bottom - region.top) * h);
         },
         Shape::Circle => {
-            let (w, h) = (f64!(p.w), f64!(p.h));
             let (rw, rh) = (region.width(), region.height());
             let r = min!(rw * w, rh * h) / 2.0;
-            p.context.arc(
+            context.arc(
                 (region.left + rw / 2.0) * w,
                 (region.top + rh / 2.0) * h,
                 r,
@@ -106,19 +132,15 @@ fn context_fill(p: ContextParamater) {
                 2.0 * PI);
         },
         Shape::Ellipse => {
-            let (w, h) = (f64!(p.w), f64!(p.h));
             let (rw, rh) = (region.width(), region.height());
-            p.context.translate(
+            context.translate(
                 (region.left + rw / 2.0) * w,
                 (region.top + rh / 2.0) * h);
-            p.context.scale(rw * w / 2.0, rh * h / 2.0);
-            p.context.arc(0.0, 0.0, 1.0, 0.0, 2.0 * PI);
+            context.scale(rw * w / 2.0, rh * h / 2.0);
+            context.arc(0.0, 0.0, 1.0, 0.0, 2.0 * PI);
         }
     }
-    if let Some(operator) = p.operator {
-        p.context.set_operator(operator.0);
-    }
-    p.context.fill();
 
-    p.context.restore();
+    // The path is kept after `restore`
+    context.restore();
 }
@@ -44,7 +44,7 @@ type TupleColor = (f64, f64, f64);
 const FERROR: f64 = 0.000_001;
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Nova {
     pub center: (f64, f64),
     pub color: Color,
@@ -82,6 +82,35 @@ impl Seed {
     pub fn rng(&self) -> StdRng {
         StdRng::from_seed(self.value)
     }
+
+    /** `fixed:HEX` or `random:HEX` **/
+    pub fn encode(&self) -> String {
+        let mut result = o!(if self.fixed { "fixed:" } else { "random:" });
+        for it in &self.value {
+            result.push_str(&format!("{:02x}", it));
+        }
+        result
+    }
+
+    pub fn decode(src: &str) -> Option<Self> {
+        let (fixed, hex) = if let Some(hex) = src.strip_prefix("fixed:") {
+            (true, hex)
+        } else if let Some(hex) = src.strip_prefix("random:") {
+            (false, hex)
+        } else {
+            return None;
+        };
+
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+
+        let mut value = [0;32];
+        for (i, it) in value.iter_mut().enumerate() {
+            *it = u8::from_str_radix(&hex[i * 2 .. i * 2 + 2], 16).ok()?;
+        }
+        Some(Seed { fixed, value })
+    }
 }
 
 
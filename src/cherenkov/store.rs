@@ -0,0 +1,240 @@
+
+use std::fs::{File, remove_file};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::app_path::data_dir;
+use crate::color::Color;
+use crate::entry::Key;
+use crate::errors::{AppError, AppResult, AppResultU};
+use crate::size::{Coord, Region};
+
+use crate::cherenkov::annotation::{Line, Text};
+use crate::cherenkov::effect::Effect;
+use crate::cherenkov::fill::Shape;
+use crate::cherenkov::nova::{Nova, Seed};
+use crate::cherenkov::{Che, Modifier};
+
+
+
+/** Load the modifiers of the entry from the store **/
+pub fn load(key: &Key) -> AppResult<Option<Vec<Modifier>>> {
+    let path = store_path(key);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut source = o!("");
+    File::open(&path)?.read_to_string(&mut source)?;
+    deserialize(&source).map(Some)
+}
+
+/** Save the modifiers (except search highlights) of the entry. The stored file is removed if there are no modifiers **/
+pub fn save(key: &Key, modifiers: &[Modifier]) -> AppResultU {
+    let path = store_path(key);
+    let ches: Vec<&Che> = modifiers.iter().filter(|it| !it.search_highlight).map(|it| &it.che).collect();
+
+    if ches.is_empty() {
+        if path.exists() {
+            remove_file(&path)?;
+        }
+        return Ok(());
+    }
+
+    let mut file = File::create(&path)?;
+    file.write_all(serialize(key, &ches).as_bytes())?;
+    Ok(())
+}
+
+fn store_path(key: &Key) -> PathBuf {
+    let mut result = data_dir("cherenkov");
+    let (ref entry_type, ref path, index) = *key;
+    result.push(format!("{:x}", md5::compute(format!("{:?}\n{}\n{}", entry_type, path, index).as_bytes())));
+    result
+}
+
+/** One modifier per line. The fields are separated by tabs **/
+fn serialize(key: &Key, ches: &[&Che]) -> String {
+    let mut result = format!("# {:?}\t{}\t{}\n", key.0, escape(&key.1), key.2 + 1);
+
+    for che in ches {
+        let fields: Vec<String> = match **che {
+            Che::Nova(ref nv) => {
+                let threads = nv.threads.map(|it| s!(it)).unwrap_or_else(|| o!("-"));
+                vec![o!("nova"), s!(nv.center.0), s!(nv.center.1), s!(nv.radius), s!(nv.n_spokes), s!(nv.random_hue), nv.color.to_hex(), nv.seed.encode(), threads]
+            },
+            Che::Fill(shape, ref region, color, operator, mask) => {
+                let mut fields = vec![o!("fill"), s!(shape)];
+                fields.extend(region_fields(region));
+                fields.push(color.to_hex());
+                fields.push(operator.map(|it| s!(it)).unwrap_or_else(|| o!("-")));
+                fields.push(o!(if mask { "1" } else { "0" }));
+                fields
+            },
+            Che::Effect(effect, shape, ref region) => {
+                let name = match effect {
+                    Effect::Blur(_) => "blur",
+                    Effect::Pixelate(_) => "pixelate",
+                    Effect::Smear(_) => "smear",
+                };
+                let mut fields = vec![o!(name), s!(effect.size()), s!(shape)];
+                fields.extend(region_fields(region));
+                fields
+            },
+            Che::Text(ref text) => {
+                let outline = text.outline.map(Color::to_hex).unwrap_or_else(|| o!("-"));
+                vec![
+                    o!("text"), s!(text.position.x), s!(text.position.y), s!(text.size), escape(&text.font), o!(if text.bold { "1" } else { "0" }),
+                    text.color.to_hex(), outline, s!(text.outline_width), escape(&text.text)]
+            },
+            Che::Line(ref line) | Che::Arrow(ref line) => {
+                let name = if let Che::Arrow(_) = **che { "arrow" } else { "line" };
+                let mut fields = vec![o!(name), s!(line.width), line.color.to_hex()];
+                for it in &line.points {
+                    fields.push(s!(it.x));
+                    fields.push(s!(it.y));
+                }
+                fields
+            },
+        };
+        result.push_str(&fields.join("\t"));
+        result.push('\n');
+    }
+
+    result
+}
+
+fn deserialize(source: &str) -> AppResult<Vec<Modifier>> {
+    let mut result = vec![];
+
+    for line in source.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        let che = parse_che(&fields).ok_or_else(|| AppError::InvalidValue(o!(line)))?;
+        result.push(Modifier { che, search_highlight: false });
+    }
+
+    Ok(result)
+}
+
+fn parse_che(fields: &[&str]) -> Option<Che> {
+    let field = |i: usize| fields.get(i).cloned();
+    let f64_at = |i: usize| field(i)?.parse::<f64>().ok();
+    let bool_at = |i: usize| match field(i)? { "1" => Some(true), "0" => Some(false), _ => None };
+    let color_at = |i: usize| Color::from_hex(field(i)?);
+    let region_at = |i: usize| Some(Region::new(f64_at(i)?, f64_at(i + 1)?, f64_at(i + 2)?, f64_at(i + 3)?));
+    let shape_at = |i: usize| field(i)?.parse::<Shape>().ok();
+
+    let che = match field(0)? {
+        "nova" => Che::Nova(Nova {
+            center: (f64_at(1)?, f64_at(2)?),
+            radius: f64_at(3)?,
+            n_spokes: field(4)?.parse().ok()?,
+            random_hue: f64_at(5)?,
+            color: color_at(6)?,
+            seed: Seed::decode(field(7)?)?,
+            threads: match field(8)? {
+                "-" => None,
+                it => Some(it.parse().ok()?),
+            },
+        }),
+        "fill" => {
+            let operator = match field(7)? {
+                "-" => None,
+                it => Some(it.parse().ok()?),
+            };
+            Che::Fill(shape_at(1)?, region_at(2)?, color_at(6)?, operator, bool_at(8)?)
+        },
+        name @ "blur" | name @ "pixelate" | name @ "smear" => {
+            let size = f64_at(1)?;
+            let effect = match name {
+                "blur" => Effect::Blur(size),
+                "pixelate" => Effect::Pixelate(size),
+                _ => Effect::Smear(size),
+            };
+            Che::Effect(effect, shape_at(2)?, region_at(3)?)
+        },
+        "text" => Che::Text(Text {
+            position: Coord { x: f64_at(1)?, y: f64_at(2)? },
+            size: f64_at(3)?,
+            font: unescape(field(4)?),
+            bold: bool_at(5)?,
+            color: color_at(6)?,
+            outline: match field(7)? {
+                "-" => None,
+                it => Some(Color::from_hex(it)?),
+            },
+            outline_width: f64_at(8)?,
+            text: unescape(field(9)?),
+        }),
+        name @ "line" | name @ "arrow" => {
+            let points = fields.get(3..)?.chunks(2).map(|it| {
+                Some(Coord { x: it.first()?.parse().ok()?, y: it.get(1)?.parse().ok()? })
+            }).collect::<Option<Vec<Coord>>>()?;
+            let line = Line { width: f64_at(1)?, color: color_at(2)?, points };
+            if name == "arrow" { Che::Arrow(line) } else { Che::Line(line) }
+        },
+        _ => return None,
+    };
+
+    Some(che)
+}
+
+fn region_fields(region: &Region) -> Vec<String> {
+    vec![s!(region.left), s!(region.top), s!(region.right), s!(region.bottom)]
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut result = o!("");
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+
+
+#[cfg(test)]#[test]
+fn test_serialize() {
+    use crate::cherenkov::Operator;
+    use crate::entry::EntryType;
+
+    let key: Key = (EntryType::Image, o!("/tmp/a.png"), 0);
+    let color = Color::new4(1, 2, 3, 128);
+    let region = Region::new(0.125, 0.2, 0.5, 1.0 / 3.0);
+    let ches = vec![
+        Che::Nova(Nova { center: (0.1, 0.2), color, n_spokes: 100, radius: 0.3, random_hue: 0.0, seed: Seed::new(Some("chrysoberyl")), threads: None }),
+        Che::Fill(Shape::Ellipse, region, color, Some(Operator(cairo::Operator::Multiply)), true),
+        Che::Fill(Shape::Rectangle, region, color, None, false),
+        Che::Effect(Effect::Pixelate(0.02), Shape::Circle, region),
+        Che::Text(Text { bold: true, color, font: o!("sans\tserif"), outline: None, outline_width: 0.1, position: Coord { x: 0.5, y: -0.25 }, size: 0.05, text: o!("a\\b\nc") }),
+        Che::Arrow(Line { color, points: vec![Coord { x: 0.0, y: 0.1 }, Coord { x: 0.7, y: 0.9 }], width: 0.005 }),
+    ];
+
+    let serialized = serialize(&key, &ches.iter().collect::<Vec<&Che>>());
+    let deserialized = deserialize(&serialized).unwrap();
+    assert_eq!(deserialized.len(), ches.len());
+    for (a, b) in ches.iter().zip(deserialized.iter()) {
+        assert_eq!(format!("{:?}", a), format!("{:?}", b.che));
+    }
+
+    assert!(deserialize("fill\tsquare\t0\t0\t1\t1\t#000000ff\t-\t0").is_err());
+    assert!(deserialize("line\t0.1\t#000000ff\t0.1").is_err());
+    assert_eq!(unescape(&escape("\\t\t\n")), "\\t\t\n");
+}
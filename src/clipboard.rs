@@ -23,7 +23,7 @@ pub fn get_operations(selection: ClipboardSelection, as_operation: bool, meta: O
     {
         let uris = cb.wait_for_uris();
         if !uris.is_empty() {
-            return Ok(uris.into_iter().enumerate().map(|(index, uri)| Operation::PushURL(uri, meta.clone(), force, show && index == 0, None)).collect())
+            return Ok(uris.into_iter().enumerate().map(|(index, uri)| Operation::PushURL(uri, meta.clone(), force, show && index == 0, None, None)).collect())
         }
     }
 
@@ -48,6 +48,11 @@ pub fn store(selection: ClipboardSelection, pixbuf: &Pixbuf) {
     cb.set_image(pixbuf);
 }
 
+pub fn store_text(selection: ClipboardSelection, text: &str) {
+    let cb = from_selection(selection);
+    cb.set_text(text);
+}
+
 fn from_pixbuf(pixbuf: &Pixbuf) -> AppResult<Vec<u8>> {
     let (width, height) = (pixbuf.get_width(), pixbuf.get_height());
     let surface = ImageSurface::create(Format::ARgb32, width, height).unwrap();
@@ -48,6 +48,37 @@ impl Color {
         (to_f(self.r), to_f(self.g), to_f(self.b), to_f(self.a))
     }
 
+    /** `#RRGGBBAA` **/
+    pub fn to_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+    }
+
+    /** `#rrggbb` for opaque colors, otherwise `#rrggbbaa` **/
+    pub fn to_short_hex(self) -> String {
+        if self.a == 255 {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            self.to_hex()
+        }
+    }
+
+    /** `rgb(R, G, B)` for opaque colors, otherwise `rgba(R, G, B, A)` **/
+    pub fn to_css(self) -> String {
+        if self.a == 255 {
+            format!("rgb({}, {}, {})", self.r, self.g, self.b)
+        } else {
+            format!("rgba({}, {}, {}, {:.3})", self.r, self.g, self.b, to_f(self.a))
+        }
+    }
+
+    pub fn from_hex(src: &str) -> Option<Color> {
+        if src.len() != 9 || !src.is_ascii() || !src.starts_with('#') {
+            return None;
+        }
+        let part = |i: usize| u8::from_str_radix(&src[i .. i + 2], 16).ok();
+        Some(Color::new4(part(1)?, part(3)?, part(5)?, part(7)?))
+    }
+
     pub fn option(self) -> Option<Color> {
         if self.a == 0 {
             None
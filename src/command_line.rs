@@ -17,6 +17,7 @@ use crate::expandable::Expandable;
 pub struct Initial {
     pub config_file: Option<String>,
     pub curl_threads: u8,
+    pub discard_queue: bool,
     pub encodings: Vec<EncodingRef>,
     pub enforce_gtk_theme: bool,
     pub entries: Vec<Entry>,
@@ -41,6 +42,7 @@ impl Default for Initial {
         Initial {
             config_file: None,
             curl_threads: 3,
+            discard_queue: false,
             encodings: vec![],
             enforce_gtk_theme: true,
             entries: vec![],
@@ -120,6 +122,7 @@ fn parse_option(arg: &str, args: &mut Args, init: &mut Initial) -> Result<bool,
             return not_enough();
         },
         "--no-config" => init.load_config = false,
+        "--discard-queue" => init.discard_queue = true,
         "--role" => if let Some(value) = args.next() {
             init.window_role = value;
         } else {
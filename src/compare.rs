@@ -0,0 +1,53 @@
+
+use cairo::{Context, Format, ImageSurface};
+use gdk::prelude::ContextExt;
+
+use crate::cherenkov::Operator;
+use crate::gtk_utils::new_pixbuf_from_surface;
+use crate::image::StaticImageBuffer;
+use crate::state::Compare;
+
+
+
+/** Render `other` over `base`. `other` is stretched to the size of `base` **/
+pub fn compose(base: &StaticImageBuffer, other: &StaticImageBuffer, compare: Compare, operator: Operator, ratio: f64) -> StaticImageBuffer {
+    let size = base.get_fit_size();
+    let other_size = other.get_fit_size();
+    let (width, height) = (f64!(other_size.width), f64!(other_size.height));
+
+    let surface = ImageSurface::create(Format::ARgb32, size.width, size.height).unwrap();
+    let context = Context::new(&surface);
+
+    context.set_source_pixbuf(&base.get_pixbuf(), 0.0, 0.0);
+    context.paint();
+
+    context.save();
+    context.scale(f64!(size.width) / width, f64!(size.height) / height);
+    context.set_source_pixbuf(&other.get_pixbuf(), 0.0, 0.0);
+    match compare {
+        Compare::Difference => {
+            context.set_operator(operator.0);
+            context.paint();
+        },
+        Compare::OnionSkin =>
+            context.paint_with_alpha(ratio),
+        Compare::Slider => {
+            let x = width * ratio;
+            context.rectangle(x, 0.0, width - x, height);
+            context.fill();
+        },
+        Compare::Off | Compare::Synced => (),
+    }
+    context.restore();
+
+    if compare == Compare::Slider {
+        let x = f64!(size.width) * ratio;
+        context.set_source_rgba(1.0, 0.0, 0.0, 0.8);
+        context.set_line_width(1.0);
+        context.move_to(x, 0.0);
+        context.line_to(x, f64!(size.height));
+        context.stroke();
+    }
+
+    StaticImageBuffer::new_from_pixbuf(&new_pixbuf_from_surface(&surface), base.original_size)
+}
@@ -0,0 +1,118 @@
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use cairo::{Context, Format, ImageSurface};
+use gdk::prelude::ContextExt;
+use gdk_pixbuf::{Pixbuf, PixbufExt};
+
+use crate::errors::{AppError, AppResultU};
+use crate::gtk_utils::new_pixbuf_from_surface;
+use crate::size::{CoordPx, Region, Size};
+
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fixed {
+    Aspect(u32, u32), /* width, height */
+    Size(Size),
+}
+
+
+/** The pixel rectangle in the image of `width` x `height` **/
+pub fn crop_rect(width: i32, height: i32, region: &Region, fixed: Option<Fixed>) -> Option<CoordPx> {
+    let clamp = |v: f64, limit: i32| min!(max!(v.round() as i32, 0), limit);
+
+    let (mut left, mut top) = (clamp(region.left * f64!(width), width), clamp(region.top * f64!(height), height));
+    let (right, bottom) = (clamp(region.right * f64!(width), width), clamp(region.bottom * f64!(height), height));
+    let (mut w, mut h) = (right - left, bottom - top);
+
+    match fixed {
+        Some(Fixed::Aspect(aw, ah)) => {
+            let (aw, ah) = (i64::from(aw), i64::from(ah));
+            if aw == 0 || ah == 0 {
+                return None;
+            }
+            // Shrink the longer side and keep the center
+            let (ow, oh) = (w, h);
+            if i64::from(w) * ah > i64::from(h) * aw {
+                w = (i64::from(h) * aw / ah) as i32;
+            } else {
+                h = (i64::from(w) * ah / aw) as i32;
+            }
+            left += (ow - w) / 2;
+            top += (oh - h) / 2;
+        },
+        Some(Fixed::Size(size)) => {
+            let (cx, cy) = (left + w / 2, top + h / 2);
+            w = min!(size.width, width);
+            h = min!(size.height, height);
+            left = min!(max!(cx - w / 2, 0), width - w);
+            top = min!(max!(cy - h / 2, 0), height - h);
+        },
+        None => (),
+    }
+
+    if w <= 0 || h <= 0 {
+        return None;
+    }
+
+    Some(CoordPx { x: left, y: top, width: w as u32, height: h as u32 })
+}
+
+pub fn crop(pixbuf: &Pixbuf, rect: &CoordPx) -> Pixbuf {
+    let surface = ImageSurface::create(Format::ARgb32, rect.width as i32, rect.height as i32).unwrap();
+    let context = Context::new(&surface);
+    context.set_source_pixbuf(pixbuf, -f64!(rect.x), -f64!(rect.y));
+    context.paint();
+    new_pixbuf_from_surface(&surface)
+}
+
+/** The name to save. The extension is replaced with `png` if the format is not writable **/
+pub fn file_name<T: AsRef<Path>>(source_name: &T) -> PathBuf {
+    let mut result = source_name.as_ref().to_path_buf();
+    if pixbuf_type(&result).is_none() {
+        result.set_extension("png");
+    }
+    result
+}
+
+/** Save in the format for the extension of `path` (PNG for unknown extensions) **/
+pub fn save<T: AsRef<Path>>(pixbuf: &Pixbuf, path: &T) -> AppResultU {
+    let path = path.as_ref();
+    let pixbuf_type = pixbuf_type(path).unwrap_or("png");
+    let options: &[(&str, &str)] = if pixbuf_type == "jpeg" { &[("quality", "95")] } else { &[] };
+    pixbuf.savev(path, pixbuf_type, options).map_err(AppError::from)
+}
+
+fn pixbuf_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension().and_then(OsStr::to_str)?.to_lowercase();
+    let result = match &*ext {
+        "bmp" => "bmp",
+        "jpeg" | "jpg" => "jpeg",
+        "png" => "png",
+        "tif" | "tiff" => "tiff",
+        _ => return None,
+    };
+    Some(result)
+}
+
+
+
+#[cfg(test)]#[test]
+fn test_crop_rect() {
+    let rect = |x, y, width, height| Some(CoordPx { x, y, width, height });
+    let region = Region::new(0.1, 0.2, 0.5, 0.6);
+
+    assert_eq!(crop_rect(100, 50, &region, None), rect(10, 10, 40, 20));
+    assert_eq!(crop_rect(100, 50, &Region::full(), None), rect(0, 0, 100, 50));
+    assert_eq!(crop_rect(100, 50, &region, Some(Fixed::Aspect(1, 1))), rect(20, 10, 20, 20));
+    assert_eq!(crop_rect(100, 50, &region, Some(Fixed::Aspect(4, 1))), rect(10, 15, 40, 10));
+    assert_eq!(crop_rect(100, 50, &region, Some(Fixed::Aspect(0, 1))), None);
+    assert_eq!(crop_rect(100, 50, &region, Some(Fixed::Size(Size::new(10, 10)))), rect(25, 15, 10, 10));
+    assert_eq!(crop_rect(100, 50, &Region::new(0.0, 0.0, 0.1, 0.1), Some(Fixed::Size(Size::new(30, 80)))), rect(0, 0, 30, 50));
+    assert_eq!(crop_rect(100, 50, &Region::new(0.5, 0.5, 0.5, 0.5), None), None);
+
+    assert_eq!(file_name(&"a/b.JPG"), PathBuf::from("a/b.JPG"));
+    assert_eq!(file_name(&"a/b.gif"), PathBuf::from("a/b.png"));
+}
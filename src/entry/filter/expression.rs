@@ -56,6 +56,8 @@ pub enum EValue {
 pub enum EVariable {
     ArchivePage,
     AspectRatio,
+    Black,
+    Brightness,
     CurrentPage,
     Dimentions,
     Extension,
@@ -66,6 +68,7 @@ pub enum EVariable {
     Path,
     RealPages,
     Type,
+    White,
     Width,
 }
 
@@ -73,6 +76,8 @@ pub enum EVariable {
 pub enum EBVariable {
     Active, // AppWindowActive
     Animation,
+    Duplicated,
+    SimilarToCurrent,
     Valid,
 }
 
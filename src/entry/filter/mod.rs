@@ -3,7 +3,8 @@ use globset::GlobMatcher;
 
 use crate::app::info::AppInfo;
 use crate::entry::info::EntryInfo;
-use crate::entry::{Entry, EntryContent};
+use crate::entry::{Entry, EntryContent, Key};
+use crate::phash;
 use crate::resolution;
 use crate::size::Size;
 
@@ -18,11 +19,12 @@ use self::expression::*;
 struct Info<'a> {
     app: &'a AppInfo,
     entry: &'a EntryInfo,
+    key: &'a Key,
 }
 
 impl Expr {
     pub fn evaluate(&self, entry: &Entry, app_info: &AppInfo) -> bool {
-        let info = Info { app: app_info, entry: &entry.info };
+        let info = Info { app: app_info, entry: &entry.info, key: &entry.key };
         eval(&info, &entry.content, self)
     }
 }
@@ -96,6 +98,8 @@ fn eval_bool(info: &Info, content: &EntryContent, b: &EBool) -> bool {
             return match *name {
                 Active => info.app.active,
                 Animation => info.entry.lazy(content, |it| it.is_animated),
+                Duplicated => info.app.phashes.is_duplicated(info.key),
+                SimilarToCurrent => is_similar_to_current(info),
                 Valid => info.entry.lazy(content, |it| it.valid),
             }
         },
@@ -169,6 +173,9 @@ fn eval_variable(info: &Info, content: &EntryContent, v: EVariable) -> Option<i6
         Pages => Some(info.app.pages as i64),
         RealPages => Some(info.app.real_pages as i64),
         FileSize => info.entry.lazy(content, |it| it.file_size).map(|it| it as i64),
+        Black => info.app.statistics.get(info.key).map(|it| percent(it.black())),
+        Brightness => info.app.statistics.get(info.key).map(|it| it.brightness().round() as i64),
+        White => info.app.statistics.get(info.key).map(|it| percent(it.white())),
         AspectRatio | Type | Path | Name | Extension => None,
     }
 }
@@ -185,10 +192,20 @@ fn eval_variable_as_s(info: &Info, content: &EntryContent, v: EVariable) -> Opti
         Extension => info.entry.strict.extension.clone(),
         Type => Some(o!(info.entry.strict.entry_type)),
         Name => Some(info.entry.strict.name.clone()),
-        ArchivePage | CurrentPage | Pages | RealPages | Dimentions | Width | Height | FileSize => None,
+        ArchivePage | CurrentPage | Pages | RealPages | Dimentions | Width | Height | FileSize | Black | Brightness | White => None,
     }
 }
 
+fn is_similar_to_current(info: &Info) -> bool {
+    if_let_some!(current = info.app.current_phash, false);
+    if_let_some!(hash = info.app.phashes.get(info.key), false);
+    phash::distance(current, hash) <= info.app.similarity_threshold
+}
+
+fn percent(ratio: f64) -> i64 {
+    (ratio * 100.0).round() as i64
+}
+
 fn resolution_match(dims: Option<Size>, w: i64, h: i64) -> bool {
     if_let_some!(dim = dims, false);
     i64!(dim.width) == w && i64!(dim.height) == h
@@ -78,7 +78,10 @@ fn variable() -> Parser<char, EValue> {
         gen("real-pages", RealPages) |
         gen("width", Width) |
         gen("filesize", FileSize) |
-        gen("ratio", AspectRatio)
+        gen("ratio", AspectRatio) |
+        gen("black", Black) |
+        gen("brightness", Brightness) |
+        gen("white", White)
 }
 
 fn value() -> Parser<char, EValue> {
@@ -131,7 +134,7 @@ fn bool_variable() -> Parser<char, EBool> {
         seq(name).map(move |_| EBool::Variable(var))
     }
 
-    gen("active", Active) | gen("animation", Animation) | gen("valid", Valid)
+    gen("active", Active) | gen("animation", Animation) | gen("duplicated", Duplicated) | gen("similar-to-current", SimilarToCurrent) | gen("valid", Valid)
 }
 
 fn lit_true() -> Parser<char, EBool> {
@@ -252,6 +255,9 @@ fn test_parser() {
     assert_parse("width < 200 and height < 400");
     assert_parse("width < 200 and height < 400 and extension == <jpg>");
     assert_parse("valid");
+    assert_parse("similar-to-current");
+    assert_parse("duplicated and not (similar-to-current)");
+    assert_parse("brightness < 10 or black > 95 or white >= 99");
 
     assert_parse("when path == <google> width < 200");
     assert_parse("unless path == <google> width < 200");
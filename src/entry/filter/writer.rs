@@ -90,6 +90,8 @@ fn write_bool(b: &EBool, out: &mut String) {
             match *name {
                 Active => sprint!(out, "active"),
                 Animation => sprint!(out, "animation"),
+                Duplicated => sprint!(out, "duplicated"),
+                SimilarToCurrent => sprint!(out, "similar-to-current"),
                 Valid => sprint!(out, "valid"),
             }
         },
@@ -111,6 +113,8 @@ fn write_value(v: &EValue, out: &mut String) {
         Variable(ref v) => match *v {
             ArchivePage => sprint!(out, "archive-page"),
             AspectRatio => sprint!(out, "ratio"),
+            Black => sprint!(out, "black"),
+            Brightness => sprint!(out, "brightness"),
             CurrentPage => sprint!(out, "current-page"),
             Dimentions => sprint!(out, "dimensions"),
             Extension => sprint!(out, "extension"),
@@ -119,6 +123,7 @@ fn write_value(v: &EValue, out: &mut String) {
             RealPages => sprint!(out, "real-pages"),
             Path => sprint!(out, "path"),
             Type => sprint!(out, "type"),
+            White => sprint!(out, "white"),
             Width => sprint!(out, "width"),
             Name => sprint!(out, "name"),
             FileSize => sprint!(out, "filesize"),
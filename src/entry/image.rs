@@ -1,11 +1,13 @@
 
+use std::cell::Cell;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
+use std::rc::Rc;
 
 use cairo::{Context, ImageSurface, Format};
 use gdk::prelude::ContextExt;
-use gdk_pixbuf::{PixbufLoader, PixbufLoaderExt};
+use gdk_pixbuf::{Pixbuf, PixbufLoader, PixbufLoaderExt};
 use image_meta::{ImageMeta, self};
 use log::trace;
 
@@ -19,6 +21,9 @@ use crate::state::Drawing;
 use crate::util::path::path_to_str;
 
 
+/** Upper limit of pixels for a vector image rendered at the cell resolution **/
+const VECTOR_MAX_PIXELS: f64 = 8192.0 * 8192.0;
+
 
 #[derive(Clone, Default, Eq, Hash, PartialEq)]
 pub struct Imaging {
@@ -34,7 +39,8 @@ impl Imaging {
 
 pub fn get_image_buffer(entry_content: &EntryContent, imaging: &Imaging) -> AppResult<ImageBuffer> {
     trace!("entry/image/get_image_buffer");
-    if imaging.drawing.animation && is_animation(entry_content) {
+    // Animations are rendered as static images to be adjusted
+    if imaging.drawing.animation && imaging.drawing.adjustment.is_identity() && is_animation(entry_content) {
         Ok(get_animation_buffer(entry_content).map(ImageBuffer::Animation)?)
     } else {
         get_static_image_buffer(entry_content, imaging).map(ImageBuffer::Static)
@@ -54,8 +60,12 @@ pub fn get_static_image_buffer(entry_content: &EntryContent, imaging: &Imaging)
             make_scaled(content, imaging),
         Pdf(ref path, index) =>
             Ok(make_scaled_from_pdf(&**path, index, imaging)),
-        Message(ref message) =>
+        Message(ref message, _) =>
             Err(AppError::Standard(o!(message))),
+        Video(ref video, index) => {
+            let frame = video.frame(index)?.ok_or(AppError::Fixed("Extracting the frame"))?;
+            make_scaled_from_file(path_to_str(&frame), imaging)
+        },
     }
 }
 
@@ -81,15 +91,7 @@ fn is_animation(entry_content: &EntryContent) -> bool {
 }
 
 fn make_scaled(buffer: &[u8], imaging: &Imaging) -> AppResult<StaticImageBuffer> {
-    let loader = PixbufLoader::new();
-    loader.write(buffer)?;
-
-    if loader.close().is_err() {
-        return Err(AppError::Fixed("Invalid image data"))
-    }
-
-    let source = loader.get_pixbuf().ok_or(AppError::Fixed("Invalid image"))?;
-    let original = Size::from_pixbuf(&source);
+    let (source, original) = load_pixbuf(buffer, imaging)?;
     let (scale, fitted, clipped_region) = original.rotate(imaging.drawing.rotation).fit_with_clipping(imaging.cell_size, &imaging.drawing);
 
     let result = {
@@ -103,14 +105,71 @@ fn make_scaled(buffer: &[u8], imaging: &Imaging) -> AppResult<StaticImageBuffer>
         }
         context_rotate(&context, original, imaging.drawing.rotation);
         context_flip(&context, original, imaging.drawing.horizontal_flip, imaging.drawing.vertical_flip);
+        let rendered = Size::from_pixbuf(&source);
+        if rendered != original {
+            context.scale(f64!(original.width) / f64!(rendered.width), f64!(original.height) / f64!(rendered.height));
+        }
         context.set_source_pixbuf(&source, 0.0, 0.0);
         context.paint();
         new_pixbuf_from_surface(&surface)
     };
+    imaging.drawing.adjustment.apply(&result);
 
     Ok(StaticImageBuffer::new_from_pixbuf(&result, Some(original)))
 }
 
+/** returns (pixbuf, original size). Vector images are rendered at the fitted resolution in the same pass **/
+fn load_pixbuf(buffer: &[u8], imaging: &Imaging) -> AppResult<(Pixbuf, Size)> {
+    let loader = PixbufLoader::new();
+    let original = Rc::new(Cell::new(None));
+
+    {
+        let original = Rc::clone(&original);
+        let imaging = imaging.clone();
+        loader.connect_size_prepared(move |loader, width, height| {
+            let size = Size::new(width, height);
+            original.set(Some(size));
+            let is_vector = loader.get_format().and_then(|it| it.get_name()).map_or(false, |it| it == "svg");
+            if !is_vector {
+                return;
+            }
+            let (scale, _, _) = size.rotate(imaging.drawing.rotation).fit_with_clipping(imaging.cell_size, &imaging.drawing);
+            if let Some(rendered) = vector_size(size, scale) {
+                loader.set_size(rendered.width, rendered.height);
+            }
+        });
+    }
+
+    loader.write(buffer)?;
+
+    if loader.close().is_err() {
+        return Err(AppError::Fixed("Invalid image data"))
+    }
+
+    let pixbuf = loader.get_pixbuf().ok_or(AppError::Fixed("Invalid image"))?;
+    let original = original.get().unwrap_or_else(|| Size::from_pixbuf(&pixbuf));
+    Ok((pixbuf, original))
+}
+
+/** Size to render the vector image at the fitted resolution, or `None` if the intrinsic size is enough **/
+fn vector_size(original: Size, scale: f64) -> Option<Size> {
+    let pixels = f64!(original.dimensions()) * scale * scale;
+    let scale = if VECTOR_MAX_PIXELS < pixels {
+        scale * (VECTOR_MAX_PIXELS / pixels).sqrt()
+    } else {
+        scale
+    };
+
+    let result = Size::new(
+        max!((f64!(original.width) * scale).round() as i32, 1),
+        max!((f64!(original.height) * scale).round() as i32, 1));
+    if result == original {
+        None
+    } else {
+        Some(result)
+    }
+}
+
 fn make_scaled_from_file(path: &str, imaging: &Imaging) -> AppResult<StaticImageBuffer> {
     let mut file = File::open(path)?;
     let mut buffer: Vec<u8> = vec![];
@@ -122,6 +181,7 @@ fn make_scaled_from_pdf<T: AsRef<Path>>(pdf_path: &T, index: usize, imaging: &Im
     let document = PopplerDocument::new_from_file(pdf_path);
     let page = document.nth_page(index);
     let pixbuf = page.get_pixbuf(imaging.cell_size, &imaging.drawing);
+    imaging.drawing.adjustment.apply(&pixbuf);
     let size = page.get_size();
     StaticImageBuffer::new_from_pixbuf(&pixbuf, Some(size))
 }
@@ -136,8 +196,19 @@ fn get_meta(entry_content: &EntryContent) -> Option<Result<ImageMeta, image_meta
             Some(image_meta::load_from_buf(&entry.content)),
         Memory(ref content, _) =>
             Some(image_meta::load_from_buf(content)),
-        Pdf(_,  _) | Message(_) =>
+        Pdf(_,  _) | Message(_, _) | Video(_, _) =>
             None
     }
 }
 
+
+
+#[cfg(test)]#[test]
+fn test_vector_size() {
+    assert_eq!(vector_size(Size::new(100, 50), 1.0), None);
+    assert_eq!(vector_size(Size::new(100, 50), 2.0), Some(Size::new(200, 100)));
+    assert_eq!(vector_size(Size::new(100, 50), 0.5), Some(Size::new(50, 25)));
+    assert_eq!(vector_size(Size::new(100, 50), 0.001), Some(Size::new(1, 1)));
+    // Capped at VECTOR_MAX_PIXELS
+    assert_eq!(vector_size(Size::new(100, 100), 1000.0), Some(Size::new(8192, 8192)));
+}
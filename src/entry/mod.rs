@@ -22,6 +22,7 @@ use crate::file_extension::{is_valid_image_filename};
 use crate::filterable_vec::{FilterableVec, Pred};
 use crate::shorter::*;
 use crate::util::path::path_to_str;
+use crate::video::Video;
 
 pub mod image;
 pub mod filter;
@@ -36,6 +37,7 @@ type FilterPred = Pred<Entry, AppInfo>;
 pub struct EntryContainer {
     serial: Serial,
     entries: FilterableVec<Entry, AppInfo>,
+    refetching: HashSet<String>, /* URLs of the offline placeholders */
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -56,7 +58,8 @@ pub enum EntryContent {
     Archive(Arc<PathBuf>, ArchiveEntry),
     Pdf(Arc<PathBuf>, usize),
     Memory(Vec<u8>, String),
-    Message(String),
+    Message(String, Option<EntryType>), /* message, entry type of the URL for the offline placeholder */
+    Video(Arc<Video>, usize),
 }
 
 pub type Meta = Arc<Vec<MetaEntry>>;
@@ -84,6 +87,7 @@ pub enum EntryType {
     Archive,
     Memory,
     Message,
+    Video,
 }
 
 
@@ -163,10 +167,14 @@ impl EntryContent {
                 (EntryType::PDF,
                  url.unwrap_or_else(|| path_to_str(&**path).to_owned()),
                  index),
-            Message(ref message) =>
+            Message(ref message, _) =>
                 (EntryType::Message,
                  o!(message),
                  0),
+            Video(ref video, index) =>
+                (EntryType::Video,
+                 url.unwrap_or_else(|| path_to_str(&video.path).to_owned()),
+                 index),
         }
     }
 
@@ -178,7 +186,9 @@ impl EntryContent {
                 Some(path.to_path_buf()),
             Image(ref path) =>
                 Some(path.to_path_buf()),
-            Memory(_, _) | Message(_) =>
+            Video(ref video, _) =>
+                Some(video.path.clone()),
+            Memory(_, _) | Message(_, _) =>
                 None
         }
     }
@@ -190,6 +200,7 @@ impl EntryContainer {
         EntryContainer {
             serial: Serial(0),
             entries: FilterableVec::new(),
+            refetching: HashSet::new(),
         }
     }
 
@@ -210,6 +221,7 @@ impl EntryContainer {
 
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.refetching.clear();
     }
 
     pub fn remove(&mut self, app_info:&AppInfo, indices: &HashSet<usize>) {
@@ -433,6 +445,12 @@ impl EntryContainer {
         self.push_entry(app_info, Entry::new(serial, content, meta, url), force);
     }
 
+    pub fn push_video_entry(&mut self, app_info: &AppInfo, video: &Arc<Video>, index: usize, meta: Option<Meta>, force: bool, url: Option<String>) {
+        let content = EntryContent::Video(Arc::clone(video), index);
+        let serial = self.new_serial();
+        self.push_entry(app_info, Entry::new(serial, content, meta, url), force);
+    }
+
     pub fn search(&self, key: &SearchKey) -> Option<usize> {
         self.entries.iter().position(|it| key.matches(it))
     }
@@ -505,11 +523,12 @@ impl EntryContainer {
         Ok(())
     }
 
-    pub fn push_message(&mut self, app_info: &AppInfo, message: String, meta: Option<Meta>) {
+    /** `url` is given for the placeholder of the URL not downloaded yet **/
+    pub fn push_message(&mut self, app_info: &AppInfo, message: String, meta: Option<Meta>, url: Option<String>, entry_type: Option<EntryType>) {
         let serial = self.new_serial();
         self.push_entry(
             app_info,
-            Entry::new(serial, EntryContent::Message(message), meta, None),
+            Entry::new(serial, EntryContent::Message(message, entry_type), meta, url),
             true);
     }
 
@@ -522,7 +541,7 @@ impl EntryContainer {
 
         match entry.content {
             Image(ref path) => is_valid_image_filename(path),
-            Archive(_, _) | Pdf(_,  _) | Memory(_, _) | Message(_) => true, // FIXME archive
+            Archive(_, _) | Pdf(_,  _) | Memory(_, _) | Message(_, _) | Video(_, _) => true, // FIXME archive
         }
     }
 
@@ -530,6 +549,24 @@ impl EntryContainer {
         self.entries.update_filter(app_info, dynamic, current_index, pred)
     }
 
+    /** The entries of the URLs will replace their offline placeholders (See `gather_refetched`) **/
+    pub fn refetch(&mut self, urls: HashSet<String>) {
+        self.refetching = urls;
+    }
+
+    /** Move the fetched entries to the positions of their offline placeholders, and remove the placeholders **/
+    pub fn gather_refetched(&mut self, app_info: &AppInfo) -> bool {
+        if self.refetching.is_empty() {
+            return false;
+        }
+
+        let refetching = &self.refetching;
+        self.entries.gather(
+            app_info,
+            |entry| entry.url.as_ref().filter(|url| refetching.contains(url.as_str())).cloned(),
+            |entry| matches!(entry.content, EntryContent::Message(_, _)))
+    }
+
     pub fn delete(&mut self, app_info: &AppInfo, current_index: Option<usize>, pred: FilterPred) -> Option<usize> {
         self.entries.delete(app_info, current_index, pred)
     }
@@ -567,6 +604,8 @@ impl Entry {
                 gen(&Path::new(name), index, None),
             Pdf(ref path, index) =>
                 gen(&**path, index, Some("png")),
+            Video(ref video, index) =>
+                gen(&video.path, index, Some("png")),
             _ => self.display_path(),
         };
 
@@ -619,7 +658,7 @@ impl SearchKey {
 impl EntryType {
     pub fn is_container(self) -> bool {
         use self::EntryType::*;
-        matches!(self, PDF | Archive)
+        matches!(self, PDF | Archive | Video)
     }
 }
 
@@ -635,6 +674,7 @@ impl fmt::Display for EntryType {
                 Archive => "archive",
                 Memory => "memory",
                 Message => "message",
+                Video => "video",
             };
 
         write!(f, "{}", result)
@@ -654,6 +694,8 @@ impl FromStr for EntryType {
                 Ok(EntryType::PDF),
             "message" =>
                 Ok(EntryType::Message),
+            "video" | "v" =>
+                Ok(EntryType::Video),
             _ =>
                 Err(format!("Invalid type: {}", src))
         }
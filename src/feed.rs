@@ -0,0 +1,258 @@
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+
+use url::Url;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::entry::{Meta, MetaEntry, new_opt_meta};
+use crate::errors::{AppError, AppResult};
+use crate::expandable::Expandable;
+use crate::operation::Operation;
+use crate::remote_cache::curl_options::CurlOptions;
+use crate::remote_cache::get_text;
+use crate::util::file::read_string;
+use crate::util::uri::is_url;
+
+
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Feed, /* RSS/Atom */
+    List, /* M3U/Plain text */
+}
+
+/** URLs already pushed for each source. To push only new items at the next poll **/
+#[derive(Clone, Default)]
+pub struct History(Arc<Mutex<HashMap<String, HashSet<String>>>>);
+
+#[derive(Debug, PartialEq)]
+enum Location {
+    Path(PathBuf),
+    Url(String),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Item {
+    pub date: Option<String>,
+    pub title: Option<String>,
+    pub url: String,
+}
+
+
+#[allow(clippy::too_many_arguments)]
+pub fn push(tx: Sender<Operation>, history: History, source: String, format: Format, options: CurlOptions, meta: Option<Meta>, force: bool, show: bool) {
+    spawn(move || {
+        let items = load(&source, &options).and_then(|text| {
+            match format {
+                Format::Feed => parse_feed(&text),
+                Format::List => Ok(parse_list(&text)),
+            }
+        });
+
+        let items = match items {
+            Ok(items) => items,
+            Err(err) => {
+                puts_error!(err, "at" => "feed", "for" => o!(&source));
+                return;
+            }
+        };
+
+        let mut history = history.0.lock().unwrap();
+        let pushed = history.entry(source.clone()).or_default();
+        let mut count = 0;
+
+        for item in items {
+            if pushed.contains(&item.url) {
+                continue;
+            }
+
+            let meta = item_meta(&meta, &item);
+            let show = show && count == 0;
+            let op = match locate(&source, &item.url) {
+                Ok(Location::Url(url)) =>
+                    Operation::PushURL(url, meta, force, show, None, None),
+                Ok(Location::Path(path)) =>
+                    Operation::Push(Expandable::expanded(s!(path.display())), meta, force, show),
+                Err(err) => {
+                    puts_error!(err, "at" => "feed", "for" => o!(&item.url));
+                    continue;
+                }
+            };
+            if tx.send(op).is_err() {
+                return;
+            }
+            pushed.insert(item.url);
+            count += 1;
+        }
+
+        puts_event!("feed/update", "source" => source, "new" => s!(count));
+    });
+}
+
+pub fn parse_feed(source: &str) -> AppResult<Vec<Item>> {
+    let mut result = vec![];
+    let mut current: Option<(Vec<String>, Item)> = None; /* enclosure URLs, item */
+    let mut text: Option<String> = None;
+
+    for event in EventReader::from_str(source) {
+        match event.map_err(|it| AppError::Standard(s!(it)))? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let attribute = |key: &str| attributes.iter().find(|it| it.name.local_name == key).map(|it| it.value.clone());
+                let prefix = name.prefix.as_ref().map(String::as_str);
+
+                match (prefix, name.local_name.as_str()) {
+                    (None, "item") | (None, "entry") =>
+                        current = Some((vec![], Item::default())),
+                    (None, "enclosure") | (Some("media"), "content") =>
+                        if let (Some((urls, _)), Some(url)) = (current.as_mut(), attribute("url")) {
+                            urls.push(url);
+                        },
+                    (None, "link") if attribute("rel").map_or(false, |it| it == "enclosure") =>
+                        if let (Some((urls, _)), Some(url)) = (current.as_mut(), attribute("href")) {
+                            urls.push(url);
+                        },
+                    (None, "title") | (None, "pubDate") | (None, "published") | (None, "updated") | (Some("dc"), "date") if current.is_some() =>
+                        text = Some(o!("")),
+                    _ => (),
+                }
+            },
+            XmlEvent::Characters(value) | XmlEvent::CData(value) =>
+                if let Some(text) = text.as_mut() {
+                    text.push_str(&value);
+                },
+            XmlEvent::EndElement { name } => {
+                match name.local_name.as_str() {
+                    "item" | "entry" if name.prefix.is_none() => if let Some((urls, item)) = current.take() {
+                        for url in urls {
+                            result.push(Item { url, title: item.title.clone(), date: item.date.clone() });
+                        }
+                    },
+                    "title" => if let (Some((_, item)), Some(value)) = (current.as_mut(), text.take()) {
+                        item.title = item.title.take().or_else(|| Some(o!(value.trim())));
+                    },
+                    "pubDate" | "published" | "updated" | "date" => if let (Some((_, item)), Some(value)) = (current.as_mut(), text.take()) {
+                        item.date = item.date.take().or_else(|| Some(o!(value.trim())));
+                    },
+                    _ => (),
+                }
+            },
+            _ => (),
+        }
+    }
+
+    Ok(result)
+}
+
+/** Lines starting with `#` are comments. `#EXTINF:<SECONDS>,<TITLE>` gives the title of the next item **/
+pub fn parse_list(source: &str) -> Vec<Item> {
+    let mut result = vec![];
+    let mut title = None;
+
+    for line in source.lines().map(str::trim) {
+        if line.starts_with("#EXTINF:") {
+            title = line.find(',').map(|index| o!(line[index + 1..].trim())).filter(|it| !it.is_empty());
+        } else if !line.is_empty() && !line.starts_with('#') {
+            result.push(Item { url: o!(line), title: title.take(), date: None });
+        }
+    }
+
+    result
+}
+
+/** Relative items are relative to the list (URL or file) **/
+fn locate(source: &str, item: &str) -> AppResult<Location> {
+    if is_url(item) {
+        return Ok(Location::Url(o!(item)));
+    }
+    if is_url(source) {
+        let url = Url::parse(source)?.join(item)?;
+        return Ok(Location::Url(s!(url)));
+    }
+    let path = Path::new(source).parent().map(|it| it.join(item)).unwrap_or_else(|| PathBuf::from(item));
+    Ok(Location::Path(path))
+}
+
+fn item_meta(base: &Option<Meta>, item: &Item) -> Option<Meta> {
+    let mut entries: Vec<MetaEntry> = base.as_ref().map(|it| it.to_vec()).unwrap_or_default();
+    if let Some(ref title) = item.title {
+        entries.push(MetaEntry { key: o!("title"), value: title.clone() });
+    }
+    if let Some(ref date) = item.date {
+        entries.push(MetaEntry { key: o!("date"), value: date.clone() });
+    }
+    new_opt_meta(entries)
+}
+
+fn load(source: &str, options: &CurlOptions) -> AppResult<String> {
+    if is_url(source) {
+        get_text(source, options, None)
+    } else {
+        read_string(source)
+    }
+}
+
+
+#[cfg(test)]#[test]
+fn test_parse_feed() {
+    let rss = r#"<?xml version="1.0"?>
+        <rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/">
+          <channel>
+            <title>Gallery</title>
+            <item>
+              <title>First</title>
+              <pubDate>Mon, 01 Jan 2024 00:00:00 GMT</pubDate>
+              <enclosure url="http://example.com/1.jpg" type="image/jpeg" />
+            </item>
+            <item>
+              <title><![CDATA[Second]]></title>
+              <media:content url="http://example.com/2.png" />
+            </item>
+            <item><title>No enclosure</title></item>
+          </channel>
+        </rss>"#;
+
+    assert_eq!(
+        parse_feed(rss).unwrap(),
+        vec![
+            Item { url: o!("http://example.com/1.jpg"), title: Some(o!("First")), date: Some(o!("Mon, 01 Jan 2024 00:00:00 GMT")) },
+            Item { url: o!("http://example.com/2.png"), title: Some(o!("Second")), date: None },
+        ]);
+
+    let atom = r#"<?xml version="1.0"?>
+        <feed xmlns="http://www.w3.org/2005/Atom">
+          <title>Gallery</title>
+          <entry>
+            <title>Atom</title>
+            <updated>2024-01-01T00:00:00Z</updated>
+            <link rel="alternate" href="http://example.com/page" />
+            <link rel="enclosure" href="http://example.com/a.jpg" />
+          </entry>
+        </feed>"#;
+
+    assert_eq!(
+        parse_feed(atom).unwrap(),
+        vec![Item { url: o!("http://example.com/a.jpg"), title: Some(o!("Atom")), date: Some(o!("2024-01-01T00:00:00Z")) }]);
+}
+
+#[cfg(test)]#[test]
+fn test_parse_list() {
+    let m3u = "#EXTM3U\n#EXTINF:-1,Cat\nhttp://example.com/cat.mp4\n\n# comment\n  images/dog.png  \n";
+    assert_eq!(
+        parse_list(m3u),
+        vec![
+            Item { url: o!("http://example.com/cat.mp4"), title: Some(o!("Cat")), date: None },
+            Item { url: o!("images/dog.png"), title: None, date: None },
+        ]);
+}
+
+#[cfg(test)]#[test]
+fn test_locate() {
+    assert_eq!(locate("http://example.com/lists/a.m3u", "http://example.net/x.png").unwrap(), Location::Url(o!("http://example.net/x.png")));
+    assert_eq!(locate("http://example.com/lists/a.m3u", "images/x.png").unwrap(), Location::Url(o!("http://example.com/lists/images/x.png")));
+    assert_eq!(locate("http://example.com/lists/a.m3u", "/x.png").unwrap(), Location::Url(o!("http://example.com/x.png")));
+    assert_eq!(locate("/home/me/a.m3u", "images/x.png").unwrap(), Location::Path(PathBuf::from("/home/me/images/x.png")));
+}
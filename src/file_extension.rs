@@ -18,6 +18,8 @@ pub fn get_entry_type_from_filename<T: AsRef<Path>>(path: &T) -> Option<EntryTyp
             Some(EntryType::PDF),
         "ani" | "bmp" | "cur" | "gif" | "icns" | "ico" | "j2k" | "jp2" | "jpc" | "jpe" | "jpeg" | "jpf" | "jpg" | "jpx" | "pbm" | "pgm" | "png" | "pnm" | "ppm" | "qif" | "qtif" | "svg" | "svg.gz" | "svgz" | "targa" | "tga" | "tif" | "tiff" | "xbm" | "xpm" =>
             Some(EntryType::Image),
+        "avi" | "flv" | "m4v" | "mkv" | "mov" | "mp4" | "mpeg" | "mpg" | "ogv" | "webm" | "wmv" =>
+            Some(EntryType::Video),
         _ =>
             None
     }
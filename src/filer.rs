@@ -71,6 +71,13 @@ impl FileOperation {
         file.write_all(source)?;
         Ok(())
     }
+
+    /** `write` writes the content to the given destination path **/
+    pub fn execute_with_writer<T: AsRef<Path>, F>(&self, source_name: &T, write: F) -> AppResultU
+    where F: FnOnce(&Path) -> AppResultU {
+        let dest = destination_path(source_name, &self.destination_directory, &self.destination_file, self.if_exist)?;
+        write(&dest)
+    }
 }
 
 
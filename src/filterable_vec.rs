@@ -177,6 +177,57 @@ impl<T: Hash + Eq + Sized + Ord, U> FilterableVec<T, U> {
         self.filter(info, None);
     }
 
+    /** Gather the entries of each group to the position of the first one of the group.
+        The placeholders are dropped from the groups which have the other entries **/
+    pub fn gather<K, F, P>(&mut self, info: &U, group_of: F, is_placeholder: P) -> bool
+    where K: Hash + Eq, F: Fn(&T) -> Option<K>, P: Fn(&T) -> bool {
+        enum Slot<E> {
+            Entry(E),
+            Group(usize),
+        }
+
+        let filled: HashSet<K> = self.original.iter().filter(|it| !is_placeholder(it)).filter_map(|it| group_of(it)).collect();
+        if filled.is_empty() {
+            return false;
+        }
+
+        let mut slots = vec![];
+        let mut groups: Vec<Vec<Arc<T>>> = vec![];
+        let mut group_indices = HashMap::new();
+
+        for entry in &self.original {
+            match group_of(entry).filter(|it| filled.contains(it)) {
+                Some(key) => {
+                    let index = *group_indices.entry(key).or_insert_with(|| {
+                        groups.push(vec![]);
+                        slots.push(Slot::Group(groups.len() - 1));
+                        groups.len() - 1
+                    });
+                    if !is_placeholder(entry) {
+                        groups[index].push(Arc::clone(entry));
+                    }
+                },
+                None =>
+                    slots.push(Slot::Entry(Arc::clone(entry))),
+            }
+        }
+
+        let mut gathered = Vec::with_capacity(self.original.len());
+        for slot in slots {
+            match slot {
+                Slot::Entry(entry) => gathered.push(entry),
+                Slot::Group(index) => gathered.append(&mut groups[index]),
+            }
+        }
+
+        let changed = gathered.len() != self.original.len() || gathered.iter().zip(&self.original).any(|(a, b)| !Arc::ptr_eq(a, b));
+        if changed {
+            self.original = gathered;
+            self.filter(info, None);
+        }
+        changed
+    }
+
     pub fn update_filter(&mut self, info: &U, dynamic: bool, index_before_filter: Option<usize>, pred: Option<Pred<T, U>>) -> Option<usize> {
         if dynamic {
             self.dynamic_pred = pred;
@@ -1,4 +1,5 @@
 
+use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::convert::Into;
 use std::default::Default;
@@ -6,6 +7,7 @@ use std::fmt;
 use std::fs::File;
 use std::ops;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::str::FromStr;
 use std::sync::mpsc::Sender;
 
@@ -14,8 +16,9 @@ use closet::clone_army;
 use enum_primitive::*;
 use gdk::{Cursor, CursorType, Display, DisplayExt, EventMask, WindowExt};
 use gdk_pixbuf::{Pixbuf, PixbufExt, PixbufAnimationExt};
+use glib::ObjectExt;
 use gtk::prelude::*;
-use gtk::{Adjustment, Align, Builder, Button, ComboBoxText, ComboBoxTextExt, CssProvider, CssProviderExt, Entry, EventBox, Grid, Image, Label, Layout, Overlay, RadioButton, Scale, ScrolledWindow, self, Stack, Switch, StyleContext, TextBuffer, TextView, Widget, WidgetExt, Window};
+use gtk::{Adjustment, Align, Continue, Builder, Button, ComboBoxText, ComboBoxTextExt, CssProvider, CssProviderExt, Entry, EventBox, Grid, Image, Label, Layout, Overlay, RadioButton, Scale, ScrolledWindow, self, Stack, Switch, StyleContext, TextBuffer, TextView, Widget, WidgetExt, Window};
 use maplit::{convert_args, hashmap};
 
 use crate::completion::gui::CompleterUI;
@@ -31,6 +34,10 @@ use crate::util::num::feq;
 
 
 
+const SMOOTH_SCROLL_INTERVAL: u32 = 15; /* ms */
+const SMOOTH_SCROLL_STEPS: usize = 10;
+
+
 enum_from_primitive! {
     #[derive(Debug, PartialEq)]
     pub enum DropItemType {
@@ -56,8 +63,13 @@ pub struct Gui {
     label: Label,
     log_box: ScrolledWindow,
     log_buffer: TextBuffer,
+    loupe: Image,
     operation_box: gtk::Box,
     overlaid_label: Label,
+    scroll_serial: Rc<RefCell<usize>>,
+    scroll_targets: Rc<RefCell<Vec<(Adjustment, f64)>>>,
+    scroll_sync: Rc<RefCell<ScrollSync>>,
+    statistics: Image,
     overlaid_status_bar: gtk::Box,
     status_bar: Layout,
     status_bar_inner: gtk::Box,
@@ -113,6 +125,26 @@ pub enum Position {
     BottomRight,
 }
 
+/** Keep the point at `ratio` of the image at `offset` pixels from the top-left of the cell **/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScrollAnchor {
+    pub ratio: (f64, f64),
+    pub offset: (f64, f64),
+}
+
+#[derive(Clone)]
+struct Slide {
+    serial: usize,
+    current: Rc<RefCell<usize>>,
+    targets: Rc<RefCell<Vec<(Adjustment, f64)>>>, /* Destinations of the running slides */
+}
+
+#[derive(Default)]
+struct ScrollSync {
+    enabled: bool,
+    syncing: bool,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Screen {
     Main,
@@ -230,6 +262,20 @@ impl Gui {
 
         let user_box = gtk::Box::new(Orientation::Vertical, 0);
 
+        let loupe = tap!(it = Image::new(), {
+            WidgetExt::set_name(&it, "loupe");
+            it.set_halign(Align::Start);
+            it.set_valign(Align::Start);
+        });
+
+        let statistics = tap!(it = Image::new(), {
+            WidgetExt::set_name(&it, "statistics");
+            it.set_halign(Align::End);
+            it.set_valign(Align::Start);
+            it.set_margin_top(8);
+            it.set_margin_end(8);
+        });
+
         let overlay = tap!(it = Overlay::new(), {
             WidgetExt::set_name(&it, "overlay");
             setup_drag(&it);
@@ -240,6 +286,8 @@ impl Gui {
             it.add_overlay(&user_box);
             it.show_all();
             it.add_overlay(&log_box);
+            it.add_overlay(&statistics);
+            it.add_overlay(&loupe);
         });
 
         let event_box = tap!(it = EventBox::new(), {
@@ -270,11 +318,16 @@ impl Gui {
             log_box,
             log_buffer,
             log_view,
+            loupe,
             operation_box,
             operation_entry,
             overlaid_label,
             overlaid_status_bar,
             overlay,
+            scroll_serial: Rc::new(RefCell::new(0)),
+            scroll_targets: Rc::new(RefCell::new(vec![])),
+            scroll_sync: Rc::new(RefCell::new(ScrollSync::default())),
+            statistics,
             status_bar,
             status_bar_inner,
             ui_event: None,
@@ -294,6 +347,13 @@ impl Gui {
         }
     }
 
+    pub fn anchor_scrolls(&self, anchor: &ScrollAnchor) {
+        self.slide(false);
+        for cell in self.cells(false) {
+            cell.anchor_scroll(anchor);
+        }
+    }
+
     pub fn cells(&self, reverse: bool) -> CellIterator {
         CellIterator { gui: self, index: 0, reverse }
     }
@@ -313,6 +373,28 @@ impl Gui {
         Size::new(width, height)
     }
 
+    /** Anchor at `coord` if it is on a cell, otherwise at the center of the first cell **/
+    pub fn get_scroll_anchor(&self, coord: Option<&CoordPx>) -> Option<ScrollAnchor> {
+        coord
+            .and_then(|coord| self.cells(false).find_map(|it| it.get_scroll_anchor(Some(coord))))
+            .or_else(|| self.cells.first().and_then(|it| it.get_scroll_anchor(None)))
+    }
+
+    /** The index of the cell under `coord` and the position on its image **/
+    pub fn get_position_on_image(&self, coord: &CoordPx, drawing: &Drawing, reverse: bool) -> Option<(usize, Coord)> {
+        self.cells(reverse).enumerate().find_map(|(index, cell)| {
+            cell.get_position_on_image(coord, drawing).map(|it| (index, it))
+        })
+    }
+
+    pub fn hide_loupe(&self) {
+        self.loupe.hide();
+    }
+
+    pub fn hide_statistics(&self) {
+        self.statistics.hide();
+    }
+
     pub fn len(&self) -> usize {
         self.cols() * self.rows()
     }
@@ -325,6 +407,17 @@ impl Gui {
         }
     }
 
+    pub fn pan_views(&self, dx: f64, dy: f64) {
+        self.slide(false);
+        for cell in self.cells(false) {
+            for &(horizontal, delta) in &[(true, dx), (false, dy)] {
+                if let Some(adj) = get_adjustment(&cell.window, horizontal) {
+                    set_scroll_value(&cell.window, horizontal, adj.get_value() - delta, &None);
+                }
+            }
+        }
+    }
+
     pub fn pop_operation_entry(&mut self) -> AppResult<Option<Operation>> {
         if_let_some!(result = self.operation_entry.get_text(), Ok(None));
         if result.is_empty() {
@@ -341,13 +434,17 @@ impl Gui {
         self.status_bar_inner.set_property_width_request(width);
     }
 
-    pub fn register_ui_events(&mut self, skip: usize, time_to_hide_pointer: Option<u32>, app_tx: &Sender<Operation>) {
+    pub fn register_ui_events(&mut self, skip: usize, time_to_hide_pointer: Option<u32>, drag_to_pan: bool, loupe: bool, pixel_inspector: bool, app_tx: &Sender<Operation>) {
         let ui_event = UIEvent::new(self, skip, app_tx);
         ui_event.update_time_to_hide_pointer(time_to_hide_pointer);
+        ui_event.update_drag_to_pan(drag_to_pan);
+        ui_event.update_loupe(loupe);
+        ui_event.update_pixel_inspector(pixel_inspector);
         self.ui_event = Some(ui_event);
     }
 
     pub fn reset_scrolls(&self, position: Position, to_end: bool) {
+        self.slide(false);
         for cell in self.cells(false) {
             cell.reset_scroll(position, to_end);
         }
@@ -374,10 +471,18 @@ impl Gui {
         Ok(())
     }
 
-    pub fn scroll_views(&self, direction: Direction, scroll_size: f64, crush: bool, count: usize, reset_scrolls_1: Option<Direction>) -> bool {
+    pub fn scroll_views(&self, direction: Direction, scroll_size: f64, crush: bool, count: usize, reset_scrolls_1: Option<Direction>, sync: bool, smooth: bool) -> bool {
+        let slide = self.slide(smooth);
+
+        if sync {
+            // The other cells follow the first one (See `connect_scroll_sync`)
+            if_let_some!(first = self.cells(false).next(), false);
+            return scroll_window(&first.window, direction, scroll_size, crush, count, reset_scrolls_1, &slide).is_some();
+        }
+
         let mut scrolled = false;
         for cell in self.cells(false) {
-            scrolled |= scroll_window(&cell.window, direction, scroll_size, crush, count, reset_scrolls_1);
+            scrolled |= scroll_window(&cell.window, direction, scroll_size, crush, count, reset_scrolls_1, &slide).is_some();
         }
         scrolled
     }
@@ -394,6 +499,31 @@ impl Gui {
         window.set_cursor(Some(&cursor));
     }
 
+    pub fn set_scroll_sync(&self, enabled: bool) {
+        self.scroll_sync.borrow_mut().enabled = enabled;
+    }
+
+    pub fn set_drag_to_pan(&mut self, enabled: bool) {
+        if let Some(ref mut ui_event) = self.ui_event {
+            ui_event.update_drag_to_pan(enabled);
+        }
+    }
+
+    pub fn set_loupe(&mut self, enabled: bool) {
+        if !enabled {
+            self.hide_loupe();
+        }
+        if let Some(ref mut ui_event) = self.ui_event {
+            ui_event.update_loupe(enabled);
+        }
+    }
+
+    pub fn set_pixel_inspector(&mut self, enabled: bool) {
+        if let Some(ref mut ui_event) = self.ui_event {
+            ui_event.update_pixel_inspector(enabled);
+        }
+    }
+
     pub fn set_time_to_hide_pointer(&mut self, time: Option<u32>) {
         if let Some(ref mut ui_event) = self.ui_event {
             ui_event.update_time_to_hide_pointer(time);
@@ -422,6 +552,10 @@ impl Gui {
             return Ok(false);
         }
 
+        if screen != Screen::Main {
+            self.hide_loupe();
+        }
+
         match screen {
             Screen::Main => {
                 self.set_operation_box_visibility(false);
@@ -492,6 +626,19 @@ impl Gui {
         self.window.show();
     }
 
+    /** Center the loupe on `coord` **/
+    pub fn show_loupe(&self, pixbuf: &Pixbuf, coord: &CoordPx) {
+        self.loupe.set_from_pixbuf(Some(pixbuf));
+        self.loupe.set_margin_start(max!(coord.x - pixbuf.get_width() / 2, 0));
+        self.loupe.set_margin_top(max!(coord.y - pixbuf.get_height() / 2, 0));
+        self.loupe.show();
+    }
+
+    pub fn show_statistics(&self, pixbuf: &Pixbuf) {
+        self.statistics.set_from_pixbuf(Some(pixbuf));
+        self.statistics.show();
+    }
+
     pub fn show_command_line(&mut self, initial: &str, app_tx: &Sender<Operation>) -> AppResultU {
         use gtk::MovementStep;
 
@@ -594,9 +741,55 @@ impl Gui {
         }
 
         self.grid_size = Size::new(state.cols as i32, state.rows as i32);
+        self.connect_scroll_sync();
         self.reset_focus();
     }
 
+    /** Scrolls of any cell (by keys, mouse drags or scrollbars) are copied to the others while the sync is enabled **/
+    fn connect_scroll_sync(&self) {
+        let windows: Vec<_> = self.cells.iter().map(|it| it.window.downgrade()).collect();
+
+        for (index, cell) in self.cells.iter().enumerate() {
+            for &horizontal in &[true, false] {
+                if let Some(adj) = get_adjustment(&cell.window, horizontal) {
+                    let (windows, sync) = (windows.clone(), self.scroll_sync.clone());
+                    adj.connect_value_changed(move |adj| {
+                        {
+                            let mut sync = sync.borrow_mut();
+                            if !sync.enabled || sync.syncing {
+                                return;
+                            }
+                            sync.syncing = true;
+                        }
+                        for (i, window) in windows.iter().enumerate() {
+                            if i != index {
+                                if let Some(window) = window.upgrade() {
+                                    sync_scroll(adj, &window, horizontal);
+                                }
+                            }
+                        }
+                        sync.borrow_mut().syncing = false;
+                    });
+                }
+            }
+        }
+    }
+
+    /** Cancel the running smooth scrolls **/
+    fn slide(&self, smooth: bool) -> Option<Slide> {
+        let serial = {
+            let mut current = self.scroll_serial.borrow_mut();
+            *current += 1;
+            *current
+        };
+        if smooth {
+            Some(Slide { serial, current: self.scroll_serial.clone(), targets: self.scroll_targets.clone() })
+        } else {
+            self.scroll_targets.borrow_mut().clear();
+            None
+        }
+    }
+
     pub fn reset_focus(&self) {
         if !self.window.get_visible() {
             return;
@@ -642,7 +835,29 @@ impl Gui {
 
 }
 
+impl Slide {
+    fn target(&self, adj: &Adjustment) -> Option<f64> {
+        self.targets.borrow().iter().find(|(it, _)| it == adj).map(|it| it.1)
+    }
+
+    fn set_target(&self, adj: &Adjustment, value: Option<f64>) {
+        let mut targets = self.targets.borrow_mut();
+        targets.retain(|(it, _)| it != adj);
+        if let Some(value) = value {
+            targets.push((adj.clone(), value));
+        }
+    }
+}
+
 impl Cell {
+    pub fn anchor_scroll(&self, anchor: &ScrollAnchor) {
+        for &(horizontal, ratio, offset) in &[(true, anchor.ratio.0, anchor.offset.0), (false, anchor.ratio.1, anchor.offset.1)] {
+            if let Some(adj) = get_adjustment(&self.window, horizontal) {
+                set_scroll_value(&self.window, horizontal, ratio * adj.get_upper() - offset, &None);
+            }
+        }
+    }
+
     /**
      * @return Scale
      */
@@ -703,6 +918,32 @@ impl Cell {
          sh as i32)
     }
 
+    /** `coord` is None => the center of the cell **/
+    pub fn get_scroll_anchor(&self, coord: Option<&CoordPx>) -> Option<ScrollAnchor> {
+        fn ratio(adj: &Adjustment, offset: f64) -> f64 {
+            let upper = adj.get_upper();
+            if 0.0 < upper { (adj.get_value() + offset) / upper } else { 0.5 }
+        }
+
+        let h_adj = self.window.get_hadjustment()?;
+        let v_adj = self.window.get_vadjustment()?;
+
+        let offset = if let Some(coord) = coord {
+            let a = self.window.get_allocation();
+            if !(a.x <= coord.x && coord.x <= a.x + a.width && a.y <= coord.y && coord.y <= a.y + a.height) {
+                return None;
+            }
+            (f64!(coord.x - a.x), f64!(coord.y - a.y))
+        } else {
+            (h_adj.get_page_size() / 2.0, v_adj.get_page_size() / 2.0)
+        };
+
+        Some(ScrollAnchor {
+            ratio: (ratio(&h_adj, offset.0), ratio(&v_adj, offset.1)),
+            offset,
+        })
+    }
+
     pub fn get_position_on_image(&self, coord: &CoordPx, drawing: &Drawing) -> Option<Coord> {
         fn extract(adj: &Adjustment) -> (f64, f64) {
             (adj.get_value(), adj.get_upper())
@@ -730,12 +971,7 @@ impl Cell {
             ry = ry * clipping.height() + clipping.top;
         }
 
-        let (rx, ry) = match drawing.rotation % 4 {
-            1 => (ry, 1.0 - rx),
-            2 => (1.0 - rx, 1.0 - ry),
-            3 => (1.0 - ry, rx),
-            _ => (rx, ry),
-        };
+        let Coord { x: rx, y: ry } = Coord { x: rx, y: ry }.on_original_image(drawing);
 
         // println!("i: {}x{}, p: {}x{}, s: {}x{}-{}x{}, c: {}x{}, r: {}x{}", ix, iy, px, py, sx, sy, sw, sh, cx, cy, rx, ry);
 
@@ -1082,55 +1318,110 @@ fn reset_scroll(window: &ScrolledWindow, direction: Direction) {
     }
 }
 
-fn scroll_window(window: &ScrolledWindow, direction: Direction, scroll_size_ratio: f64, crush: bool, count: usize, reset_scrolls_1: Option<Direction>) -> bool {
+/**
+ * @return The relative position after scrolled
+ */
+fn scroll_window(window: &ScrolledWindow, direction: Direction, scroll_size_ratio: f64, crush: bool, count: usize, reset_scrolls_1: Option<Direction>, slide: &Option<Slide>) -> Option<f64> {
     use self::Direction::*;
 
-    let scroll = |horizontal| -> bool {
-        let adj = if horizontal { window.get_hadjustment() } else { window.get_vadjustment() };
-        if_let_some!(adj = adj, false);
-
-        let page_size = adj.get_page_size();
-        let scroll_size = page_size * scroll_size_ratio * count as f64;
-        let space = page_size * (1.0 - scroll_size_ratio);
-        let value = adj.get_value();
-        let scroll_size = match direction {
-            Right | Down => {
-                let rest = adj.get_upper() - value - scroll_size - page_size;
-                if rest < space && crush {
-                    scroll_size + rest
-                } else {
-                    scroll_size
-                }
-            }
-            Left | Up => {
-                let rest = value - scroll_size;
-                if rest < space && crush {
-                    -(scroll_size + rest)
-                } else {
-                    -scroll_size
-                }
+    let horizontal = is_horizontal(direction);
+    let adj = get_adjustment(window, horizontal)?;
+
+    let page_size = adj.get_page_size();
+    let scroll_size = page_size * scroll_size_ratio * count as f64;
+    let space = page_size * (1.0 - scroll_size_ratio);
+    // Accumulate onto the destination of the running slide
+    let value = slide.as_ref().and_then(|it| it.target(&adj)).unwrap_or_else(|| adj.get_value());
+    let scroll_size = match direction {
+        Right | Down => {
+            let rest = adj.get_upper() - value - scroll_size - page_size;
+            if rest < space && crush {
+                scroll_size + rest
+            } else {
+                scroll_size
             }
-        };
-
-        adj.set_value(value + scroll_size);
-
-        if feq(adj.get_value(), value, 0.000_000_1) {
-            false
-        } else {
-            if horizontal { window.set_hadjustment(&adj) } else { window.set_vadjustment(&adj) }
-            if let Some(direction) = reset_scrolls_1 {
-                reset_scroll(window, direction);
+        }
+        Left | Up => {
+            let rest = value - scroll_size;
+            if rest < space && crush {
+                -(scroll_size + rest)
+            } else {
+                -scroll_size
             }
-            true
         }
     };
 
+    let range = adj.get_upper() - page_size;
+    let new_value = clamp!(adj.get_lower(), value + scroll_size, range);
+
+    if feq(new_value, value, 0.000_000_1) {
+        return None;
+    }
+
+    set_scroll_value(window, horizontal, new_value, slide);
+    if let Some(direction) = reset_scrolls_1 {
+        reset_scroll(window, direction);
+    }
+    Some(if 0.0 < range { new_value / range } else { 0.0 })
+}
+
+/** Copy the relative scroll position of `source` to `target` **/
+fn sync_scroll(source: &Adjustment, target: &ScrolledWindow, horizontal: bool) {
+    if_let_some!(adj = get_adjustment(target, horizontal));
+    let range = source.get_upper() - source.get_page_size();
+    let ratio = if 0.0 < range { source.get_value() / range } else { 0.0 };
+    adj.set_value((adj.get_upper() - adj.get_page_size()) * ratio);
+}
+
+fn get_adjustment(window: &ScrolledWindow, horizontal: bool) -> Option<Adjustment> {
+    if horizontal { window.get_hadjustment() } else { window.get_vadjustment() }
+}
+
+fn is_horizontal(direction: Direction) -> bool {
+    use self::Direction::*;
+
     match direction {
-        Left | Right => scroll(true),
-        Up | Down => scroll(false),
+        Left | Right => true,
+        Up | Down => false,
     }
 }
 
+fn set_scroll_value(window: &ScrolledWindow, horizontal: bool, value: f64, slide: &Option<Slide>) {
+    fn set(window: &ScrolledWindow, horizontal: bool, adj: &Adjustment) {
+        if horizontal { window.set_hadjustment(adj) } else { window.set_vadjustment(adj) }
+    }
+
+    if_let_some!(adj = get_adjustment(window, horizontal));
+
+    let slide = match *slide {
+        Some(ref slide) => slide.clone(),
+        None => {
+            adj.set_value(value);
+            set(window, horizontal, &adj);
+            return;
+        }
+    };
+
+    let window = window.clone();
+    let from = adj.get_value();
+    let mut step = 0;
+    slide.set_target(&adj, Some(value));
+    gtk::timeout_add(SMOOTH_SCROLL_INTERVAL, move || {
+        if *slide.current.borrow() != slide.serial {
+            return Continue(false);
+        }
+        step += 1;
+        let t = 1.0 - (1.0 - f64!(step) / f64!(SMOOTH_SCROLL_STEPS)).powi(3);
+        adj.set_value(from + (value - from) * t);
+        set(&window, horizontal, &adj);
+        if step < SMOOTH_SCROLL_STEPS {
+            return Continue(true);
+        }
+        slide.set_target(&adj, None);
+        Continue(false)
+    });
+}
+
 fn setup_drag<T: WidgetExt + WidgetExtManual >(widget: &T) {
     use gdk::DragAction;
     use gtk::{DestDefaults, TargetEntry, TargetFlags};
@@ -10,6 +10,7 @@ use crate::cherenkov::{Cherenkoved, Modifier};
 use crate::entry::image::Imaging;
 use crate::entry::{Entry, Key, self};
 use crate::errors::AppResultU;
+use crate::filer::FileOperation;
 use crate::image::ImageBuffer;
 use crate::session::StatusText;
 
@@ -145,6 +146,16 @@ impl ImageCache {
         cherenkoved.generate_animation_png(entry, imaging, length, path)
     }
 
+    pub fn bake(&mut self, entry: &Entry, imaging: &Imaging, file_operation: &FileOperation) -> AppResultU {
+        let mut cherenkoved = self.cherenkoved.lock().unwrap();
+        cherenkoved.bake(entry, imaging, file_operation)
+    }
+
+    pub fn set_cherenkov_store(&mut self, value: bool) {
+        let mut cherenkoved = self.cherenkoved.lock().unwrap();
+        cherenkoved.set_store(value)
+    }
+
     pub fn cherenkov1(&mut self, entry: &Entry, imaging: &Imaging, modifier: Modifier) {
         let mut cherenkoved = self.cherenkoved.lock().unwrap();
         cherenkoved.cherenkov1(entry, imaging, modifier)
@@ -165,9 +176,14 @@ impl ImageCache {
         cherenkoved.remove(key)
     }
 
-    pub fn undo_cherenkov(&mut self, key: &Key, count: usize) {
+    pub fn cherenkov_modifiers(&self, key: &Key) -> Vec<Modifier> {
+        let cherenkoved = self.cherenkoved.lock().unwrap();
+        cherenkoved.modifiers(key)
+    }
+
+    pub fn set_cherenkov_modifiers(&mut self, key: &Key, imaging: &Imaging, modifiers: Vec<Modifier>) {
         let mut cherenkoved = self.cherenkoved.lock().unwrap();
-        cherenkoved.undo(key, count)
+        cherenkoved.set_modifiers(key, imaging, modifiers)
     }
 
     pub fn clear_entry_search_highlights(&mut self, entry: &Entry) -> bool {
@@ -1,5 +1,5 @@
 
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::default::Default;
 use std::sync::Arc;
 use std::sync::mpsc::{channel, Sender};
@@ -12,6 +12,9 @@ use crate::entry::image::Imaging;
 use crate::entry::{Entry, Key, self};
 use crate::image::ImageBuffer;
 use crate::image_cache::ImageCache;
+use crate::operation::Operation;
+use crate::phash::PHashTable;
+use crate::statistics::StatisticsTable;
 
 
 
@@ -26,15 +29,20 @@ pub struct FetchTarget {
 }
 
 pub enum FetcherOperation {
+    Analyze(VecDeque<Arc<Entry>>, bool), /* entries, first */
+    Analyzed(Key),
     Refresh(FetchTarget),
     Done(Key, Imaging, Result<ImageBuffer, String>),
+    Hash(VecDeque<Arc<Entry>>),
+    Hashed,
 }
 
 
 impl ImageFetcher {
-    pub fn new(image_cache: ImageCache) -> ImageFetcher {
+    /** `app_tx` receives `ShowStatistics` for each analyzed entry **/
+    pub fn new(image_cache: ImageCache, phashes: PHashTable, statistics: StatisticsTable, app_tx: Sender<Operation>) -> ImageFetcher {
         ImageFetcher {
-            main_tx: main(image_cache)
+            main_tx: main(image_cache, phashes, statistics, app_tx)
         }
     }
 
@@ -46,11 +54,22 @@ impl ImageFetcher {
                     entries,
                 })).unwrap();
     }
+
+    /** Compute perceptual hashes in the background. Images to show take precedence **/
+    pub fn new_hash_target(&self, entries: VecDeque<Arc<Entry>>) {
+        self.main_tx.send(FetcherOperation::Hash(entries)).unwrap();
+    }
+
+    /** Compute image statistics in the background. Images to show take precedence.
+        The entries already queued or being analyzed are ignored. `first` puts the entries before the queued ones **/
+    pub fn new_statistics_target(&self, entries: VecDeque<Arc<Entry>>, first: bool) {
+        self.main_tx.send(FetcherOperation::Analyze(entries, first)).unwrap();
+    }
 }
 
 
 
-fn main(mut cache: ImageCache) -> Sender<FetcherOperation> {
+fn main(mut cache: ImageCache, phashes: PHashTable, statistics: StatisticsTable, app_tx: Sender<Operation>) -> Sender<FetcherOperation> {
     use self::FetcherOperation::*;
 
     let (tx, rx) = channel();
@@ -58,6 +77,11 @@ fn main(mut cache: ImageCache) -> Sender<FetcherOperation> {
     spawn(clone_army!([tx] move || {
         let mut idles = num_cpus::get();
         let mut current_target = FetchTarget::default();
+        let mut hash_targets = VecDeque::new();
+        let mut hashing = 0;
+        let mut statistics_targets = VecDeque::new();
+        let mut statistics_pending = HashSet::new(); /* Queued or being analyzed */
+        let mut analyzing = 0;
 
         info!("image_fetcher: threads={}", idles);
 
@@ -71,11 +95,51 @@ fn main(mut cache: ImageCache) -> Sender<FetcherOperation> {
                         &mut current_target.entries,
                         &mut idles,
                         &current_target.imaging);
+                    start_hashing(&tx, &phashes, &mut hash_targets, &mut idles, &mut hashing);
+                    start_analyzing(&tx, &app_tx, &statistics, &mut statistics_targets, &mut statistics_pending, &mut idles, &mut analyzing);
                 }
                 Done(key, imaging, image_buffer) => {
                     idles += 1;
                     cache.push(&imaging, &key, image_buffer);
                     start(&tx, &mut cache, &mut current_target.entries, &mut idles, &current_target.imaging);
+                    start_hashing(&tx, &phashes, &mut hash_targets, &mut idles, &mut hashing);
+                    start_analyzing(&tx, &app_tx, &statistics, &mut statistics_targets, &mut statistics_pending, &mut idles, &mut analyzing);
+                }
+                Hash(entries) => {
+                    hash_targets.extend(entries);
+                    start_hashing(&tx, &phashes, &mut hash_targets, &mut idles, &mut hashing);
+                }
+                Hashed => {
+                    idles += 1;
+                    hashing -= 1;
+                    start(&tx, &mut cache, &mut current_target.entries, &mut idles, &current_target.imaging);
+                    start_hashing(&tx, &phashes, &mut hash_targets, &mut idles, &mut hashing);
+                    start_analyzing(&tx, &app_tx, &statistics, &mut statistics_targets, &mut statistics_pending, &mut idles, &mut analyzing);
+                    if hashing == 0 && hash_targets.is_empty() {
+                        puts_event!("phash/done");
+                    }
+                }
+                Analyze(entries, first) => {
+                    let entries: Vec<Arc<Entry>> = entries.into_iter().filter(|it| statistics_pending.insert(it.key.clone())).collect();
+                    if first {
+                        for entry in entries.into_iter().rev() {
+                            statistics_targets.push_front(entry);
+                        }
+                    } else {
+                        statistics_targets.extend(entries);
+                    }
+                    start_analyzing(&tx, &app_tx, &statistics, &mut statistics_targets, &mut statistics_pending, &mut idles, &mut analyzing);
+                }
+                Analyzed(key) => {
+                    idles += 1;
+                    analyzing -= 1;
+                    statistics_pending.remove(&key);
+                    start(&tx, &mut cache, &mut current_target.entries, &mut idles, &current_target.imaging);
+                    start_hashing(&tx, &phashes, &mut hash_targets, &mut idles, &mut hashing);
+                    start_analyzing(&tx, &app_tx, &statistics, &mut statistics_targets, &mut statistics_pending, &mut idles, &mut analyzing);
+                    if analyzing == 0 && statistics_targets.is_empty() {
+                        puts_event!("statistics/done");
+                    }
                 }
             }
         }
@@ -99,6 +163,38 @@ fn start(tx: &Sender<FetcherOperation>, cache: &mut ImageCache, entries: &mut Ve
 }
 
 
+fn start_hashing(tx: &Sender<FetcherOperation>, phashes: &PHashTable, entries: &mut VecDeque<Arc<Entry>>, idles: &mut usize, hashing: &mut usize) {
+    while 0 < *idles {
+        if let Some(entry) = entries.pop_front() {
+            if phashes.get(&entry.key).is_none() {
+                *idles -= 1;
+                *hashing += 1;
+                hash(tx.clone(), phashes.clone(), entry);
+            }
+        } else {
+            return;
+        }
+    }
+}
+
+
+fn start_analyzing(tx: &Sender<FetcherOperation>, app_tx: &Sender<Operation>, statistics: &StatisticsTable, entries: &mut VecDeque<Arc<Entry>>, pending: &mut HashSet<Key>, idles: &mut usize, analyzing: &mut usize) {
+    while 0 < *idles {
+        if let Some(entry) = entries.pop_front() {
+            if statistics.get(&entry.key).is_none() {
+                *idles -= 1;
+                *analyzing += 1;
+                analyze(tx.clone(), app_tx.clone(), statistics.clone(), entry);
+            } else {
+                pending.remove(&entry.key);
+            }
+        } else {
+            return;
+        }
+    }
+}
+
+
 fn fetch(tx: Sender<FetcherOperation>, entry: Arc<Entry>, imaging: Imaging) {
     spawn(move || {
         trace!("image_fetcher/get_image_buffer: key={:?}", &entry.key);
@@ -106,3 +202,24 @@ fn fetch(tx: Sender<FetcherOperation>, entry: Arc<Entry>, imaging: Imaging) {
         tx.send(FetcherOperation::Done(entry.key.clone(), imaging, image_buffer)).unwrap();
     });
 }
+
+fn hash(tx: Sender<FetcherOperation>, phashes: PHashTable, entry: Arc<Entry>) {
+    spawn(move || {
+        trace!("image_fetcher/hash: key={:?}", &entry.key);
+        if let Err(err) = phashes.compute(&entry) {
+            puts_event!("phash/fail", "for" => entry.display_path(), "reason" => s!(err));
+        }
+        tx.send(FetcherOperation::Hashed).unwrap();
+    });
+}
+
+fn analyze(tx: Sender<FetcherOperation>, app_tx: Sender<Operation>, statistics: StatisticsTable, entry: Arc<Entry>) {
+    spawn(move || {
+        trace!("image_fetcher/analyze: key={:?}", &entry.key);
+        match statistics.compute(&entry) {
+            Ok(_) => app_tx.send(Operation::ShowStatistics(entry.key.clone())).unwrap(),
+            Err(err) => puts_event!("statistics/fail", "for" => entry.display_path(), "reason" => s!(err)),
+        }
+        tx.send(FetcherOperation::Analyzed(entry.key.clone())).unwrap();
+    });
+}
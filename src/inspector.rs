@@ -0,0 +1,127 @@
+
+use std::env;
+use std::fmt;
+
+use gdk_pixbuf::{Pixbuf, PixbufExt};
+
+use crate::color::Color;
+use crate::constant::env_name;
+use crate::size::Coord;
+
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorFormat {
+    Css,
+    Hex,
+}
+
+/** A pixel in the original image coordinates **/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pixel {
+    pub color: Color,
+    pub x: i32,
+    pub y: i32,
+}
+
+/** The pixel under the pointer. The environment variables (`$CHRY_PIXEL`...) are updated only when they are read **/
+#[derive(Default)]
+pub struct PixelEnv {
+    dirty: bool,
+    pixel: Option<Pixel>,
+}
+
+
+#[allow(clippy::derivable_impls)]
+impl Default for ColorFormat {
+    fn default() -> Self {
+        ColorFormat::Hex
+    }
+}
+
+impl Pixel {
+    pub fn format(&self, format: ColorFormat) -> String {
+        match format {
+            ColorFormat::Css => self.color.to_css(),
+            ColorFormat::Hex => self.color.to_short_hex(),
+        }
+    }
+}
+
+impl PixelEnv {
+    /** Returns `true` if the pixel is changed **/
+    pub fn update(&mut self, pixel: Option<Pixel>) -> bool {
+        if self.pixel == pixel {
+            return false;
+        }
+        self.pixel = pixel;
+        self.dirty = true;
+        true
+    }
+
+    pub fn export(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        let names = ["PIXEL", "PIXEL_X", "PIXEL_Y", "PIXEL_COLOR"];
+        if let Some(pixel) = self.pixel {
+            let values = [s!(pixel), s!(pixel.x), s!(pixel.y), pixel.format(ColorFormat::Hex)];
+            for (name, value) in names.iter().zip(values.iter()) {
+                env::set_var(env_name(name), value);
+            }
+        } else {
+            for name in &names {
+                env::remove_var(env_name(name));
+            }
+        }
+    }
+}
+
+impl fmt::Display for Pixel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{} {}", self.x, self.y, self.color.to_short_hex())
+    }
+}
+
+
+/** The pixel of `pixbuf` at `position` (relative) **/
+pub fn pick(pixbuf: &Pixbuf, position: &Coord) -> Option<Pixel> {
+    let (width, height) = (pixbuf.get_width(), pixbuf.get_height());
+    let (x, y) = (pixel_index(position.x, width)?, pixel_index(position.y, height)?);
+
+    let channels = pixbuf.get_n_channels() as usize;
+    let offset = y as usize * pixbuf.get_rowstride() as usize + x as usize * channels;
+    let pixels: &[u8] = unsafe { pixbuf.get_pixels() };
+    let alpha = if pixbuf.get_has_alpha() { pixels[offset + 3] } else { 255 };
+
+    Some(Pixel { color: Color::new4(pixels[offset], pixels[offset + 1], pixels[offset + 2], alpha), x, y })
+}
+
+fn pixel_index(position: f64, length: i32) -> Option<i32> {
+    if position < 0.0 || 1.0 < position || length <= 0 {
+        return None;
+    }
+    Some(min!((position * f64!(length)) as i32, length - 1))
+}
+
+
+
+#[cfg(test)]#[test]
+fn test_pixel() {
+    let pixel = Pixel { color: Color::new(0x1a, 0x2b, 0x3c), x: 10, y: 20 };
+    assert_eq!(s!(pixel), "10,20 #1a2b3c");
+    assert_eq!(pixel.format(ColorFormat::Hex), "#1a2b3c");
+    assert_eq!(pixel.format(ColorFormat::Css), "rgb(26, 43, 60)");
+
+    let pixel = Pixel { color: Color::new4(255, 0, 0, 51), x: 0, y: 0 };
+    assert_eq!(pixel.format(ColorFormat::Hex), "#ff000033");
+    assert_eq!(pixel.format(ColorFormat::Css), "rgba(255, 0, 0, 0.200)");
+
+    assert_eq!(pixel_index(0.0, 100), Some(0));
+    assert_eq!(pixel_index(0.505, 100), Some(50));
+    assert_eq!(pixel_index(1.0, 100), Some(99));
+    assert_eq!(pixel_index(1.01, 100), None);
+    assert_eq!(pixel_index(-0.01, 100), None);
+}
@@ -0,0 +1,50 @@
+
+use std::f64::consts::PI;
+
+use cairo::{Context, Format, ImageSurface};
+use gdk::prelude::ContextExt;
+use gdk_pixbuf::{Pixbuf, PixbufExt};
+
+use crate::gtk_utils::new_pixbuf_from_surface;
+use crate::size::Coord;
+use crate::state::{Drawing, LoupeState};
+
+
+
+/** Magnify `source` around `position` (relative) as displayed with `drawing` **/
+pub fn render(source: &Pixbuf, position: Coord, drawing: &Drawing, state: &LoupeState) -> Pixbuf {
+    let size = state.size as i32;
+    let half = f64!(size) / 2.0;
+    let magnification = f64!(state.magnification) / 100.0;
+
+    let surface = ImageSurface::create(Format::ARgb32, size, size).unwrap();
+    let context = Context::new(&surface);
+
+    context.arc(half, half, half - 1.0, 0.0, PI * 2.0);
+    context.clip_preserve();
+
+    context.save();
+    context.set_source_rgb(0.5, 0.5, 0.5);
+    context.paint();
+    // Same order as `make_scaled`: rotate, then flip
+    context.translate(half, half);
+    context.rotate(PI / 2.0 * f64!(drawing.rotation % 4));
+    context.scale(flip_scale(drawing.vertical_flip), flip_scale(drawing.horizontal_flip));
+    context.scale(magnification, magnification);
+    context.translate(-position.x * f64!(source.get_width()), -position.y * f64!(source.get_height()));
+    context.set_source_pixbuf(source, 0.0, 0.0);
+    context.paint();
+    context.restore();
+
+    context.set_source_rgb(0.0, 0.0, 0.0);
+    context.set_line_width(2.0);
+    context.stroke();
+
+    let result = new_pixbuf_from_surface(&surface);
+    drawing.adjustment.apply(&result);
+    result
+}
+
+fn flip_scale(flip: bool) -> f64 {
+    if flip { -1.0 } else { 1.0 }
+}
@@ -7,6 +7,7 @@
 #[macro_use] mod gtk_utils;
 #[macro_use] mod util;
 
+mod adjustment;
 mod app;
 mod app_path;
 mod archive;
@@ -17,15 +18,18 @@ mod chrysoberyl;
 mod clipboard;
 mod color;
 mod command_line;
+mod compare;
 mod completion;
 mod config;
 mod constant;
 mod controller;
 mod counter;
+mod crop;
 mod editor;
 mod entry;
 mod events;
 mod expandable;
+mod feed;
 mod file_extension;
 mod filer;
 mod filterable_vec;
@@ -34,17 +38,22 @@ mod history;
 mod image;
 mod image_cache;
 mod image_fetcher;
+mod inspector;
 mod joiner;
 mod key;
 mod lazy;
 mod lazy_sender;
+mod loupe;
 mod mapping;
 mod operation;
 mod option;
+mod original_image;
 mod paginator;
+mod phash;
 mod poppler;
 mod remote_cache;
 mod resolution;
+mod scraper;
 mod script;
 mod session;
 mod shell;
@@ -54,8 +63,11 @@ mod shorter;
 mod size;
 mod sorting_buffer;
 mod state;
+mod statistics;
 mod timer;
 mod ui_event;
+mod undo;
+mod video;
 mod watcher;
 
 
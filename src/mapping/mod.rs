@@ -4,7 +4,7 @@ use std::str::FromStr;
 
 use crate::events::EventName;
 use crate::key::Key;
-use crate::size::{Region, CoordPx};
+use crate::size::{Coord, Region, CoordPx};
 
 pub mod event_mapping;
 pub mod input_mapping;
@@ -18,7 +18,7 @@ pub enum Mapped {
     Operation(String, Vec<String>), // command_name, 
     Event(EventName),
     Input(CoordPx, Key),
-    Region(Region, Key, usize), // region, button, cell_index
+    Region(Region, Key, usize, Coord), // region, button, cell_index, drag start
 }
 
 #[derive(Clone, Copy)]
@@ -103,7 +103,7 @@ impl Mapping {
                     Some((ops, s!(event_name)))
                 }
             }
-            Mapped::Region(_, ref button, _) =>
+            Mapped::Region(_, ref button, _, _) =>
                 self.region_mapping.matched(button).map(|op| {
                     (vec![op], s!(button))
                 })
@@ -118,7 +118,7 @@ impl Mapped {
             Mapped::Operation(_, _) => "operation",
             Mapped::Input(_, _) => "input",
             Mapped::Event(_) => "event",
-            Mapped::Region(_, _, _) => "region",
+            Mapped::Region(_, _, _, _) => "region",
         }
     }
 }
@@ -130,7 +130,7 @@ impl fmt::Display for Mapped {
             Mapped::Input(ref coord, ref key) if coord.is_valid() => write!(f, "{} ({})", key, coord),
             Mapped::Input(_, ref key) => write!(f, "{}", key),
             Mapped::Event(ref event_name) => write!(f, "{}", event_name),
-            Mapped::Region(ref region, ref button, _) => write!(f, "{} ({})",  button,  region),
+            Mapped::Region(ref region, ref button, _, _) => write!(f, "{} ({})",  button,  region),
         }
     }
 }
@@ -10,11 +10,14 @@ use cmdline_parser::Parser;
 use crate::archive::ArchiveEntry;
 use crate::chainer;
 use crate::cherenkov::Operator;
+use crate::cherenkov::annotation;
+use crate::cherenkov::effect::{Effect, self};
 use crate::cherenkov::fill::Shape;
 use crate::cherenkov::nova::Seed;
 use crate::color::Color;
 use crate::command_line;
 use crate::controller;
+use crate::crop;
 use crate::entry::filter::expression::Expr as FilterExpr;
 use crate::entry::{Meta, EntryType};
 use crate::entry;
@@ -22,13 +25,14 @@ use crate::errors::{AppResult, AppError, ParsingError};
 use crate::events::EventName;
 use crate::expandable::Expandable;
 use crate::filer;
-use crate::gui::Direction;
+use crate::inspector;
+use crate::gui::{Direction, ScrollAnchor};
 use crate::key::Key;
 use crate::key::KeySequence;
 use crate::mapping;
 use crate::poppler;
 use crate::session::Session;
-use crate::size::Region;
+use crate::size::{CoordPx, Region};
 
 mod parser;
 pub mod option;
@@ -40,8 +44,11 @@ use self::option::{OptionName, OptionUpdater};
 #[derive(Clone)]
 pub enum Operation {
     Apng(PathBuf, u8), /* path, length */
+    AnchorScrolls(ScrollAnchor),
     AppEvent(EventName, HashMap<String, String>),
     Backward,
+    Bake(filer::FileOperation, usize), /* file operation, cell index */
+    CancelDownload(String), /* URL or ticket */
     Chain(chainer::Target),
     ChangeDirectory(Expandable),
     Cherenkov(CherenkovParameter),
@@ -54,14 +61,18 @@ pub enum Operation {
     CopyToClipboard(ClipboardSelection),
     Count(Option<usize>),
     CountDigit(u8),
+    Crop(CropParameter),
     DefineUserSwitch(String, Vec<Vec<String>>),
     Delete(Box<entry::filter::expression::Expr>),
     DetectEyes(CherenkovParameter),
+    DiscardJournal(PathBuf), /* internal use only */
     Draw,
     Editor(Vec<Expandable>, Vec<Expandable>, Vec<Session>, bool, bool), /* editor_command, options, session, comment_out, freeze */
+    Effect(Effect, Shape, Option<Region>, usize), /* effect, shape, region, cell index */
     Error(String),
     Eval(Vec<String>),
     Expand(bool, Option<PathBuf>), /* recursive, base */
+    FetchOffline,
     FileChanged(PathBuf),
     Fill(Shape, Option<Region>, Color, Option<Operator>, bool, usize), /* shape, region, color, fill_operator, mask, cell index */
     Filter(bool, Box<Option<entry::filter::expression::Expr>>), /* dynamic, filter expression */
@@ -78,6 +89,7 @@ pub enum Operation {
     KillTimer(String),
     Last(Option<usize>, bool, MoveBy, bool),
     LazyDraw(u64, bool), /* serial, to_end */
+    Line(annotation::Line, bool, usize), /* line, arrow, cell index */
     LinkAction(Vec<String>),
     Load(Expandable, bool), /* path, search_path */
     LoadDefault,
@@ -85,6 +97,7 @@ pub enum Operation {
     MakeVisibles(Vec<Option<Region>>),
     Map(MappingTarget, Option<usize>, Vec<String>), /* target, remain, operation */
     Mark(String, Option<(String, usize, Option<EntryType>)>),
+    MarkDuplicates(bool), /* delete */
     Meow,
     Message(Option<String>, bool),
     MoveAgain(Option<usize>, bool, MoveBy, bool, bool), /* count, ignore-views, archive/page, wrap, reverse */
@@ -93,8 +106,11 @@ pub enum Operation {
     Nop,
     OperateFile(filer::FileOperation),
     Page(usize),
+    Pan(f64, f64), /* dx, dy */
     PdfIndex(bool, bool, bool, Vec<Expandable>, poppler::index::Format, Option<String>), /* async, read_operations, search_path, ... */
+    PickColor(Option<CoordPx>, inspector::ColorFormat, ClipboardSelection), /* coord, format, selection */
     Pointer(bool),
+    PointerMotion(Option<CoordPx>), /* internal use only. `None` means the last position */
     PopCount,
     PreFetch(u64),
     Previous(Option<usize>, bool, MoveBy, bool, bool), /* count, ignore_views, move_by, wrap, forget */
@@ -104,23 +120,31 @@ pub enum Operation {
     PushClipboard(ClipboardSelection, bool, Option<Meta>, bool, bool), /* selection, as_operation, meta, force, show */
     PushCount,
     PushDirectory(Expandable, Option<Meta>, bool), /* path, meta, force */
+    PushFeed(Expandable, Option<Meta>, bool, bool), /* path or URL, meta, force, show */
     PushImage(Expandable, Option<Meta>, bool, bool, Option<u8>), /* path, meta, force, show, expand-level */
+    PushList(Expandable, Option<Meta>, bool, bool), /* path or URL, meta, force, show */
     PushMemory(Vec<u8>, Option<Meta>, bool), /* memory, meta, show */
     PushMessage(String, Option<Meta>, bool), /* message, meta, show */
+    PushPage(String, Option<Meta>, bool, bool, usize), /* URL, meta, force, show, max_pages */
     PushPdf(Expandable, Option<Meta>, bool, bool), /* path, meta, force, show */
     PushSibling(bool, bool, Option<Meta>, bool, bool), /* next?, clear, meta, force, show */
-    PushURL(String, Option<Meta>, bool, bool, Option<EntryType>), /* path, meta, force, show, entry_type */
+    PushURL(String, Option<Meta>, bool, bool, Option<EntryType>, Option<String>), /* path, meta, force, show, entry_type, referer */
+    PushVideo(Expandable, Option<Meta>, bool, bool), /* path, meta, force, show */
     Query(Vec<String>, Option<String>), /* operation, caption */
     Queue(Vec<String>, usize),
     Random,
     Record(usize, usize, entry::Key), /* minimum_move, index, key */
     RecordPre(Vec<String>, usize),
+    Redo(Option<usize>),
     Refresh(bool), /* image_cache */
+    RemoteCache(bool, Vec<String>), /* sweep, delete URL patterns */
     RemoveEffects,
     ResetFocus,
     UIAction(UIActionType),
     ResetScrolls(bool), /* to_end */
+    RetryFailed,
     Save(PathBuf, Vec<Session>, bool), /* path, sessions, freeze */
+    ScrapeRule(String, Option<String>, bool, bool), /* URL pattern, selector or regex (None => unset), next, regex */
     SearchText(Option<String>, bool, Color), /* text, backward */
     Scroll(Direction, f64, bool, bool, Vec<String>, Option<Direction>), /* direction, scroll_size_ratio, crush, reset_at_end, operation, reset_scrolls_1 */
     SetEnv(String, Option<Expandable>),
@@ -128,10 +152,12 @@ pub enum Operation {
     ShellFilter(Vec<Expandable>, bool), /* path, search_path */
     Show(Option<usize>, bool, MoveBy, bool), /* count, ignore-views, archive/page, wrap */
     ShowCommandLine(String),
+    ShowStatistics(entry::Key), /* internal use only */
     Shuffle(bool), /* Fix current */
     Sort(bool, SortKey, bool), /* fix_current, key, reverse */
     Sorter(bool, Vec<Expandable>, bool), /* fix_current, command, reverse */
     TellRegion(f64, f64, f64, f64, Key), /* lef,t top, right, bottom, mousesbutton */
+    Text(annotation::Text, usize), /* text, cell index */
     Timer(Option<String>, Vec<String>, Duration, Option<usize>, bool),
     Unchain(chainer::Target),
     Unclip,
@@ -139,7 +165,9 @@ pub enum Operation {
     Unmap(MappingTarget),
     Unmark(Option<String>), /* all or given */
     Update(Updated),
+    UpdateHashes,
     UpdateOption(OptionName, OptionUpdater),
+    UpdateStatistics,
     UpdateUI,
     User(Vec<(String, String)>),
     Views(Option<usize>, Option<usize>, bool), /* cols, rows, ignore_views */
@@ -148,6 +176,7 @@ pub enum Operation {
     When(FilterExpr, bool, Vec<String>), /* filter, reverse(unless), operation */
     WithMessage(Option<String>, Box<Operation>),
     Write(PathBuf, Option<usize>),
+    Zoom(bool, usize), /* out, step (percent) */
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -162,6 +191,14 @@ pub struct CherenkovParameter {
     pub y: Option<f64>,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct CropParameter {
+    pub cell_index: usize,
+    pub file_operation: filer::FileOperation,
+    pub fixed: Option<crop::Fixed>,
+    pub region: Option<Region>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct OperationContext {
     pub cell_index: Option<usize>,
@@ -197,11 +234,13 @@ pub enum QueuedOperation {
     PushArchive(PathBuf, Option<Meta>, bool, bool, Option<String>), /* path, meta, force, show, remote-url */
     PushArchiveEntry(PathBuf, ArchiveEntry, Option<Meta>, bool, bool, Option<String>), /* path, archive-entry, meta, force, show, remote-url */
     PushDirectory(PathBuf, Option<Meta>, bool), /* path, meta, force */
-    PushMessage(String, Option<Meta>, bool), /* message, meta, show */
+    PushMessage(String, Option<Meta>, bool, Option<String>, Option<EntryType>), /* message, meta, show, remote-url, entry_type */
     PushImage(PathBuf, Option<Meta>, bool, bool, Option<u8>, Option<String>), /* path, meta, force, show, expand-level, remote-url */
     PushMemory(Vec<u8>, Option<Meta>, bool), /* memory */
     PushPdf(PathBuf, Option<Meta>, bool, bool, Option<String>), /* path, meta, force, show, remote-url */
     PushPdfEntries(PathBuf, usize, Option<Meta>, bool, bool, Option<String>), /* path, pages, meta, force, show, remote-url */
+    PushVideo(PathBuf, Option<Meta>, bool, bool, Option<String>), /* path, meta, force, show, remote-url */
+    PushVideoEntries(PathBuf, usize, String, Option<Meta>, bool, bool, Option<String>), /* path, frames, command, meta, force, show, remote-url */
 }
 
 #[derive(Default, Debug, Clone)]
@@ -214,6 +253,7 @@ pub struct Updated {
     pub pointer: bool,
     pub remote: bool,
     pub size: bool,
+    pub scroll_anchor: Option<ScrollAnchor>,
     pub target_regions: Option<Vec<Option<Region>>>,
 }
 
@@ -227,6 +267,8 @@ pub enum SortKey {
     Dimensions,
     Width,
     Height,
+    Similarity,
+    Duplicates,
 }
 
 #[derive(Clone, Copy)]
@@ -273,7 +315,11 @@ fn _parse_from_vec(whole: &[String]) -> Result<Operation, ParsingError> {
         match name {
             ";"                             => parse_multi_args(args, ";", true),
             "@apng"                         => parse_apng(whole),
+            "@arrow"                        => parse_line(whole, true),
             "@backward" | "@back"           => Ok(Backward),
+            "@bake"                         => parse_bake(whole),
+            "@blur"                         => parse_effect(whole, effect::Effect::Blur),
+            "@cancel-download"              => parse_command1(whole, Operation::CancelDownload),
             "@cd" | "@chdir" | "@change-directory"
                                             => parse_command1(whole, Operation::ChangeDirectory),
             "@chain"                        => parse_chainer(whole, Operation::Chain),
@@ -291,18 +337,21 @@ fn _parse_from_vec(whole: &[String]) -> Result<Operation, ParsingError> {
             "@copy-to-clipboard" | "@clipboard"
                                             => parse_copy_to_clipboard(whole),
             "@count"                        => parse_count(whole),
+            "@crop"                         => parse_crop(whole),
             "@cycle"                        => parse_option_cycle(whole),
             "@dec" | "@decrement" | "@decrease" | "@--"
                                             => parse_usize(whole, OptionUpdater::Decrement, 1),
             "@default"                      => Ok(LoadDefault),
             "@define-switch"                => parse_define_switch(whole),
             "@delete"                       => parse_delete(whole),
+            "@delete-duplicates"            => Ok(MarkDuplicates(true)),
             "@disable"                      => parse_option_1(whole, OptionUpdater::Disable),
             "@draw"                         => Ok(Draw),
             "@editor"                       => parse_editor(whole),
             "@enable"                       => parse_option_1(whole, OptionUpdater::Enable),
             "@eval"                         => Ok(Operation::Eval(whole[1..].to_vec())),
             "@expand"                       => parse_expand(whole),
+            "@fetch-offline"                => Ok(FetchOffline),
             "@file-copy"                    => parse_file(whole, filer::FileOperation::new_copy),
             "@file-move"                    => parse_file(whole, filer::FileOperation::new_move),
             "@fill"                         => parse_fill(whole),
@@ -324,6 +373,7 @@ fn _parse_from_vec(whole: &[String]) -> Result<Operation, ParsingError> {
             "@load-ui"                      => parse_load(whole, LoadUI),
             "@map"                          => parse_map(whole, true),
             "@mark"                         => parse_mark(whole),
+            "@mark-duplicates"              => Ok(MarkDuplicates(false)),
             "@meow"                         => Ok(Meow),
             "@message"                      => parse_message(whole),
             "@move-again"                   => parse_move_again(whole),
@@ -332,6 +382,9 @@ fn _parse_from_vec(whole: &[String]) -> Result<Operation, ParsingError> {
             "@nop"                          => Ok(Nop),
             "@page"                         => parse_page(whole),
             "@pdf-index"                    => parse_pdf_index(whole),
+            "@pick-color"                   => parse_pick_color(whole),
+            "@pixelate" | "@mosaic"         => parse_effect(whole, effect::Effect::Pixelate),
+            "@line"                         => parse_line(whole, false),
             "@link-action" | "@link"        => Ok(Operation::LinkAction(whole[1..].to_vec())),
             "@prev" | "@p" | "@previous"    => parse_move5(whole, Previous),
             "@pop-count"                    => Ok(PopCount),
@@ -340,20 +393,28 @@ fn _parse_from_vec(whole: &[String]) -> Result<Operation, ParsingError> {
             "@push-archive"                 => parse_push(whole, |it, meta, force, show| PushArchive(Expandable::new(it), meta, force, show)),
             "@push-clipboard"               => parse_push_clipboard(whole),
             "@push-directory" | "@push-dir" => parse_push(whole, |it, meta, force, _| PushDirectory(Expandable::new(it), meta, force)),
+            "@push-feed"                    => parse_push(whole, |it, meta, force, show| PushFeed(Expandable::new(it), meta, force, show)),
             "@push-image"                   => parse_push_image(whole),
+            "@push-list"                    => parse_push(whole, |it, meta, force, show| PushList(Expandable::new(it), meta, force, show)),
             "@push-message"                 => parse_push_message(whole),
             "@push-next"                    => parse_push_sibling(whole, true),
+            "@push-page"                    => parse_push_page(whole),
             "@push-pdf"                     => parse_push(whole, |it, meta, force, show| PushPdf(Expandable::new(it), meta, force, show)),
             "@push-previous" | "@push-prev" => parse_push_sibling(whole, false),
             "@push-url"                     => parse_push_url(whole),
+            "@push-video"                   => parse_push(whole, |it, meta, force, show| PushVideo(Expandable::new(it), meta, force, show)),
             "@query"                        => parse_query(whole),
             "@queue"                        => parse_queue(whole),
             "@quit"                         => Ok(EventName::Quit.operation()),
             "@record"                       => parse_record_pre(whole),
             "@random" | "@rand"             => Ok(Random),
+            "@redo"                         => parse_redo(whole),
             "@refresh" | "@r"               => parse_refresh(whole),
+            "@remote-cache"                 => parse_remote_cache(whole),
             "@remove-effects"               => Ok(RemoveEffects),
+            "@retry-failed"                 => Ok(RetryFailed),
             "@save"                         => parse_save(whole),
+            "@scrape-rule"                  => parse_scrape_rule(whole),
             "@scroll"                       => parse_scroll(whole),
             "@search"                       => parse_search(whole),
             "@set"                          => parse_option_set(whole),
@@ -363,7 +424,9 @@ fn _parse_from_vec(whole: &[String]) -> Result<Operation, ParsingError> {
             "@shell-filter"                 => parse_shell_filter(whole),
             "@show"                         => parse_move(whole, Show),
             "@shuffle"                      => parse_modify_entry_order(whole, Operation::Shuffle),
+            "@smear"                        => parse_effect(whole, effect::Effect::Smear),
             "@sort"                         => parse_sort(whole),
+            "@text"                         => parse_text(whole),
             "@timer"                        => parse_timer(whole),
             "@toggle"                       => parse_option_1(whole, OptionUpdater::Toggle),
             "@unchain"                      => parse_chainer(whole, Operation::Unchain),
@@ -375,10 +438,14 @@ fn _parse_from_vec(whole: &[String]) -> Result<Operation, ParsingError> {
             "@unmark-all"                   => Ok(Unmark(None)),
             "@unset"                        => parse_option_1(whole, OptionUpdater::Unset),
             "@update"                       => parse_update(whole),
+            "@update-hashes"                => Ok(UpdateHashes),
+            "@update-statistics"            => Ok(UpdateStatistics),
             "@user"                         => Ok(Operation::user(args)),
             "@views" | "@v"                 => parse_views(whole),
             "@when"                         => parse_when(whole, false),
             "@write"                        => parse_write(whole),
+            "@zoom-in"                      => parse_zoom(whole, false),
+            "@zoom-out"                     => parse_zoom(whole, true),
             name => if let Some(stripped) = name.strip_prefix('@') {
                 Ok(Operation::Fire(mapping::Mapped::Operation(o!(stripped), whole[1..].to_vec())))
             } else {
@@ -413,6 +480,18 @@ impl Operation {
         }
     }
 
+    /** Operations that change the modifiers or the drawing states **/
+    pub fn is_undoable(&self) -> bool {
+        use self::Operation::*;
+
+        match *self {
+            Cherenkov(_) | CherenkovReset | Clip(_) | Effect(_, _, _, _) | Fill(_, _, _, _, _, _) | Line(_, _, _) |
+                RemoveEffects | Text(_, _) | Unclip | Zoom(_, _) => true,
+            UpdateOption(OptionName::PreDefined(ref name), _) => name.is_for_drawing(),
+            _ => false,
+        }
+    }
+
     fn user(args: &[String]) -> Operation {
         let mut result: Vec<(String, String)> = vec![];
         let mut index = 0;
@@ -451,8 +530,11 @@ impl fmt::Debug for Operation {
 
         let s = match *self {
             Apng(_, _) => "Apng",
+            AnchorScrolls(_) => "AnchorScrolls",
             AppEvent(ref ev, _) => return write!(f, "AppEvent({:?})", ev),
             Backward => "Backward",
+            Bake(_, _) => "Bake",
+            CancelDownload(_) => "CancelDownload",
             Chain(_) => "Chain",
             ChangeDirectory(_) => "ChangeDirectory",
             Cherenkov(_) => "Cherenkov",
@@ -465,14 +547,18 @@ impl fmt::Debug for Operation {
             CopyToClipboard(_) => "CopyToClipboard",
             Count(_) => "Count",
             CountDigit(_) => "CountDigit",
+            Crop(_) => "Crop",
             DefineUserSwitch(_, _) => "DefineUserSwitch",
             Delete(_) => "delete",
             DetectEyes(_) => "DetectEyes",
+            DiscardJournal(_) => "DiscardJournal",
             Draw => "Draw ",
             Editor(_, _, _, _, _) => "Editor",
+            Effect(_, _, _, _) => "Effect",
             Error(ref error) => return write!(f, "Error({:?})", error),
             Eval(_) => "Eval",
             Expand(_, _) => "Expand",
+            FetchOffline => "FetchOffline",
             FileChanged(_) => "FileChanged",
             Fill(_, _, _, _, _, _) => "Fill",
             Filter(_, _) => "Filter",
@@ -489,6 +575,7 @@ impl fmt::Debug for Operation {
             KillTimer(_) => "KillTimer",
             Last(_, _, _, _) => "Last",
             LazyDraw(_, _) => "LazyDraw",
+            Line(_, _, _) => "Line",
             LinkAction(_) => "LinkAction",
             Load(_, _) => "Load",
             LoadDefault => "LoadDefault ",
@@ -496,6 +583,7 @@ impl fmt::Debug for Operation {
             MakeVisibles(_) => "MakeVisibles",
             Map(_, _, _) => "Map",
             Mark(_, _) => "Mark",
+            MarkDuplicates(_) => "MarkDuplicates",
             Meow => "Meow",
             Message(_, _) => "Message",
             MoveAgain(_, _, _, _, _) => "MoveAgain",
@@ -504,10 +592,13 @@ impl fmt::Debug for Operation {
             Nop => "Nop ",
             OperateFile(_) => "OperateFile",
             Page(_) => "Page",
+            Pan(_, _) => "Pan",
             PdfIndex(_, _, _, _, _, _) => "PdfIndex",
+            PickColor(_, _, _) => "PickColor",
             PreFetch(_) => "PreFetch",
             Previous(_, _, _, _, _) => "Previous",
             Pointer(visibility) => return write!(f, "Pointer({:?})", visibility),
+            PointerMotion(_) => "PointerMotion",
             PopCount => "PopCount",
             Pull => "Pull ",
             Push(_, _, _, _) => "Push",
@@ -515,22 +606,30 @@ impl fmt::Debug for Operation {
             PushArchive(_, _, _, _) => "PushArchive",
             PushClipboard(_, _, _, _, _) => "PushClipboard",
             PushDirectory(_, _, _) => "PushDirectory",
+            PushFeed(_, _, _, _) => "PushFeed",
             PushImage(_, _, _, _, _) => "PushImage",
+            PushList(_, _, _, _) => "PushList",
             PushMessage(_, _, _) => "PushMessage",
             PushMemory(_, _, _) => "PushMemory",
+            PushPage(_, _, _, _, _) => "PushPage",
             PushPdf(_, _, _, _) => "PushPdf",
             PushSibling(_, _, _, _, _) => "PushSibling",
-            PushURL(_, _, _, _, _) => "PushURL",
+            PushURL(_, _, _, _, _, _) => "PushURL",
+            PushVideo(_, _, _, _) => "PushVideo",
             Query(_, _) => "Query",
             Queue(_, _) => "Queue",
             Random => "Random ",
             Record(_, _, _) => "Record",
             RecordPre(_, _) => "RecordPre",
+            Redo(_) => "Redo",
             Refresh(_) => "Refresh",
+            RemoteCache(_, _) => "RemoteCache",
             RemoveEffects => "RemoveEffects ",
             ResetFocus => "ResetFocus",
             ResetScrolls(_) => "ResetScrolls",
+            RetryFailed => "RetryFailed",
             Save(_, _, _) => "Save",
+            ScrapeRule(_, _, _, _) => "ScrapeRule",
             SearchText(_, _, _) => "SearchText",
             Scroll(_, _, _, _, _, _) => "Scroll",
             SetEnv(_, _) => "SetEnv",
@@ -538,10 +637,12 @@ impl fmt::Debug for Operation {
             ShellFilter(_, _) => "ShellFilter",
             Show(_, _, _, _) => "Show",
             ShowCommandLine(_) => "ShowCommandLine",
+            ShowStatistics(_) => "ShowStatistics",
             Shuffle(_) => "Shuffle",
             Sort(_, _, _) => "Sort",
             Sorter(_, _, _) => "Sorter",
             TellRegion(_, _, _, _, _) => "TellRegion",
+            Text(_, _) => "Text",
             Timer(_, _, _, _, _) => "Timer",
             UIAction(_) => "UIAction",
             Unchain(_) => "Unchain",
@@ -550,7 +651,9 @@ impl fmt::Debug for Operation {
             Unmap(_) => "Unmap",
             Unmark(_) => "Unmark",
             Update(_) => "Update",
+            UpdateHashes => "UpdateHashes",
             UpdateOption(ref name, _) => return write!(f, "UpdateOption({:?})", name),
+            UpdateStatistics => "UpdateStatistics",
             UpdateUI => "UpdateUI ",
             User(_) => "User",
             Views(_, _, _) => "Views",
@@ -559,6 +662,7 @@ impl fmt::Debug for Operation {
             When(_, _, _) => "When",
             WithMessage(_, _) => "WithMessage",
             Write(_, _) => "Write",
+            Zoom(_, _) => "Zoom",
         };
         write!(f, "{}", s)
     }
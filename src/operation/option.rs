@@ -1,6 +1,8 @@
 
 use std::str::FromStr;
 
+use crate::remote_cache::curl_options::HostOptionName;
+
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum OptionUpdater {
@@ -18,6 +20,7 @@ pub enum OptionUpdater {
 #[derive(Clone, Debug, PartialEq)]
 pub enum OptionName {
     PreDefined(PreDefinedOptionName),
+    Host(String, HostOptionName), /* `http-<NAME>@<HOST_PATTERN>` */
     UserDefined(String),
 }
 
@@ -26,34 +29,61 @@ iterable_enum!(PreDefinedOptionName =>
     Animation,
     AutoPaging,
     AutoReload,
+    Brightness,
     Canonicalize,
+    CherenkovStore,
     ColorLink,
+    Compare,
+    CompareOperator,
+    CompareRatio,
+    Contrast,
     CurlConnectTimeout,
     CurlFollowLocation,
     CurlLowSpeedLimit,
     CurlLowSpeedTime,
+    CurlRetries,
+    CurlRetryDelay,
     CurlTimeout,
+    DragToPan,
     EmptyStatusFormat,
+    EyeDetector,
+    EyeDetectorCascade,
+    EyeDetectorCommand,
     FitTo,
     Freeze,
+    Gamma,
+    Grayscale,
     HistoryFile,
     HorizontalFlip,
     HorizontalViews,
     IdleTime,
     IgnoreFailures,
     InitialPosition,
+    Invert,
     LogFile,
+    Loupe,
+    LoupeMagnification,
+    LoupeSize,
     MaskOperator,
+    Offline,
     PathList,
+    PixelInspector,
     PreFetchEnabled,
     PreFetchLimit,
     PreFetchPageSize,
     PreFetchStages,
+    RememberZoom,
+    RemoteCacheMaxAge,
+    RemoteCacheMaxSize,
     Reverse,
     Rotation,
+    Saturation,
     Screen,
+    SimilarityThreshold,
     SkipResizeWindow,
+    SmoothScroll,
     StablePush,
+    Statistics,
     StatusBar,
     StatusBarAlign,
     StatusBarHeight,
@@ -63,9 +93,12 @@ iterable_enum!(PreDefinedOptionName =>
     Style,
     TimeToHidePointer,
     TitleFormat,
+    UndoDepth,
     UpdateCacheAccessTime,
     VerticalFlip,
     VerticalViews,
+    VideoFrameCommand,
+    VideoFrames,
     WatchFiles,
 );
 
@@ -81,32 +114,59 @@ impl FromStr for PreDefinedOptionName {
             "animation" | "anim"                   => Animation,
             "auto-reload"                          => AutoReload,
             "auto-page" | "auto-paging" | "paging" => AutoPaging,
+            "brightness"                           => Brightness,
             "canonicalize"                         => Canonicalize,
+            "cherenkov-store"                      => CherenkovStore,
+            "compare"                              => Compare,
+            "compare-operator"                     => CompareOperator,
+            "compare-ratio"                        => CompareRatio,
+            "contrast"                             => Contrast,
             "curl-connect-timeout"                 => CurlConnectTimeout,
             "curl-follow-location"                 => CurlFollowLocation,
             "curl-low-speed-limit"                 => CurlLowSpeedLimit,
             "curl-low-speed-time"                  => CurlLowSpeedTime,
+            "curl-retries"                         => CurlRetries,
+            "curl-retry-delay"                     => CurlRetryDelay,
             "curl-timeout"                         => CurlTimeout,
+            "drag-to-pan"                          => DragToPan,
             "empty-status-format"                  => EmptyStatusFormat,
+            "eye-detector"                         => EyeDetector,
+            "eye-detector-cascade"                 => EyeDetectorCascade,
+            "eye-detector-command"                 => EyeDetectorCommand,
             "fit-to" | "fit"                       => FitTo,
             "freeze" | "freezed"                   => Freeze,
+            "gamma"                                => Gamma,
+            "grayscale" | "greyscale"              => Grayscale,
             "history-file"                         => HistoryFile,
             "horizontal-flip"                      => HorizontalFlip,
             "horizontal-views"                     => HorizontalViews,
             "idle-time"                            => IdleTime,
             "ignore-failures" | "ignore-failure"   => IgnoreFailures,
             "initial-position"                     => InitialPosition,
+            "invert"                               => Invert,
             "log-file" | "log"                     => LogFile,
+            "loupe"                                => Loupe,
+            "loupe-magnification"                  => LoupeMagnification,
+            "loupe-size"                           => LoupeSize,
             "mask-operator"                        => MaskOperator,
+            "offline"                              => Offline,
             "path"                                 => PathList,
+            "pixel-inspector"                      => PixelInspector,
             "pre-render"                           => PreFetchEnabled,
             "pre-render-limit"                     => PreFetchLimit,
             "pre-render-pages"                     => PreFetchPageSize,
             "pre-render-stages"                    => PreFetchStages,
+            "remember-zoom"                        => RememberZoom,
+            "remote-cache-max-age"                 => RemoteCacheMaxAge,
+            "remote-cache-max-size"                => RemoteCacheMaxSize,
             "reverse" | "rev"                      => Reverse,
             "rotation"                             => Rotation,
+            "saturation"                           => Saturation,
             "screen"                               => Screen,
+            "similarity-threshold"                 => SimilarityThreshold,
+            "smooth-scroll"                        => SmoothScroll,
             "stable-push"                          => StablePush,
+            "statistics" | "histogram"             => Statistics,
             "status-bar" | "status"                => StatusBar,
             "status-bar-align" | "status-align"    => StatusBarAlign,
             "status-bar-height" | "status-height"  => StatusBarHeight,
@@ -116,9 +176,12 @@ impl FromStr for PreDefinedOptionName {
             "stdout"                               => StdOut,
             "time-to-hide-pointer"                 => TimeToHidePointer,
             "title-format"                         => TitleFormat,
+            "undo-depth"                           => UndoDepth,
             "update-cache-atime"                   => UpdateCacheAccessTime,
             "vertical-flip"                        => VerticalFlip,
             "vertical-views"                       => VerticalViews,
+            "video-frame-command"                  => VideoFrameCommand,
+            "video-frames"                         => VideoFrames,
             "watch-files"                          => WatchFiles,
             "skip-resize-window"                   => SkipResizeWindow,
             "link-color"                           => ColorLink,
@@ -133,15 +196,22 @@ impl PreDefinedOptionName {
         use self::PreDefinedOptionName::*;
 
         match *self {
-            AbbrevLength | Animation | AutoReload | AutoPaging |
-                Canonicalize | ColorLink |
-                FitTo | Freeze | HorizontalFlip | HorizontalViews | IdleTime | IgnoreFailures | InitialPosition | LogFile | MaskOperator |
-                PathList | PreFetchEnabled | PreFetchLimit | PreFetchPageSize | PreFetchStages |
-                Reverse | Rotation | Screen | SkipResizeWindow | StablePush | StatusBar | StatusBarAlign | StatusBarHeight | StatusBarOverlay | StatusFormat | EmptyStatusFormat | Style |
-                StdOut | UpdateCacheAccessTime | TimeToHidePointer | TitleFormat | VerticalFlip | VerticalViews | WatchFiles | HistoryFile => false,
-            CurlConnectTimeout | CurlFollowLocation | CurlLowSpeedLimit | CurlLowSpeedTime | CurlTimeout => true,
+            AbbrevLength | Animation | AutoReload | AutoPaging | Brightness |
+                Canonicalize | CherenkovStore | ColorLink | Compare | CompareOperator | CompareRatio | Contrast | Gamma | Grayscale | Invert | Saturation |
+                DragToPan | EyeDetector | EyeDetectorCascade | EyeDetectorCommand | FitTo | Freeze | HorizontalFlip | HorizontalViews | IdleTime | IgnoreFailures | InitialPosition | LogFile | Loupe | LoupeMagnification | LoupeSize | MaskOperator | Offline |
+                PathList | PixelInspector | PreFetchEnabled | PreFetchLimit | PreFetchPageSize | PreFetchStages |
+                RememberZoom | RemoteCacheMaxAge | RemoteCacheMaxSize | Reverse | Rotation | Screen | SimilarityThreshold | SkipResizeWindow | SmoothScroll | StablePush | Statistics | StatusBar | StatusBarAlign | StatusBarHeight | StatusBarOverlay | StatusFormat | EmptyStatusFormat | Style |
+                StdOut | UpdateCacheAccessTime | TimeToHidePointer | TitleFormat | UndoDepth | VerticalFlip | VerticalViews | VideoFrameCommand | VideoFrames | WatchFiles | HistoryFile => false,
+            CurlConnectTimeout | CurlFollowLocation | CurlLowSpeedLimit | CurlLowSpeedTime | CurlRetries | CurlRetryDelay | CurlTimeout => true,
         }
     }
+
+    /** Options restored by `@undo` **/
+    pub fn is_for_drawing(&self) -> bool {
+        use self::PreDefinedOptionName::*;
+
+        matches!(*self, Brightness | Contrast | FitTo | Gamma | Grayscale | HorizontalFlip | Invert | Rotation | Saturation | VerticalFlip)
+    }
 }
 
 
@@ -151,6 +221,13 @@ impl FromStr for OptionName {
     fn from_str(src: &str) -> Result<Self, String> {
         use self::OptionName::*;
 
+        if let Some(rest) = src.strip_prefix("http-") {
+            let mut parts = rest.splitn(2, '@');
+            if let (Some(name), Some(pattern)) = (parts.next(), parts.next()) {
+                return Ok(Host(o!(pattern), name.parse().map_err(|it| s!(it))?));
+            }
+        }
+
         Ok({
             src.parse().map(PreDefined).unwrap_or_else(|_| {
                 UserDefined(o!(src))
@@ -164,3 +241,16 @@ impl Default for OptionName {
         OptionName::PreDefined(PreDefinedOptionName::StatusBar)
     }
 }
+
+
+
+#[cfg(test)]#[test]
+fn test_option_name() {
+    use self::OptionName::*;
+
+    assert_eq!("fit-to".parse::<OptionName>(), Ok(PreDefined(PreDefinedOptionName::FitTo)));
+    assert_eq!("http-header@*.example.com".parse::<OptionName>(), Ok(Host(o!("*.example.com"), HostOptionName::Header)));
+    assert_eq!("http-ua@*".parse::<OptionName>(), Ok(Host(o!("*"), HostOptionName::UserAgent)));
+    assert!("http-unknown@*".parse::<OptionName>().is_err());
+    assert_eq!("http-foo".parse::<OptionName>(), Ok(UserDefined(o!("http-foo"))));
+}
@@ -7,17 +7,21 @@ use std::time::Duration;
 use argparse::{ArgumentParser, Collect, Store, StoreConst, StoreTrue, StoreFalse, StoreOption, List};
 
 use crate::chainer;
+use crate::cherenkov::annotation;
+use crate::cherenkov::effect::Effect;
 use crate::cherenkov::fill::Shape;
 use crate::cherenkov::nova::Seed;
 use crate::color::Color;
+use crate::crop::Fixed;
 use crate::entry::filter::expression::Expr as FilterExpr;
 use crate::entry::{Meta, MetaEntry, SearchKey, new_opt_meta};
 use crate::expandable::Expandable;
 use crate::filer::{IfExist, FileOperation};
+use crate::inspector::ColorFormat;
 use crate::key::{Key, new_key_sequence};
 use crate::mapping::{Mapped, MappedType};
 use crate::shellexpand_wrapper as sh;
-use crate::size::{CoordPx, Size};
+use crate::size::{Coord, CoordPx, Size};
 use crate::util::string::join;
 
 use crate::operation::*;
@@ -143,6 +147,26 @@ pub fn parse_apng(args: &[String]) -> Result<Operation, ParsingError> {
     })
 }
 
+pub fn parse_bake(args: &[String]) -> Result<Operation, ParsingError> {
+    let mut cell_index = 1;
+    let mut if_exist = IfExist::NewFileName;
+    let mut path: String = o!("");
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut if_exist)
+            .add_option(&["--fail", "-f"], StoreConst(IfExist::Fail), "Fail if file exists")
+            .add_option(&["--overwrite", "-o"], StoreConst(IfExist::Overwrite), "Overwrite the file if file exists")
+            .add_option(&["--new", "--new-file-name", "-n"], StoreConst(IfExist::NewFileName), "Generate new file name if file exists (default)");
+        ap.refer(&mut cell_index).add_option(&["--cell-index", "-i"], Store, "Cell index (1 origin, default = 1)");
+        ap.refer(&mut path).add_argument("path", Store, "Save to").required();
+        parse_args(&mut ap, args)
+    } .and_then(|_| {
+        let (destination, filename) = parse_destination(&path, None, true)?;
+        Ok(Operation::Bake(FileOperation::new_copy(destination, filename, if_exist, None), max!(cell_index, 1) - 1))
+    })
+}
+
 pub fn parse_cherenkov(args: &[String]) -> Result<Operation, ParsingError> {
     let mut p = CherenkovParameter {
         radius: 0.1,
@@ -203,20 +227,26 @@ where F: FnOnce(PathBuf, Option<String>, IfExist, Option<Size>) -> FileOperation
         ap.refer(&mut filename).add_argument("filename", StoreOption, "Filename");
         parse_args(&mut ap, args)
     } .and_then(|_| {
-        if as_filepath {
-            if let Some(filename) = filename {
-                return Err(ParsingError::InvalidArgument(format!("If `--as-filepath` is given, omit `{}`", filename)));
-            }
-            let destination = sh::expand_to_pathbuf(&destination);
-            if_let_some!(dir = destination.parent(), Err(ParsingError::InvalidArgument(format!("No directory part: {:?}", destination))));
-            if_let_some!(filename = destination.file_name(), Err(ParsingError::InvalidArgument(format!("No filename part: {:?}", destination))));
-            let filename = o!(filename.to_str().unwrap());
-            return Ok(Operation::OperateFile(op(dir.to_path_buf(), Some(filename), if_exist, size)));
-        }
-        Ok(Operation::OperateFile(op(sh::expand_to_pathbuf(&destination), filename, if_exist, size)))
+        let (destination, filename) = parse_destination(&destination, filename, as_filepath)?;
+        Ok(Operation::OperateFile(op(destination, filename, if_exist, size)))
     })
 }
 
+/** (directory, file name) **/
+fn parse_destination(destination: &str, filename: Option<String>, as_filepath: bool) -> Result<(PathBuf, Option<String>), ParsingError> {
+    if as_filepath {
+        if let Some(filename) = filename {
+            return Err(ParsingError::InvalidArgument(format!("If `--as-filepath` is given, omit `{}`", filename)));
+        }
+        let destination = sh::expand_to_pathbuf(destination);
+        if_let_some!(dir = destination.parent(), Err(ParsingError::InvalidArgument(format!("No directory part: {:?}", destination))));
+        if_let_some!(filename = destination.file_name(), Err(ParsingError::InvalidArgument(format!("No filename part: {:?}", destination))));
+        let filename = o!(filename.to_str().unwrap());
+        return Ok((dir.to_path_buf(), Some(filename)));
+    }
+    Ok((sh::expand_to_pathbuf(destination), filename))
+}
+
 pub fn parse_fire(args: &[String]) -> Result<Operation, ParsingError> {
     impl MappedType {
         pub fn input_from_text(self, text: &str) -> Result<Mapped, ParsingError> {
@@ -439,6 +469,77 @@ pub fn parse_fill(args: &[String]) -> Result<Operation, ParsingError> {
     })
 }
 
+pub fn parse_crop(args: &[String]) -> Result<Operation, ParsingError> {
+    let mut aspect: Option<String> = None;
+    let mut as_filepath = false;
+    let mut cell_index = 1;
+    let mut destination = o!("");
+    let mut filename: Option<String> = None;
+    let mut if_exist = IfExist::NewFileName;
+    let mut region = None;
+    let mut size: Option<Size> = None;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut if_exist)
+            .add_option(&["--fail", "-f"], StoreConst(IfExist::Fail), "Fail if file exists")
+            .add_option(&["--overwrite", "-o"], StoreConst(IfExist::Overwrite), "Overwrite the file if file exists")
+            .add_option(&["--new", "--new-file-name", "-n"], StoreConst(IfExist::NewFileName), "Generate new file name if file exists (default)");
+        ap.refer(&mut aspect).add_option(&["--aspect", "-a"], StoreOption, "Aspect ratio (e.g. 16:9)");
+        ap.refer(&mut size).add_option(&["--size", "-s"], StoreOption, "Pixel size (e.g. 640x480)");
+        ap.refer(&mut cell_index).add_option(&["--cell-index", "-i"], Store, "Cell index (1 origin, default = 1)");
+        ap.refer(&mut region).add_option(&["--region", "-r"], StoreOption, "Target region");
+        ap.refer(&mut as_filepath).add_option(&["--as-filepath", "-F"], StoreTrue, "Destination as filepath");
+        ap.refer(&mut destination).add_argument("destination", Store, "Destination directory").required();
+        ap.refer(&mut filename).add_argument("filename", StoreOption, "Filename");
+        parse_args(&mut ap, args)
+    } .and_then(|_| {
+        let fixed = match (aspect, size) {
+            (Some(_), Some(_)) =>
+                return Err(ParsingError::Fixed("`--aspect` and `--size` are exclusive")),
+            (Some(aspect), None) => {
+                let ratio: Vec<u32> = aspect.split(':').filter_map(|it| it.parse().ok()).collect();
+                if ratio.len() != 2 || ratio.contains(&0) {
+                    return Err(ParsingError::InvalidArgument(format!("Invalid aspect ratio: {}", aspect)));
+                }
+                Some(Fixed::Aspect(ratio[0], ratio[1]))
+            },
+            (None, Some(size)) =>
+                Some(Fixed::Size(size)),
+            (None, None) =>
+                None,
+        };
+        let (destination, filename) = parse_destination(&destination, filename, as_filepath)?;
+        Ok(Operation::Crop(CropParameter {
+            cell_index: max!(cell_index, 1) - 1,
+            file_operation: FileOperation::new_copy(destination, filename, if_exist, None),
+            fixed,
+            region,
+        }))
+    })
+}
+
+pub fn parse_effect(args: &[String], effect: fn(f64) -> Effect) -> Result<Operation, ParsingError> {
+    let mut cell_index = 1;
+    let mut region = None;
+    let mut shape = Shape::Rectangle;
+    let mut size = 0.02;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut cell_index).add_option(&["--cell-index", "-i"], Store, "Cell index (1 origin, default = 1)");
+        ap.refer(&mut region).add_option(&["--region", "-r"], StoreOption, "Target region");
+        ap.refer(&mut shape).add_option(&["--shape", "-s"], Store, "Shape (rectangle/circle/ellipse)");
+        ap.refer(&mut size).add_option(&["--size", "-S"], Store, "Size (ratio to the longer side of the image)");
+        parse_args(&mut ap, args)
+    } .and_then(|_| {
+        if size <= 0.0 {
+            return Err(ParsingError::InvalidArgument(format!("Invalid size: {}", size)));
+        }
+        Ok(Operation::Effect(effect(size), shape, region, max!(cell_index, 1) - 1))
+    })
+}
+
 pub fn parse_filter(args: &[String]) -> Result<Operation, ParsingError> {
     let mut expr = vec![];
     let mut dynamic = true;
@@ -546,6 +647,30 @@ pub fn parse_kill_timer(args: &[String]) -> Result<Operation, ParsingError> {
     })
 }
 
+pub fn parse_line(args: &[String], arrow: bool) -> Result<Operation, ParsingError> {
+    let mut arrow = arrow;
+    let mut cell_index = 1;
+    let mut color = Color::new(255, 0, 0);
+    let mut points: Vec<Coord> = vec![];
+    let mut width = 0.005;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut arrow).add_option(&["--arrow", "-a"], StoreTrue, "Arrow");
+        ap.refer(&mut cell_index).add_option(&["--cell-index", "-i"], Store, "Cell index (1 origin, default = 1)");
+        ap.refer(&mut color).add_option(&["--color", "-c"], Store, "Line color");
+        ap.refer(&mut width).add_option(&["--width", "-w"], Store, "Line width (ratio to the longer side of the image)");
+        ap.refer(&mut points).add_argument("points", List, "Points (e.g. 0.1x0.1 0.5x0.5)");
+        parse_args(&mut ap, args)
+    } .and_then(|_| {
+        if width <= 0.0 {
+            return Err(ParsingError::InvalidArgument(format!("Invalid width: {}", width)));
+        }
+        let line = annotation::Line { color, points, width };
+        Ok(Operation::Line(line, arrow, max!(cell_index, 1) - 1))
+    })
+}
+
 pub fn parse_load<T>(args: &[String], op: T) -> Result<Operation, ParsingError>
 where T: Fn(Expandable, bool) -> Operation {
     let mut file: String = o!("");
@@ -840,6 +965,25 @@ pub fn parse_pdf_index(args: &[String]) -> Result<Operation, ParsingError> {
     })
 }
 
+pub fn parse_pick_color(args: &[String]) -> Result<Operation, ParsingError> {
+    let mut format = ColorFormat::default();
+    let mut selection = ClipboardSelection::default();
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut format)
+            .add_option(&["--css"], StoreConst(ColorFormat::Css), "`rgb(R, G, B)` or `rgba(R, G, B, A)`")
+            .add_option(&["--hex"], StoreConst(ColorFormat::Hex), "`#rrggbb` or `#rrggbbaa`");
+        ap.refer(&mut selection)
+            .add_option(&["--clipboard", "-c"], StoreConst(ClipboardSelection::Clipboard), "Use `Clipboard`")
+            .add_option(&["--primary", "-1", "-p"], StoreConst(ClipboardSelection::Primary), "Use `Primary`")
+            .add_option(&["--secondary", "-2", "-s"], StoreConst(ClipboardSelection::Secondary), "Use `Secondary`");
+        parse_args(&mut ap, args)
+    } .map(|_| {
+        Operation::PickColor(None, format, selection)
+    })
+}
+
 pub fn parse_push<T>(args: &[String], op: T) -> Result<Operation, ParsingError>
 where T: Fn(String, Option<Meta>, bool, bool) -> Operation {
     let mut meta: Vec<MetaEntry> = vec![];
@@ -948,24 +1092,49 @@ pub fn parse_push_sibling(args: &[String], next: bool) -> Result<Operation, Pars
     })
 }
 
+pub fn parse_push_page(args: &[String]) -> Result<Operation, ParsingError> {
+    let mut meta: Vec<MetaEntry> = vec![];
+    let mut url = o!("");
+    let mut force = false;
+    let mut show = false;
+    let mut pages = 10;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut meta).add_option(&["--meta", "-m"], Collect, "Meta data");
+        ap.refer(&mut force).add_option(&["--force", "-f"], StoreTrue, "Meta data");
+        ap.refer(&mut show).add_option(&["--show", "-s"], StoreTrue, "Show the first found entry");
+        ap.refer(&mut pages).add_option(&["--pages", "-p"], Store, "Maximum number of pages to follow");
+        ap.refer(&mut url).add_argument("URL", Store, "Page URL").required();
+        parse_args(&mut ap, args)
+    } .and_then(|_| {
+        if pages == 0 {
+            return Err(ParsingError::Fixed("Zero is invalid"));
+        }
+        Ok(Operation::PushPage(url, new_opt_meta(meta), force, show, pages))
+    })
+}
+
 pub fn parse_push_url(args: &[String]) -> Result<Operation, ParsingError> {
     let mut meta: Vec<MetaEntry> = vec![];
     let mut urls = Vec::<String>::new();
     let mut force = false;
     let mut show = false;
     let mut entry_type = None;
+    let mut referer = None;
 
     {
         let mut ap = ArgumentParser::new();
         ap.refer(&mut meta).add_option(&["--meta", "-m"], Collect, "Meta data");
         ap.refer(&mut force).add_option(&["--force", "-f"], StoreTrue, "Meta data");
         ap.refer(&mut show).add_option(&["--show", "-s"], StoreTrue, "Show the found entry");
-        ap.refer(&mut entry_type).add_option(&["--type", "-t", "--as"], StoreOption, "Type (image/archive/pdf)");
+        ap.refer(&mut entry_type).add_option(&["--type", "-t", "--as"], StoreOption, "Type (image/archive/pdf/video)");
+        ap.refer(&mut referer).add_option(&["--referer", "-r"], StoreOption, "Referer");
         ap.refer(&mut urls).add_argument("URL", Collect, "URL").required();
         parse_args(&mut ap, args)
     } .map(|_| {
         let meta = new_opt_meta(meta);
-        let ops = urls.into_iter().map(|it| Operation::PushURL(it, meta.clone(), force, show,entry_type)).collect();
+        let ops = urls.into_iter().map(|it| Operation::PushURL(it, meta.clone(), force, show, entry_type, referer.clone())).collect();
         Operation::Multi(ops, false)
     })
 }
@@ -1012,6 +1181,18 @@ pub fn parse_record_pre(args: &[String]) -> Result<Operation, ParsingError> {
     })
 }
 
+pub fn parse_redo(args: &[String]) -> Result<Operation, ParsingError> {
+    let mut count = None;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut count).add_argument("Count", StoreOption, "Count");
+        parse_args(&mut ap, args)
+    } .map(|_| {
+        Operation::Redo(count)
+    })
+}
+
 pub fn parse_refresh(args: &[String]) -> Result<Operation, ParsingError> {
     let mut image = false;
 
@@ -1024,6 +1205,20 @@ pub fn parse_refresh(args: &[String]) -> Result<Operation, ParsingError> {
     })
 }
 
+pub fn parse_remote_cache(args: &[String]) -> Result<Operation, ParsingError> {
+    let mut sweep = false;
+    let mut patterns: Vec<String> = vec![];
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut sweep).add_option(&["--sweep", "-s"], StoreTrue, "Remove old files");
+        ap.refer(&mut patterns).add_option(&["--delete", "-d"], Collect, "Remove the files for URL pattern");
+        parse_args(&mut ap, args)
+    } .map(|_| {
+        Operation::RemoteCache(sweep, patterns)
+    })
+}
+
 pub fn parse_save(args: &[String]) -> Result<Operation, ParsingError> {
     let mut path: String = o!("");
     let mut sources: Vec<Session> = vec![];
@@ -1063,6 +1258,32 @@ pub fn parse_set_env(args: &[String]) -> Result<Operation, ParsingError> {
     })
 }
 
+pub fn parse_scrape_rule(args: &[String]) -> Result<Operation, ParsingError> {
+    let mut unset = false;
+    let mut next = false;
+    let mut regex = false;
+    let mut pattern = o!("");
+    let mut source = None;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut unset).add_option(&["--unset", "-u"], StoreTrue, "Unset");
+        ap.refer(&mut next).add_option(&["--next", "-n"], StoreTrue, "Extract next page links");
+        ap.refer(&mut regex).add_option(&["--regex", "-r"], StoreTrue, "Regular expression instead of selector");
+        ap.refer(&mut pattern).add_argument("url_pattern", Store, "URL pattern (glob)").required();
+        ap.refer(&mut source).add_argument("selector", StoreOption, "Selector or regular expression");
+        parse_args(&mut ap, args)
+    } .and_then(|_| {
+        if unset {
+            Ok(Operation::ScrapeRule(pattern, None, next, regex))
+        } else if source.is_some() {
+            Ok(Operation::ScrapeRule(pattern, source, next, regex))
+        } else {
+            Err(ParsingError::TooFewArguments)
+        }
+    })
+}
+
 pub fn parse_scroll(args: &[String]) -> Result<Operation, ParsingError> {
     let mut direction = Direction::Down;
     let mut operation = vec![];
@@ -1160,7 +1381,9 @@ pub fn parse_sort(args: &[String]) -> Result<Operation, ParsingError> {
             .add_option(&["--file-size", "-s"], StoreConst(SortKey::FileSize), "Sort by file size")
             .add_option(&["--width", "-w"], StoreConst(SortKey::Width), "Sort by width")
             .add_option(&["--height", "-h"], StoreConst(SortKey::Height), "Sort by heigth")
-            .add_option(&["--dimensions", "-d"], StoreConst(SortKey::Dimensions), "Sort by width x height");
+            .add_option(&["--dimensions", "-d"], StoreConst(SortKey::Dimensions), "Sort by width x height")
+            .add_option(&["--similarity", "-S"], StoreConst(SortKey::Similarity), "Sort by similarity to the current entry")
+            .add_option(&["--duplicates", "-D"], StoreConst(SortKey::Duplicates), "Group near-duplicates");
         ap.refer(&mut reverse).add_option(&["--reverse", "-r"], StoreTrue, "Reversed");
         ap.refer(&mut command).add_argument("command", Collect, "Commadn");
         parse_args(&mut ap, args)
@@ -1188,6 +1411,38 @@ where T: FnOnce(bool) -> Operation {
     } .map(|_| op(fix))
 }
 
+pub fn parse_text(args: &[String]) -> Result<Operation, ParsingError> {
+    let mut bold = false;
+    let mut cell_index = 1;
+    let mut color = Color::new(255, 0, 0);
+    let mut font = o!("sans-serif");
+    let mut outline = None;
+    let mut outline_width = 0.1;
+    let mut position = Coord { x: 0.0, y: 0.0 };
+    let mut size = 0.05;
+    let mut text: Vec<String> = vec![];
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut bold).add_option(&["--bold", "-b"], StoreTrue, "Bold");
+        ap.refer(&mut cell_index).add_option(&["--cell-index", "-i"], Store, "Cell index (1 origin, default = 1)");
+        ap.refer(&mut color).add_option(&["--color", "-c"], Store, "Text color");
+        ap.refer(&mut font).add_option(&["--font", "-f"], Store, "Font family");
+        ap.refer(&mut outline).add_option(&["--outline", "-o"], StoreOption, "Outline color");
+        ap.refer(&mut outline_width).add_option(&["--outline-width", "-w"], Store, "Outline width (ratio to the font size)");
+        ap.refer(&mut position).add_option(&["--position", "-p"], Store, "Top left position (e.g. 0.1x0.1)");
+        ap.refer(&mut size).add_option(&["--size", "-S"], Store, "Font size (ratio to the longer side of the image)");
+        ap.refer(&mut text).add_argument("text", Collect, "Text").required();
+        parse_args(&mut ap, args)
+    } .and_then(|_| {
+        if size <= 0.0 {
+            return Err(ParsingError::InvalidArgument(format!("Invalid size: {}", size)));
+        }
+        let text = annotation::Text { bold, color, font, outline, outline_width, position, size, text: text.join(" ") };
+        Ok(Operation::Text(text, max!(cell_index, 1) - 1))
+    })
+}
+
 pub fn parse_timer(args: &[String]) -> Result<Operation, ParsingError> {
     let mut interval_seconds = 1.0;
     let mut name = None;
@@ -1301,6 +1556,18 @@ pub fn parse_write(args: &[String]) -> Result<Operation, ParsingError> {
     })
 }
 
+pub fn parse_zoom(args: &[String], out: bool) -> Result<Operation, ParsingError> {
+    let mut step = 10;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.refer(&mut step).add_argument("step", Store, "Step (percent)");
+        parse_args(&mut ap, args)
+    } .map(|_| {
+        Operation::Zoom(out, step)
+    })
+}
+
 pub fn parse_args(parser: &mut ArgumentParser, args: &[String]) -> Result<(), ParsingError> {
     parser.stop_on_first_argument(true);
     parser.parse(args.to_vec(), &mut sink(), &mut sink()).map_err(|code| ParsingError::InvalidArgument(s!(code)))
@@ -7,13 +7,15 @@ use cairo;
 use num::Integer;
 
 use crate::cherenkov::Operator;
+use crate::cherenkov::eye_detector;
+use crate::adjustment::Level;
 use crate::color::Color;
 use crate::errors::{AppResult, AppResultU, AppError};
 use crate::gui::{Position, Screen};
 use crate::option::*;
 use crate::resolution;
 use crate::size::FitTo;
-use crate::state::{Alignment, AutoPaging};
+use crate::state::{Alignment, AutoPaging, Compare};
 
 use crate::option::common;
 
@@ -61,6 +63,18 @@ impl OptionValue for Option<PathBuf> {
     }
 }
 
+impl OptionValue for Option<String> {
+    fn set(&mut self, value: &str) -> AppResultU {
+        *self = Some(o!(value));
+        Ok(())
+    }
+
+    fn unset(&mut self) -> AppResultU {
+        *self = None;
+        Ok(())
+    }
+}
+
 impl OptionValue for Duration {
     fn set(&mut self, value: &str) -> AppResultU {
         value.parse().map(|value: f64| {
@@ -175,6 +189,7 @@ def_opt_uint!(usize);
 def_opt_uint!(u64);
 def_opt_uint!(u32);
 def_uint!(usize);
+def_uint!(u32);
 def_uint!(u8);
 
 
@@ -224,6 +239,101 @@ impl OptionValue for AutoPaging {
     }
 }
 
+impl FromStr for Compare {
+    type Err = AppError;
+
+    fn from_str(src: &str) -> AppResult<Self> {
+        use self::Compare::*;
+
+        common::parse_bool(src).map(|it| {
+            if it { Compare::Synced } else { Compare::Off }
+        }).or_else(|_| {
+            let result = match src {
+                "sync" | "s" => Synced,
+                "difference" | "diff" | "d" => Difference,
+                "onion-skin" | "onion" | "o" => OnionSkin,
+                "slider" => Slider,
+                _ => return Err(AppError::InvalidValue(o!(src)))
+            };
+            Ok(result)
+        })
+    }
+}
+
+impl OptionValue for Compare {
+    fn is_enabled(&self) -> AppResult<bool> {
+        Ok(*self != Compare::Off)
+    }
+
+    fn enable(&mut self) -> AppResultU {
+        *self = Compare::Synced;
+        Ok(())
+    }
+
+    fn disable(&mut self) -> AppResultU {
+        *self = Compare::Off;
+        Ok(())
+    }
+
+    fn cycle(&mut self, reverse: bool, n: usize, candidates: &[String]) -> AppResultU {
+        use self::Compare::*;
+        set_cycled(self, &[Off, Synced, Difference, OnionSkin, Slider], reverse, n, candidates)
+    }
+
+    fn set(&mut self, value: &str) -> AppResultU {
+        value.parse().map(|value| {
+            *self = value;
+        })
+    }
+}
+
+impl OptionValue for eye_detector::Backend {
+    fn cycle(&mut self, reverse: bool, n: usize, candidates: &[String]) -> AppResultU {
+        use self::eye_detector::Backend::*;
+        set_cycled(self, &[Cascade, Command], reverse, n, candidates)
+    }
+
+    fn unset(&mut self) -> AppResultU {
+        *self = eye_detector::Backend::Cascade;
+        Ok(())
+    }
+
+    fn set(&mut self, value: &str) -> AppResultU {
+        value.parse().map(|value| {
+            *self = value;
+        })
+    }
+}
+
+impl OptionValue for Level {
+    fn cycle(&mut self, reverse: bool, n: usize, candidates: &[String]) -> AppResultU {
+        if candidates.is_empty() {
+            return if reverse { self.decrement(n) } else { self.increment(n) };
+        }
+        set_cycled(self, &[], reverse, n, candidates)
+    }
+
+    fn unset(&mut self) -> AppResultU {
+        *self = Level::default();
+        Ok(())
+    }
+
+    fn set(&mut self, value: &str) -> AppResultU {
+        *self = value.parse()?;
+        Ok(())
+    }
+
+    fn increment(&mut self, delta: usize) -> AppResultU {
+        *self = self.offset(min!(delta, 200) as i32);
+        Ok(())
+    }
+
+    fn decrement(&mut self, delta: usize) -> AppResultU {
+        *self = self.offset(-(min!(delta, 200) as i32));
+        Ok(())
+    }
+}
+
 impl OptionValue for Color {
     // CSS Color names
     // fn cycle(&mut self) -> Result<(), ChryError> {
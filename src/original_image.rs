@@ -0,0 +1,67 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+
+use gdk_pixbuf::Pixbuf;
+
+use crate::entry::image::{Imaging, get_image_buffer};
+use crate::entry::{Entry, Key};
+use crate::errors::{AppError, AppResult};
+use crate::image::{ImageBuffer, StaticImageBuffer};
+use crate::operation::Operation;
+use crate::size::{FitTo, Size};
+use crate::state::Drawing;
+
+
+
+/** The not scaled and not modified images of the cells for the pointer tools (loupe and pixel inspector).
+    They are decoded in background **/
+#[derive(Default)]
+pub struct OriginalImage {
+    images: HashMap<Key, Result<Pixbuf, String>>,
+    loaded: Arc<Mutex<HashMap<Key, Result<StaticImageBuffer, String>>>>,
+    loading: HashSet<Key>,
+}
+
+
+impl OriginalImage {
+    /** Returns `None` while the image is decoded, and then `on_load` is sent.
+        The failure is reported once when it is decoded, and then returned as `Err` **/
+    pub fn get(&mut self, entry: &Arc<Entry>, cell_size: Size, tx: &Sender<Operation>, on_load: Operation) -> AppResult<Option<Pixbuf>> {
+        if let Some(loaded) = self.loaded.lock().unwrap().remove(&entry.key) {
+            self.loading.remove(&entry.key);
+            self.images.insert(entry.key.clone(), loaded.map(|it| it.get_pixbuf()));
+        }
+
+        if let Some(image) = self.images.get(&entry.key) {
+            return image.clone().map(Some).map_err(AppError::Standard);
+        }
+
+        if self.loading.insert(entry.key.clone()) {
+            let (entry, loaded, tx) = (Arc::clone(entry), Arc::clone(&self.loaded), tx.clone());
+            spawn(move || {
+                let drawing = Drawing { animation: false, fit_to: FitTo::Original, ..Drawing::default() };
+                let image = match get_image_buffer(&entry.content, &Imaging::new(cell_size, drawing)) {
+                    Ok(ImageBuffer::Static(buffer)) => Ok(buffer),
+                    Ok(_) => Err(o!("Not static image")),
+                    Err(err) => Err(s!(err)),
+                };
+                if let Err(ref err) = image {
+                    puts_error!(err, "at" => "original_image", "for" => entry.display_path());
+                }
+                loaded.lock().unwrap().insert(entry.key.clone(), image);
+                tx.send(on_load).unwrap();
+            });
+        }
+
+        Ok(None)
+    }
+
+    /** Drop the images except for `keys` (the entries in the cells) **/
+    pub fn retain(&mut self, keys: &[Key]) {
+        self.images.retain(|key, _| keys.contains(key));
+        self.loaded.lock().unwrap().retain(|key, _| keys.contains(key));
+        self.loading.retain(|key| keys.contains(key));
+    }
+}
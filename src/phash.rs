@@ -0,0 +1,254 @@
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use gdk_pixbuf::{InterpType, Pixbuf, PixbufExt};
+
+use crate::app_path::cache_dir;
+use crate::entry::image::{Imaging, get_static_image_buffer};
+use crate::entry::{Entry, EntryContent, Key};
+use crate::errors::{AppError, AppResult, AppResultU};
+use crate::size::Size;
+use crate::state::Drawing;
+use crate::util::path::path_to_str;
+
+
+
+/** Difference hash (dHash) **/
+pub type PHash = u64;
+
+const HASH_WIDTH: i32 = 9;
+const HASH_HEIGHT: i32 = 8;
+const SOURCE_SIZE: i32 = 128;
+
+
+/** Clones share the same table **/
+#[derive(Clone, Default)]
+pub struct PHashTable {
+    table: Arc<Mutex<Table>>,
+}
+
+#[derive(Default)]
+struct Table {
+    duplicates: HashSet<Key>,
+    hashes: HashMap<Key, PHash>,
+    stored: Option<HashMap<String, PHash>>, /* path + mtime + page => hash */
+}
+
+
+impl PHashTable {
+    pub fn get(&self, key: &Key) -> Option<PHash> {
+        let table = self.table.lock().unwrap();
+        table.hashes.get(key).cloned()
+    }
+
+    pub fn is_duplicated(&self, key: &Key) -> bool {
+        let table = self.table.lock().unwrap();
+        table.duplicates.contains(key)
+    }
+
+    pub fn set_duplicates(&self, duplicates: HashSet<Key>) {
+        let mut table = self.table.lock().unwrap();
+        table.duplicates = duplicates;
+    }
+
+    /** Compute the hash of the entry, or load it from the disk cache **/
+    pub fn compute(&self, entry: &Entry) -> AppResult<PHash> {
+        let cache_key = cache_key(entry);
+
+        if let Some(ref cache_key) = cache_key {
+            let mut table = self.table.lock().unwrap();
+            if let Some(hash) = table.stored().get(cache_key).cloned() {
+                table.hashes.insert(entry.key.clone(), hash);
+                return Ok(hash);
+            }
+        }
+
+        let hash = dhash(&entry.content)?;
+
+        let mut table = self.table.lock().unwrap();
+        table.hashes.insert(entry.key.clone(), hash);
+        if let Some(cache_key) = cache_key {
+            table.store(cache_key, hash)?;
+        }
+        Ok(hash)
+    }
+
+    /** Group the near-duplicate keys. The order of `keys` is kept **/
+    pub fn groups(&self, keys: &[Key], threshold: u32) -> Vec<Vec<Key>> {
+        let table = self.table.lock().unwrap();
+        let mut result: Vec<Vec<Key>> = vec![];
+        let mut representatives: Vec<(PHash, usize)> = vec![];
+
+        for key in keys {
+            if let Some(hash) = table.hashes.get(key) {
+                let found = representatives.iter().find(|(it, _)| distance(*it, *hash) <= threshold).map(|it| it.1);
+                if let Some(index) = found {
+                    result[index].push(key.clone());
+                    continue;
+                }
+                representatives.push((*hash, result.len()));
+            }
+            result.push(vec![key.clone()]);
+        }
+
+        result
+    }
+}
+
+impl Table {
+    fn stored(&mut self) -> &mut HashMap<String, PHash> {
+        if self.stored.is_none() {
+            self.stored = Some(load().unwrap_or_else(|err| {
+                puts_event!("phash/load", "reason" => s!(err));
+                HashMap::new()
+            }));
+        }
+        self.stored.as_mut().unwrap()
+    }
+
+    fn store(&mut self, cache_key: String, hash: PHash) -> AppResultU {
+        let mut file = OpenOptions::new().create(true).append(true).open(cache_file())?;
+        writeln!(file, "{}\t{:016x}", cache_key, hash)?;
+        self.stored().insert(cache_key, hash);
+        Ok(())
+    }
+}
+
+
+pub fn distance(a: PHash, b: PHash) -> u32 {
+    (a ^ b).count_ones()
+}
+
+
+fn cache_file() -> PathBuf {
+    let mut result = cache_dir("phash");
+    result.push("hashes");
+    result
+}
+
+fn cache_key(entry: &Entry) -> Option<String> {
+    if let EntryContent::Memory(_, ref hash) = entry.content {
+        return Some(format!("{}\t0\t0", hash));
+    }
+
+    let path = entry.content.local_file_path()?;
+    let modified = path.metadata().and_then(|it| it.modified()).ok()?;
+    let modified = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let path = path_to_str(&path);
+    if path.contains('\n') {
+        return None;
+    }
+    Some(format!("{}\t{}\t{}", path, modified, entry.key.2))
+}
+
+fn dhash(content: &EntryContent) -> AppResult<PHash> {
+    let imaging = Imaging::new(Size::new(SOURCE_SIZE, SOURCE_SIZE), Drawing::default());
+    let buffer = get_static_image_buffer(content, &imaging)?;
+    let pixbuf = buffer.get_pixbuf().scale_simple(HASH_WIDTH, HASH_HEIGHT, InterpType::Bilinear).ok_or(AppError::Fixed("Could not scale image"))?;
+    Ok(dhash_from_pixbuf(&pixbuf))
+}
+
+fn dhash_from_pixbuf(pixbuf: &Pixbuf) -> PHash {
+    let channels = pixbuf.get_n_channels() as usize;
+    let rowstride = pixbuf.get_rowstride() as usize;
+    let pixels: &[u8] = unsafe { pixbuf.get_pixels() };
+    dhash_from_pixels(pixels, rowstride, channels)
+}
+
+/** `pixels` must be HASH_WIDTH x HASH_HEIGHT **/
+fn dhash_from_pixels(pixels: &[u8], rowstride: usize, channels: usize) -> PHash {
+    let luma = |x: i32, y: i32| -> u32 {
+        let i = y as usize * rowstride + x as usize * channels;
+        (u32::from(pixels[i]) * 299 + u32::from(pixels[i + 1]) * 587 + u32::from(pixels[i + 2]) * 114) / 1000
+    };
+
+    let mut result = 0;
+    for y in 0 .. HASH_HEIGHT {
+        for x in 0 .. HASH_WIDTH - 1 {
+            result <<= 1;
+            if luma(x, y) < luma(x + 1, y) {
+                result |= 1;
+            }
+        }
+    }
+    result
+}
+
+fn load() -> AppResult<HashMap<String, PHash>> {
+    let mut result = HashMap::new();
+
+    let file = cache_file();
+    if !file.exists() {
+        return Ok(result);
+    }
+
+    for line in BufReader::new(File::open(file)?).lines() {
+        let line = line?;
+        let mut columns = line.rsplitn(2, '\t');
+        if let (Some(hash), Some(cache_key)) = (columns.next(), columns.next()) {
+            if let Ok(hash) = PHash::from_str_radix(hash, 16) {
+                result.insert(o!(cache_key), hash);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+
+
+#[cfg(test)]#[test]
+fn test_dhash() {
+    let gray = |f: &dyn Fn(i32, i32) -> u8| -> Vec<u8> {
+        let mut result = vec![];
+        for y in 0 .. HASH_HEIGHT {
+            for x in 0 .. HASH_WIDTH {
+                let v = f(x, y);
+                result.extend_from_slice(&[v, v, v]);
+            }
+        }
+        result
+    };
+    let rowstride = HASH_WIDTH as usize * 3;
+
+    assert_eq!(dhash_from_pixels(&gray(&|_, _| 128), rowstride, 3), 0);
+    assert_eq!(dhash_from_pixels(&gray(&|x, _| (x * 20) as u8), rowstride, 3), PHash::max_value());
+    assert_eq!(
+        dhash_from_pixels(&gray(&|x, y| if y % 2 == 0 { (x * 20) as u8 } else { (200 - x * 20) as u8 }), rowstride, 3),
+        0xff00_ff00_ff00_ff00);
+}
+
+#[cfg(test)]#[test]
+fn test_distance() {
+    assert_eq!(distance(0, 0), 0);
+    assert_eq!(distance(0b1011, 0b0001), 2);
+    assert_eq!(distance(0, PHash::max_value()), 64);
+}
+
+#[cfg(test)]#[test]
+fn test_groups() {
+    use crate::entry::EntryType;
+
+    let key = |name: &str| (EntryType::Image, o!(name), 0);
+    let table = PHashTable::default();
+    {
+        let mut table = table.table.lock().unwrap();
+        table.hashes.insert(key("a"), 0);
+        table.hashes.insert(key("b"), 0b111);
+        table.hashes.insert(key("c"), 0xffff);
+        table.hashes.insert(key("d"), 0b1);
+    }
+
+    let keys = vec![key("a"), key("b"), key("c"), key("x"), key("d")];
+    assert_eq!(
+        table.groups(&keys, 3),
+        vec![vec![key("a"), key("b"), key("d")], vec![key("c")], vec![key("x")]]);
+    assert_eq!(
+        table.groups(&keys, 0),
+        vec![vec![key("a")], vec![key("b")], vec![key("c")], vec![key("x")], vec![key("d")]]);
+}
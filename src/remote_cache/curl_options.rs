@@ -1,9 +1,18 @@
 
 
 use std::default::Default;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Duration;
 
-use curl::easy::Easy as EasyCurl;
+use curl::easy::{Auth, Easy as EasyCurl, List};
+use globset::{Glob, GlobMatcher};
+use url::Url;
+
+use crate::errors::{AppError, AppResult, AppResultU};
+use crate::shellexpand_wrapper as sh;
+use crate::util::time::duration_to_seconds;
 
 
 
@@ -25,17 +34,166 @@ macro_rules! set {
 pub struct CurlOptions {
     pub connect_timeout: Option<u64>,
     pub follow_location: bool,
+    pub hosts: Vec<HostOptions>,
     pub low_speed_limit: Option<u32>,
     pub low_speed_time: Option<u64>,
+    pub retries: usize,
+    pub retry_delay: Duration,
     pub timeout: Option<u64>,
 }
 
+/** Options for the hosts that match `pattern` **/
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostOptions {
+    pub pattern: HostPattern,
+    pub auth: Option<HostAuth>,
+    pub cookie_jar: Option<PathBuf>,
+    pub headers: Vec<(String, String)>,
+    pub interval: Option<Duration>,
+    pub max_connections: Option<usize>,
+    pub priority: Option<i32>,
+    pub user_agent: Option<String>,
+}
+
+/** Glob for host names, compiled once **/
+#[derive(Clone, Debug)]
+pub struct HostPattern {
+    pub source: String,
+    matcher: GlobMatcher,
+}
+
+/** Limits for the download pool **/
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HostLimits {
+    pub interval: Option<Duration>, /* Minimum interval between requests */
+    pub max_connections: Option<usize>,
+    pub priority: i32, /* Higher is earlier */
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum HostAuth {
+    Basic(String, String), /* user, password */
+    Bearer(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HostOptionName {
+    BasicAuth,
+    BearerToken,
+    CookieJar,
+    Header,
+    Interval,
+    MaxConnections,
+    Priority,
+    UserAgent,
+}
+
 
 impl CurlOptions {
-    pub fn generate(&self) -> EasyCurl {
+    pub fn generate(&self, url: &str) -> AppResult<EasyCurl> {
         let mut curl = EasyCurl::new();
         self.apply(&mut curl);
-        curl
+        self.apply_host_options(&mut curl, url)?;
+        Ok(curl)
+    }
+
+    pub fn set_host_option(&mut self, pattern: &str, name: HostOptionName, value: &str) -> AppResultU {
+        let index = if let Some(index) = self.hosts.iter().position(|it| it.pattern.source == pattern) {
+            index
+        } else {
+            self.hosts.push(HostOptions::new(HostPattern::new(pattern)?));
+            self.hosts.len() - 1
+        };
+
+        let result = self.hosts[index].set(name, value);
+        self.hosts.retain(|it| !it.is_empty());
+        result
+    }
+
+    pub fn unset_host_option(&mut self, pattern: &str, name: HostOptionName) {
+        for it in self.hosts.iter_mut().filter(|it| it.pattern.source == pattern) {
+            it.unset(name);
+        }
+        self.hosts.retain(|it| !it.is_empty());
+    }
+
+    /** Returns the host name of `url` and its limits. Later options take precedence **/
+    pub fn limits(&self, url: &str) -> (String, HostLimits) {
+        let mut result = HostLimits::default();
+
+        let host = Url::parse(url).ok().and_then(|it| it.host_str().map(|it| o!(it))).unwrap_or_default();
+
+        for it in self.hosts.iter().filter(|it| it.matches(&host)) {
+            if it.interval.is_some() {
+                result.interval = it.interval;
+            }
+            if it.max_connections.is_some() {
+                result.max_connections = it.max_connections;
+            }
+            if let Some(priority) = it.priority {
+                result.priority = priority;
+            }
+        }
+
+        (host, result)
+    }
+
+    /** Later options take precedence. Headers are accumulated **/
+    fn apply_host_options(&self, curl: &mut EasyCurl, url: &str) -> AppResultU {
+        if self.hosts.is_empty() {
+            return Ok(());
+        }
+
+        let url = Url::parse(url)?;
+        if_let_some!(host = url.host_str(), Ok(()));
+
+        let mut headers: Vec<(String, String)> = vec![];
+        let mut auth = None;
+
+        for it in &self.hosts {
+            if !it.matches(host) {
+                continue;
+            }
+            for (name, value) in &it.headers {
+                headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+                headers.push((name.clone(), value.clone()));
+            }
+            if let Some(ref cookie_jar) = it.cookie_jar {
+                curl.cookie_file(cookie_jar)?;
+                curl.cookie_jar(cookie_jar)?;
+            }
+            if let Some(ref user_agent) = it.user_agent {
+                curl.useragent(user_agent)?;
+            }
+            if it.auth.is_some() {
+                auth = it.auth.clone();
+            }
+        }
+
+        match auth {
+            Some(HostAuth::Basic(ref user, ref password)) => {
+                let mut basic = Auth::new();
+                basic.basic(true);
+                curl.http_auth(&basic)?;
+                curl.username(user)?;
+                curl.password(password)?;
+            },
+            Some(HostAuth::Bearer(ref token)) => {
+                headers.retain(|(n, _)| !n.eq_ignore_ascii_case("Authorization"));
+                headers.push((o!("Authorization"), format!("Bearer {}", token)));
+            },
+            None => (),
+        }
+
+        if !headers.is_empty() {
+            let mut list = List::new();
+            for (name, value) in &headers {
+                list.append(&format!("{}: {}", name, value))?;
+            }
+            curl.http_headers(list)?;
+        }
+
+        Ok(())
     }
 
     fn apply(&self, curl: &mut EasyCurl) {
@@ -52,8 +210,11 @@ impl Default for CurlOptions {
         CurlOptions {
             connect_timeout: Some(10),
             follow_location: true,
+            hosts: vec![],
             low_speed_limit: Some(1024),
             low_speed_time: Some(10),
+            retries: 3,
+            retry_delay: Duration::from_secs(1),
             timeout: None,
         }
     }
@@ -62,3 +223,215 @@ impl Default for CurlOptions {
 fn sec(v: u64) -> Duration {
     Duration::from_secs(v)
 }
+
+fn is_secret_header(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["authorization", "proxy-authorization", "cookie"].contains(&name.as_str()) ||
+        ["token", "secret", "password", "api-key", "apikey"].iter().any(|it| name.contains(it))
+}
+
+
+impl HostOptions {
+    fn new(pattern: HostPattern) -> Self {
+        HostOptions {
+            pattern,
+            auth: None,
+            cookie_jar: None,
+            headers: vec![],
+            interval: None,
+            max_connections: None,
+            priority: None,
+            user_agent: None,
+        }
+    }
+
+    /** Values to save in sessions. The credentials are excluded **/
+    pub fn values(&self) -> Vec<(HostOptionName, String)> {
+        let mut result = vec![];
+        for (name, value) in self.headers.iter().filter(|(name, _)| !is_secret_header(name)) {
+            result.push((HostOptionName::Header, format!("{}: {}", name, value)));
+        }
+        if let Some(ref cookie_jar) = self.cookie_jar {
+            result.push((HostOptionName::CookieJar, s!(cookie_jar.display())));
+        }
+        if let Some(ref user_agent) = self.user_agent {
+            result.push((HostOptionName::UserAgent, user_agent.clone()));
+        }
+        if let Some(ref interval) = self.interval {
+            result.push((HostOptionName::Interval, s!(duration_to_seconds(interval))));
+        }
+        if let Some(max_connections) = self.max_connections {
+            result.push((HostOptionName::MaxConnections, s!(max_connections)));
+        }
+        if let Some(priority) = self.priority {
+            result.push((HostOptionName::Priority, s!(priority)));
+        }
+        result
+    }
+
+    fn is_empty(&self) -> bool {
+        self.auth.is_none() && self.cookie_jar.is_none() && self.headers.is_empty() && self.user_agent.is_none() &&
+            self.interval.is_none() && self.max_connections.is_none() && self.priority.is_none()
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        self.pattern.matcher.is_match(host)
+    }
+
+    fn set(&mut self, name: HostOptionName, value: &str) -> AppResultU {
+        use self::HostOptionName::*;
+
+        match name {
+            BasicAuth => {
+                let mut pair = value.splitn(2, ':');
+                let user = pair.next().unwrap_or("");
+                let password = pair.next().ok_or_else(|| AppError::InvalidValueWithReason(o!(value), o!("USER:PASSWORD")))?;
+                self.auth = Some(HostAuth::Basic(o!(user), o!(password)));
+            },
+            BearerToken =>
+                self.auth = Some(HostAuth::Bearer(o!(value))),
+            CookieJar =>
+                self.cookie_jar = Some(sh::expand_to_pathbuf(value)),
+            Header => {
+                let mut pair = value.splitn(2, ':');
+                let name = pair.next().map(str::trim).unwrap_or("");
+                let value = pair.next().map(str::trim).ok_or_else(|| AppError::InvalidValueWithReason(o!(value), o!("NAME: VALUE")))?;
+                self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+                self.headers.push((o!(name), o!(value)));
+            },
+            Interval => {
+                let seconds: f64 = value.parse().map_err(|it| AppError::InvalidValueWithReason(o!(value), s!(it)))?;
+                self.interval = Some(Duration::from_millis((seconds * 1000.0) as u64));
+            },
+            MaxConnections => {
+                let max_connections: usize = value.parse().map_err(|it| AppError::InvalidValueWithReason(o!(value), s!(it)))?;
+                if max_connections == 0 {
+                    return Err(AppError::InvalidValueWithReason(o!(value), o!("Must be greater than 0")));
+                }
+                self.max_connections = Some(max_connections);
+            },
+            Priority =>
+                self.priority = Some(value.parse().map_err(|it| AppError::InvalidValueWithReason(o!(value), s!(it)))?),
+            UserAgent =>
+                self.user_agent = Some(o!(value)),
+        }
+
+        Ok(())
+    }
+
+    fn unset(&mut self, name: HostOptionName) {
+        use self::HostOptionName::*;
+
+        match name {
+            BasicAuth | BearerToken => self.auth = None,
+            CookieJar => self.cookie_jar = None,
+            Header => self.headers.clear(),
+            Interval => self.interval = None,
+            MaxConnections => self.max_connections = None,
+            Priority => self.priority = None,
+            UserAgent => self.user_agent = None,
+        }
+    }
+}
+
+
+impl HostPattern {
+    pub fn new(source: &str) -> AppResult<Self> {
+        let matcher = Glob::new(source).map_err(|it| AppError::InvalidValueWithReason(o!(source), s!(it)))?.compile_matcher();
+        Ok(HostPattern { source: o!(source), matcher })
+    }
+}
+
+impl PartialEq for HostPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+
+impl FromStr for HostOptionName {
+    type Err = AppError;
+
+    fn from_str(src: &str) -> AppResult<Self> {
+        use self::HostOptionName::*;
+
+        let result = match src {
+            "basic-auth" | "basic" => BasicAuth,
+            "bearer-token" | "bearer" => BearerToken,
+            "cookie-jar" | "cookie" => CookieJar,
+            "header" => Header,
+            "interval" => Interval,
+            "max-connections" | "connections" => MaxConnections,
+            "priority" => Priority,
+            "user-agent" | "ua" => UserAgent,
+            _ => return Err(AppError::InvalidValue(o!(src))),
+        };
+        Ok(result)
+    }
+}
+
+impl fmt::Display for HostOptionName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::HostOptionName::*;
+
+        let result = match *self {
+            BasicAuth => "basic-auth",
+            BearerToken => "bearer-token",
+            CookieJar => "cookie-jar",
+            Header => "header",
+            Interval => "interval",
+            MaxConnections => "max-connections",
+            Priority => "priority",
+            UserAgent => "user-agent",
+        };
+        write!(f, "{}", result)
+    }
+}
+
+
+#[cfg(test)]#[test]
+fn test_host_options() {
+    use self::HostOptionName::*;
+
+    let mut options = CurlOptions::default();
+    options.set_host_option("*.example.com", Header, "Referer: https://example.com/").unwrap();
+    options.set_host_option("*.example.com", Header, "referer:https://www.example.com/").unwrap();
+    options.set_host_option("*.example.com", BasicAuth, "user:pass:word").unwrap();
+    options.set_host_option("*.example.com", Header, "X-Api-Key: xxxxxxxx").unwrap();
+    assert!(options.set_host_option("*.example.com", Header, "Referer").is_err());
+    assert!(options.set_host_option("[", Header, "Referer: x").is_err());
+
+    assert_eq!(options.hosts.len(), 1);
+    assert!(options.hosts[0].matches("www.example.com"));
+    assert!(!options.hosts[0].matches("example.org"));
+    assert_eq!(options.hosts[0].auth, Some(HostAuth::Basic(o!("user"), o!("pass:word"))));
+    // Credentials are not saved in sessions
+    assert_eq!(
+        options.hosts[0].values(),
+        vec![(Header, o!("referer: https://www.example.com/"))]);
+
+    options.unset_host_option("*.example.com", Header);
+    assert!(options.hosts[0].values().is_empty());
+    options.unset_host_option("*.example.com", BasicAuth);
+    assert!(options.hosts.is_empty());
+}
+
+#[cfg(test)]#[test]
+fn test_host_limits() {
+    use self::HostOptionName::*;
+
+    let mut options = CurlOptions::default();
+    options.set_host_option("*", MaxConnections, "4").unwrap();
+    options.set_host_option("*.example.com", MaxConnections, "1").unwrap();
+    options.set_host_option("*.example.com", Interval, "0.5").unwrap();
+    options.set_host_option("img.example.com", Priority, "10").unwrap();
+    assert!(options.set_host_option("*", MaxConnections, "0").is_err());
+
+    let (host, limits) = options.limits("http://img.example.com/a.png");
+    assert_eq!(host, "img.example.com");
+    assert_eq!(limits, HostLimits { interval: Some(Duration::from_millis(500)), max_connections: Some(1), priority: 10 });
+
+    let (host, limits) = options.limits("http://example.org/a.png");
+    assert_eq!(host, "example.org");
+    assert_eq!(limits, HostLimits { interval: None, max_connections: Some(4), priority: 0 });
+}
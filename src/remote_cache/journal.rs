@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+
+use lazy_static::lazy_static;
+use uuid::Uuid;
+
+use crate::app_path::{self, PathList};
+use crate::errors::AppResultU;
+use crate::operation::Operation;
+use crate::script;
+use crate::session::write_requests;
+
+use super::State;
+
+
+
+const EXTENSION: &str = "chry";
+
+lazy_static! {
+    /** The journals of the dead instances being resumed. The locks are kept until they are discarded **/
+    static ref ADOPTED: Mutex<HashMap<PathBuf, File>> = Mutex::new(HashMap::new());
+}
+
+/** Keeps the pending requests in the file of each instance, to resume them at the next start **/
+pub struct Journal {
+    last: String,
+    lock: Option<File>,
+    path: PathBuf,
+}
+
+
+impl Journal {
+    pub fn new() -> Self {
+        let path = app_path::cache_dir("remote_queue").join(format!("{}.{}", Uuid::new_v4(), EXTENSION));
+        Journal { last: o!(""), lock: None, path }
+    }
+
+    pub fn write(&mut self, state: &State) -> AppResultU {
+        let mut requests = state.requests();
+        requests.sort();
+
+        let mut out = o!("");
+        write_requests(&requests, &mut out);
+        if out == self.last {
+            return Ok(());
+        }
+
+        if out.is_empty() {
+            if self.path.exists() {
+                fs::remove_file(&self.path)?;
+            }
+            if let Some(lock) = self.lock.take() {
+                remove(&self.path, lock);
+            }
+        } else {
+            if self.lock.is_none() {
+                self.lock = lock(&self.path)?;
+            }
+            let mut temporary = self.path.clone();
+            temporary.set_extension("tmp");
+            fs::write(&temporary, &out)?;
+            fs::rename(&temporary, &self.path)?;
+        }
+
+        self.last = out;
+        Ok(())
+    }
+}
+
+
+/** Load the journals of the dead instances.
+    Each journal is discarded by `Operation::DiscardJournal` after its requests are journaled again **/
+pub fn resume(tx: &Sender<Operation>, path_list: &PathList) {
+    for (path, lock) in lock_orphans() {
+        puts_event!("remote/resume", "file" => p!(path));
+        script::load_from_file(tx, &path, path_list);
+        tx.send(Operation::DiscardJournal(path.clone())).unwrap();
+        ADOPTED.lock().unwrap().insert(path, lock);
+    }
+}
+
+/** Discard the journal resumed by `resume` **/
+pub fn discard_adopted(path: &Path) {
+    if let Some(lock) = ADOPTED.lock().unwrap().remove(path) {
+        remove(path, lock);
+    }
+}
+
+/** Discard the journals of the dead instances **/
+pub fn discard() {
+    for (path, lock) in lock_orphans() {
+        remove(&path, lock);
+    }
+}
+
+fn lock_orphans() -> Vec<(PathBuf, File)> {
+    let dir = app_path::cache_dir("remote_queue");
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(err) => {
+            puts_error!(err, "at" => "remote/find_journals", "file" => p!(dir));
+            return vec![];
+        }
+    };
+
+    let mut result = vec![];
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.extension() != Some(OsStr::new(EXTENSION)) {
+            continue;
+        }
+        match lock(&path) {
+            Ok(Some(lock)) => result.push((path, lock)),
+            Ok(None) => (), // Alive or being resumed
+            Err(err) => puts_error!(err, "at" => "remote/lock_journal", "file" => p!(path)),
+        }
+    }
+    result
+}
+
+/** Remove the journal and then its lock file, while holding the lock **/
+fn remove(path: &Path, lock: File) {
+    if let Err(err) = fs::remove_file(path) {
+        if err.kind() != io::ErrorKind::NotFound {
+            puts_error!(err, "at" => "remote/discard_journal", "file" => p!(path));
+        }
+    }
+    let _ = fs::remove_file(lock_path(path));
+    drop(lock);
+}
+
+/** Returns `None` if the other instance holds the lock **/
+fn lock(journal: &Path) -> io::Result<Option<File>> {
+    let file = OpenOptions::new().create(true).write(true).open(lock_path(journal))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(Some(file));
+    }
+    let err = io::Error::last_os_error();
+    if err.kind() == io::ErrorKind::WouldBlock {
+        Ok(None)
+    } else {
+        Err(err)
+    }
+}
+
+fn lock_path(journal: &Path) -> PathBuf {
+    journal.with_extension("lock")
+}
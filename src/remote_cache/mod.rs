@@ -1,14 +1,17 @@
 
 use std::cmp::Ordering;
-use std::collections::{BTreeSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::convert::TryFrom;
 use std::env;
 use std::ffi::OsStr;
-use std::fs::{self, File, create_dir_all};
-use std::io::{BufWriter, Write};
+use std::fmt;
+use std::fs::{self, OpenOptions, create_dir_all};
+use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Sender};
 use std::sync::{Arc, Mutex};
-use std::thread::spawn;
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
 
 use closet::clone_army;
 use curl::easy::Easy as EasyCurl;
@@ -26,53 +29,92 @@ use crate::operation::{Operation, QueuedOperation, Updated};
 use crate::session::StatusText;
 use crate::shorter::shorten_url;
 use crate::sorting_buffer::SortingBuffer;
+use crate::util::time::duration_to_seconds;
 
 pub mod curl_options;
+pub mod journal;
+pub mod progress;
+pub mod sweeper;
 
-use self::curl_options::CurlOptions;
+use self::curl_options::{CurlOptions, HostLimits};
+use self::journal::Journal;
+use self::progress::Transfers;
 
 
 
 type Tid = usize;
 
+const MAX_FAILED: usize = 1000;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
 pub struct RemoteCache {
     main_tx: Sender<Getter>,
     sorting_buffer: SortingBuffer<QueuedOperation>,
     pub state: Arc<Mutex<State>>,
     pub do_update_atime: bool,
+    pub offline: bool,
 }
 
 
 #[derive(Default)]
 pub struct State {
     curl_options: CurlOptions,
+    failed: Vec<Request>,
+    hosts: BTreeMap<String, HostState>,
     idles: Vec<Tid>,
     processing: BTreeSet<Request>,
     queued: VecDeque<Request>,
+    retrying: BTreeSet<Request>,
     threads: Vec<Sender<Request>>,
+    transfers: Arc<Mutex<Transfers>>,
+    waking: Option<Instant>, /* The earliest deadline of the scheduled wakes */
     ok: usize,
     fail: usize,
 }
 
+#[derive(Default)]
+struct HostState {
+    connections: usize,
+    last: Option<Instant>,
+    limits: HostLimits,
+}
+
 #[derive(Clone)]
 pub struct Request {
     pub entry_type: Option<EntryType>,
     pub meta: Option<Meta>,
+    pub referer: Option<String>,
     pub url: String,
     cache_filepath: PathBuf,
     force: bool,
+    host: String,
     show: bool,
     options: CurlOptions,
+    priority: i32,
+    retries: usize,
     ticket: usize,
 }
 
 
 #[derive(Clone)]
 enum Getter {
-    Queue(String, PathBuf, Option<Meta>, bool, bool, Option<EntryType>), /* url, filepath, meta, force, show, entry_type */
+    Queue(String, PathBuf, Option<Meta>, bool, bool, Option<EntryType>, Option<String>), /* url, filepath, meta, force, show, entry_type, referer */
+    Cancel(String), /* URL or ticket */
+    DiscardJournal(PathBuf),
     Done(usize, Request),
-    Fail(usize, String, Request),
+    Fail(usize, Failure, Request),
+    Progress,
+    Retry(Request),
+    RetryFailed,
     SetIgnoreFailures(bool),
+    Wake,
+}
+
+#[derive(Clone)]
+struct Failure {
+    cancelled: bool,
+    reason: String,
+    transient: bool, /* Timeouts, 5xx, connection resets... */
 }
 
 // Status Paramter
@@ -80,8 +122,10 @@ enum SP {
     Initial,
     Process(String),
     Queue(String),
+    Cancel(usize, String), /* ticket, url */
     Complete(usize),
     Fail(usize, String, String),
+    Retry(usize, String, Duration), /* retries, url, delay */
 }
 
 
@@ -89,10 +133,11 @@ impl RemoteCache {
     pub fn new(max_threads: u8, app_tx: Sender<Operation>, sorting_buffer: SortingBuffer<QueuedOperation>) -> Self {
         let state = Arc::new(Mutex::new(State::default()));
         let main_tx = main(max_threads, app_tx, sorting_buffer.clone(), state.clone());
-        RemoteCache { main_tx, sorting_buffer, do_update_atime: false, state }
+        RemoteCache { main_tx, sorting_buffer, do_update_atime: false, offline: false, state }
     }
 
-    pub fn fetch(&mut self, url: String, meta: Option<Meta>, force: bool, show: bool, entry_type: Option<EntryType>) -> Vec<QueuedOperation> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn fetch(&mut self, url: String, meta: Option<Meta>, force: bool, show: bool, entry_type: Option<EntryType>, referer: Option<String>) -> Vec<QueuedOperation> {
         if_let_ok!(filepath = generate_temporary_filename(&url), |err| {
             puts_error!(err, "at" => "generate_temporary_filename");
             vec![]
@@ -108,8 +153,13 @@ impl RemoteCache {
                 make_queued_operation(filepath, url, meta, force, show, entry_type));
             self.update_sorting_buffer_len();
             result
+        } else if self.offline {
+            let result = self.sorting_buffer.push_with_reserve(
+                QueuedOperation::PushMessage(format!("Offline: {}", url), meta, show, Some(url), entry_type));
+            self.update_sorting_buffer_len();
+            result
         } else {
-            self.main_tx.send(Getter::Queue(url, filepath, meta, force, show, entry_type)).unwrap();
+            self.main_tx.send(Getter::Queue(url, filepath, meta, force, show, entry_type, referer)).unwrap();
             vec![]
         }
     }
@@ -126,6 +176,19 @@ impl RemoteCache {
     pub fn set_ignore_failures(&self, value: bool) {
         self.main_tx.send(Getter::SetIgnoreFailures(value)).unwrap();
     }
+
+    pub fn cancel(&self, target: String) {
+        self.main_tx.send(Getter::Cancel(target)).unwrap();
+    }
+
+    pub fn retry_failed(&self) {
+        self.main_tx.send(Getter::RetryFailed).unwrap();
+    }
+
+    /** Discard the resumed journal, after the requests in it are queued **/
+    pub fn discard_journal(&self, path: PathBuf) {
+        self.main_tx.send(Getter::DiscardJournal(path)).unwrap();
+    }
 }
 
 impl StatusText for RemoteCache {
@@ -140,6 +203,36 @@ impl StatusText for RemoteCache {
                 sprintln!(out, "unstable_buffer={:?}", item);
             }
         });
+
+        let state = self.state.lock().unwrap();
+        {
+            let transfers = state.transfers.lock().unwrap();
+            for (ticket, progress) in &transfers.progresses {
+                sprintln!(
+                    out,
+                    "remote_download={} url={} received={} total={} speed={}",
+                    ticket,
+                    progress.url,
+                    progress.received as u64,
+                    progress.total as u64,
+                    progress.speed() as u64);
+            }
+        }
+        for request in &state.queued {
+            sprintln!(out, "remote_queued={} url={}", request.ticket, request.url);
+        }
+        for (name, host) in &state.hosts {
+            let limits = &host.limits;
+            sprintln!(
+                out,
+                "remote_host={} connections={} max_connections={} interval={} priority={} queued={}",
+                name,
+                host.connections,
+                limits.max_connections.map(|it| s!(it)).unwrap_or_default(),
+                limits.interval.map(|it| s!(duration_to_seconds(&it))).unwrap_or_default(),
+                limits.priority,
+                state.queued.iter().filter(|it| it.host == *name).count());
+        }
     }
 }
 
@@ -153,83 +246,200 @@ fn main(max_threads: u8, app_tx: Sender<Operation>, mut buffer: SortingBuffer<Qu
         {
             let mut state = state.lock().unwrap();
             for thread_id in 0..max_threads as usize {
-                state.threads.push(processor(thread_id, main_tx.clone()));
+                let transfers = state.transfers.clone();
+                state.threads.push(processor(thread_id, main_tx.clone(), transfers));
                 state.idles.push(thread_id);
             }
             log_status(&app_tx, &SP::Initial, &state, buffer.len());
         }
 
         let mut ignore_failures = true;
+        let mut journal = Journal::new();
 
         while let Ok(it) = main_rx.recv() {
             match it {
                 SetIgnoreFailures(value) =>
                     ignore_failures = value,
-                Queue(url, cache_filepath, meta, force, show, entry_type) => {
+                Queue(url, cache_filepath, meta, force, show, entry_type, referer) => {
                     let mut state = state.lock().unwrap();
                     let ticket = buffer.reserve();
+                    let request = Request { ticket, url, cache_filepath, meta, force, host: o!(""), show, entry_type, options: state.curl_options.clone(), priority: 0, referer, retries: 0 };
+                    dispatch(&app_tx, &main_tx, request, &mut state, buffer.len());
+                }
+                Retry(request) => {
+                    let mut state = state.lock().unwrap();
+                    if state.retrying.remove(&request) { // Not cancelled
+                        dispatch(&app_tx, &main_tx, request, &mut state, buffer.len());
+                    }
+                }
+                Cancel(target) => {
+                    let mut state = state.lock().unwrap();
+                    let is_target = |it: &Request| it.url == target || s!(it.ticket) == target;
 
-                    let request = Request { ticket, url: url.clone(), cache_filepath, meta, force, show, entry_type, options: state.curl_options.clone() };
+                    let mut cancelled: Vec<Request> = state.queued.iter().filter(|it| is_target(*it)).cloned().collect();
+                    cancelled.extend(state.retrying.iter().filter(|it| is_target(*it)).cloned());
+                    state.queued.retain(|it| !is_target(it));
+                    for request in &cancelled {
+                        state.retrying.remove(request);
+                        buffer.skip(request.ticket);
+                        log_status(&app_tx, &SP::Cancel(request.ticket, request.url.clone()), &state, buffer.len());
+                    }
+                    if !cancelled.is_empty() {
+                        app_tx.send(Operation::Pull).unwrap();
+                        try_next(&app_tx, &main_tx, &mut state, buffer.len());
+                    }
 
-                    if let Some(worker) = state.idles.pop() {
-                        state.processing.insert(request.clone());
-                        state.threads[worker].send(request).unwrap();
-                        log_status(&app_tx, &SP::Process(url), &state, buffer.len());
-                    } else {
-                        state.queued.push_back(request);
-                        log_status(&app_tx, &SP::Queue(url), &state, buffer.len());
+                    // Processing requests are aborted in the progress callback
+                    let mut transfers = state.transfers.lock().unwrap();
+                    for request in state.processing.iter().filter(|it| is_target(*it)) {
+                        transfers.cancel(request.ticket);
+                    }
+                }
+                Progress => {
+                    let state = state.lock().unwrap();
+                    let transfers = state.transfers.lock().unwrap();
+                    for (ticket, progress) in &transfers.progresses {
+                        puts_event!(
+                            "remote/progress",
+                            "ticket" => s!(ticket),
+                            "url" => o!(&progress.url),
+                            "received" => s!(progress.received as u64),
+                            "total" => s!(progress.total as u64),
+                            "speed" => s!(progress.speed() as u64));
+                    }
+                    env::set_var(env_name("remote_progress"), transfers.summary());
+                    app_tx.send(Operation::Update(Updated { remote: true, ..Default::default() })).unwrap();
+                }
+                RetryFailed => {
+                    let mut state = state.lock().unwrap();
+                    let failed: Vec<Request> = state.failed.drain(..).collect();
+                    state.fail = state.fail.saturating_sub(failed.len());
+                    for mut request in failed {
+                        request.ticket = buffer.reserve();
+                        request.retries = 0;
+                        request.options = state.curl_options.clone();
+                        dispatch(&app_tx, &main_tx, request, &mut state, buffer.len());
+                    }
+                }
+                DiscardJournal(path) => {
+                    // The resumed requests have been queued by now
+                    match journal.write(&state.lock().unwrap()) {
+                        Ok(_) => self::journal::discard_adopted(&path),
+                        Err(err) => puts_error!(err, "at" => "remote/journal"),
                     }
                 }
+                Wake => {
+                    let mut state = state.lock().unwrap();
+                    if state.waking.map_or(false, |it| it <= Instant::now()) {
+                        state.waking = None;
+                    }
+                    flush(&app_tx, &main_tx, &mut state, buffer.len());
+                }
                 Done(thread_id, request) => {
                     let mut state = state.lock().unwrap();
                     state.ok += 1;
-                    state.processing.remove(&request);
+                    release(thread_id, &request, &mut state);
                     buffer.push(
                         request.ticket,
                         make_queued_operation(request.cache_filepath, request.url, request.meta, request.force, request.show, request.entry_type));
                     app_tx.send(Operation::Pull).unwrap();
-                    try_next(&app_tx, thread_id, &mut state);
+                    try_next(&app_tx, &main_tx, &mut state, buffer.len());
                     log_status(&app_tx, &SP::Complete(thread_id), &state, buffer.len());
                 }
-                Fail(thread_id, err, request) => {
+                Fail(thread_id, failure, mut request) => {
                     let mut state = state.lock().unwrap();
-                    state.fail += 1;
-                    state.processing.remove(&request);
-                    if ignore_failures {
+                    release(thread_id, &request, &mut state);
+
+                    if failure.cancelled {
                         buffer.skip(request.ticket);
+                        app_tx.send(Operation::Pull).unwrap();
+                        try_next(&app_tx, &main_tx, &mut state, buffer.len());
+                        log_status(&app_tx, &SP::Cancel(request.ticket, request.url), &state, buffer.len());
+                    } else if failure.transient && request.retries < request.options.retries {
+                        let delay = retry_delay(request.options.retry_delay, request.retries);
+                        request.retries += 1;
+                        state.retrying.insert(request.clone());
+                        log_status(&app_tx, &SP::Retry(request.retries, request.url.clone(), delay), &state, buffer.len());
+                        spawn(clone_army!([main_tx] move || {
+                            sleep(delay);
+                            main_tx.send(Retry(request)).unwrap();
+                        }));
+                        try_next(&app_tx, &main_tx, &mut state, buffer.len());
                     } else {
-                        let url = Url::parse(&request.url).expect("Invalid URL");
-                        buffer.push(
-                            request.ticket,
-                            QueuedOperation::PushMessage(
-                                format!("{} for {}", err, shorten_url(&url, 40)),
-                                request.meta,
-                                request.show));
+                        state.fail += 1;
+                        state.failed.retain(|it| it.url != request.url);
+                        if MAX_FAILED <= state.failed.len() {
+                            state.failed.remove(0);
+                        }
+                        state.failed.push(request.clone());
+                        if ignore_failures {
+                            buffer.skip(request.ticket);
+                        } else {
+                            let url = Url::parse(&request.url).expect("Invalid URL");
+                            buffer.push(
+                                request.ticket,
+                                QueuedOperation::PushMessage(
+                                    format!("{} for {}", failure.reason, shorten_url(&url, 40)),
+                                    request.meta,
+                                    request.show,
+                                    None,
+                                    None));
+                        }
+                        app_tx.send(Operation::Pull).unwrap();
+                        try_next(&app_tx, &main_tx, &mut state, buffer.len());
+                        log_status(&app_tx, &SP::Fail(thread_id, failure.reason, request.url), &state, buffer.len());
                     }
-                    app_tx.send(Operation::Pull).unwrap();
-                    try_next(&app_tx, thread_id, &mut state);
-                    log_status(&app_tx, &SP::Fail(thread_id, err, request.url), &state, buffer.len());
                 }
             }
+
+            if let Err(err) = journal.write(&state.lock().unwrap()) {
+                puts_error!(err, "at" => "remote/journal");
+            }
         }
     }));
 
     main_tx
 }
 
-fn processor(thread_id: usize, main_tx: Sender<Getter>) -> Sender<Request> {
+fn retry_delay(base: Duration, retries: usize) -> Duration {
+    let delay = u32::try_from(retries).ok()
+        .and_then(|it| 2u32.checked_pow(it))
+        .and_then(|it| base.checked_mul(it));
+    delay.map_or(MAX_RETRY_DELAY, |it| min!(it, MAX_RETRY_DELAY))
+}
+
+fn processor(thread_id: usize, main_tx: Sender<Getter>, transfers: Arc<Mutex<Transfers>>) -> Sender<Request> {
     let (getter_tx, getter_rx) = channel();
 
     spawn(move || {
         while let Ok(request) = getter_rx.recv() {
             let request: Request = request;
-            let mut curl =  request.options.generate();
 
             puts!("event" => "remote/get", "thread_id" => s!(thread_id), "url" => o!(&request.url));
 
-            match http_save(&mut curl, &request.url, &request.cache_filepath) {
+            let ticket = request.ticket;
+            let on_progress = |offset: f64, received: f64, total: f64| {
+                let mut transfers = transfers.lock().unwrap();
+                if !transfers.update(ticket, offset, received, total) {
+                    return false;
+                }
+                if transfers.should_report() {
+                    main_tx.send(Getter::Progress).unwrap();
+                }
+                true
+            };
+
+            let result = request.options.generate(&request.url).and_then(|mut curl| {
+                if let Some(ref referer) = request.referer {
+                    curl.referer(referer)?;
+                }
+                Ok(curl)
+            }).map_err(Failure::permanent).and_then(|mut curl| {
+                http_save(&mut curl, &request.url, &request.cache_filepath, on_progress)
+            });
+            match result {
                 Ok(_) => main_tx.send(Getter::Done(thread_id, request)).unwrap(),
-                Err(err) => main_tx.send(Getter::Fail(thread_id, s!(err), request)).unwrap(),
+                Err(failure) => main_tx.send(Getter::Fail(thread_id, failure, request)).unwrap(),
             }
         }
     });
@@ -237,27 +447,85 @@ fn processor(thread_id: usize, main_tx: Sender<Getter>) -> Sender<Request> {
     getter_tx
 }
 
-fn http_save<T: AsRef<Path>>(curl: &mut EasyCurl, url: &str, cache_filepath: &T) -> AppResultU {
+/** Download into `*.part` file, and resume it with HTTP Range at the next time **/
+fn http_save<T: AsRef<Path>, F>(curl: &mut EasyCurl, url: &str, cache_filepath: &T, mut on_progress: F) -> Result<(), Failure>
+where F: FnMut(f64, f64, f64) -> bool {
+    let part = part_filepath(cache_filepath);
+    let offset = part.metadata().map(|it| it.len()).unwrap_or(0);
+    if 0 < offset {
+        curl.resume_from(offset)?;
+    }
+
     let mut buf = vec![];
-    curl_get(curl, url, &mut buf)?;
-    File::create(cache_filepath).and_then(|file| {
+    let result = curl_get(curl, url, &mut buf, |received, total| on_progress(offset as f64, received, total));
+    if result.as_ref().err().map_or(false, curl::Error::is_aborted_by_callback) { // Cancelled
+        let _ = fs::remove_file(&part);
+        return result.map_err(Failure::from);
+    }
+    let code = curl.response_code().unwrap_or(0);
+
+    if !buf.is_empty() && code < 300 {
+        if let Some(dir) = part.parent() {
+            create_dir_all(dir)?; // The sweeper may remove the empty directory
+        }
+        let file = OpenOptions::new().create(true).write(true).append(code == 206).truncate(code != 206).open(&part)?;
         let mut writer = BufWriter::new(file);
-        writer.write_all(buf.as_slice())
-    })?;
+        writer.write_all(buf.as_slice())?;
+    }
+
+    result?;
+
+    if 400 <= code {
+        let _ = fs::remove_file(&part);
+        return Err(Failure {
+            cancelled: false,
+            reason: format!("HTTP {}", code),
+            transient: 500 <= code || code == 408 || code == 416 || code == 429, // 416: Broken `*.part`. Retry from scratch
+        });
+    }
+
+    fs::rename(&part, cache_filepath)?;
+    if let Err(err) = sweeper::write_source(cache_filepath, url) {
+        puts_error!(err, "at" => "remote/write_source", "for" => o!(url));
+    }
     Ok(())
 }
 
-fn curl_get(curl: &mut EasyCurl, url: &str, buf: &mut Vec<u8>) -> AppResultU {
+fn curl_get<F>(curl: &mut EasyCurl, url: &str, buf: &mut Vec<u8>, mut on_progress: F) -> Result<(), curl::Error>
+where F: FnMut(f64, f64) -> bool {
     curl.url(url)?;
+    curl.progress(true)?;
     let mut transfer = curl.transfer();
     transfer.write_function(|data| {
         buf.extend_from_slice(data);
         Ok(data.len())
     })?;
+    transfer.progress_function(|total, received, _, _| on_progress(received, total))?;
     transfer.perform()?;
     Ok(())
 }
 
+/** Fetch a text resource (e.g. feed, HTML page) synchronously, without caching **/
+pub fn get_text(url: &str, options: &CurlOptions, referer: Option<&str>) -> AppResult<String> {
+    let mut curl = options.generate(url)?;
+    if let Some(referer) = referer {
+        curl.referer(referer)?;
+    }
+    let mut buf = vec![];
+    curl_get(&mut curl, url, &mut buf, |_, _| true)?;
+    let code = curl.response_code()?;
+    if url.starts_with("http") && !(200..300).contains(&code) {
+        return Err(AppError::Standard(format!("HTTP {} for {}", code, url)));
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+fn part_filepath<T: AsRef<Path>>(cache_filepath: &T) -> PathBuf {
+    let mut result = cache_filepath.as_ref().as_os_str().to_owned();
+    result.push(".part");
+    PathBuf::from(result)
+}
+
 fn fix_path_segment(s: &str, last: bool) -> String {
     if s.len() > 32 {
         if last {
@@ -305,19 +573,125 @@ fn make_queued_operation(file: PathBuf, url: String, meta: Option<Meta>, force:
             QueuedOperation::PushArchive(file, meta, force, show, Some(url)),
         EntryType::PDF =>
             QueuedOperation::PushPdf(file, meta, force, show, Some(url)),
+        EntryType::Video =>
+            QueuedOperation::PushVideo(file, meta, force, show, Some(url)),
         _ =>
             not_implemented!(),
     }
 }
 
-fn try_next(app_tx: &Sender<Operation>, thread_id: Tid, state: &mut State) {
-    if let Some(next) = state.queued.pop_front() {
-        state.threads[thread_id].send(next).unwrap();
-    } else {
-        state.idles.push(thread_id);
+fn dispatch(app_tx: &Sender<Operation>, main_tx: &Sender<Getter>, mut request: Request, state: &mut State, buffers: usize) {
+    let (host, limits) = request.options.limits(&request.url);
+    let (url, ticket) = (request.url.clone(), request.ticket);
+
+    request.priority = limits.priority;
+    state.hosts.entry(host.clone()).or_default().limits = limits;
+    request.host = host;
+
+    // Behind the requests of the same or higher priority
+    let index = state.queued.iter().position(|it| it.priority < request.priority).unwrap_or_else(|| state.queued.len());
+    state.queued.insert(index, request);
+
+    flush(app_tx, main_tx, state, buffers);
+
+    if state.queued.iter().any(|it| it.ticket == ticket) {
+        log_status(app_tx, &SP::Queue(url), state, buffers);
+    }
+}
+
+/** Start the queued requests on the idle threads as far as the host limits allow **/
+fn flush(app_tx: &Sender<Operation>, main_tx: &Sender<Getter>, state: &mut State, buffers: usize) {
+    while !state.idles.is_empty() {
+        match pop_next(state) {
+            Ok(request) => {
+                let worker = state.idles.pop().unwrap();
+                if let Some(host) = state.hosts.get_mut(&request.host) {
+                    host.connections += 1;
+                    host.last = Some(Instant::now());
+                }
+                let url = request.url.clone();
+                state.transfers.lock().unwrap().start(request.ticket, url.clone());
+                state.processing.insert(request.clone());
+                state.threads[worker].send(request).unwrap();
+                log_status(app_tx, &SP::Process(url), state, buffers);
+            },
+            Err(Some(wait)) => {
+                let deadline = Instant::now() + wait;
+                if state.waking.map_or(true, |it| deadline < it) {
+                    state.waking = Some(deadline);
+                    spawn(clone_army!([main_tx] move || {
+                        sleep(wait);
+                        main_tx.send(Getter::Wake).unwrap();
+                    }));
+                }
+                break;
+            },
+            Err(None) =>
+                break,
+        }
     }
 
-    if state.idles.len() == state.threads.len() {
+    prune_hosts(state);
+}
+
+/** Forget the hosts which have no requests and no longer need to wait for the interval **/
+fn prune_hosts(state: &mut State) {
+    let now = Instant::now();
+    let State { ref mut hosts, ref queued, ref retrying, .. } = *state;
+
+    hosts.retain(|name, host| {
+        if 0 < host.connections || queued.iter().chain(retrying.iter()).any(|it| it.host == *name) {
+            return true;
+        }
+        if let (Some(interval), Some(last)) = (host.limits.interval, host.last) {
+            return now.duration_since(last) < interval;
+        }
+        false
+    });
+}
+
+/** Pop the first request whose host is not limited. Otherwise returns the time to wait for **/
+fn pop_next(state: &mut State) -> Result<Request, Option<Duration>> {
+    let now = Instant::now();
+    let mut wait: Option<Duration> = None;
+    let mut found = None;
+
+    for (index, request) in state.queued.iter().enumerate() {
+        if let Some(host) = state.hosts.get(&request.host) {
+            if let Some(max_connections) = host.limits.max_connections {
+                if max_connections <= host.connections {
+                    continue;
+                }
+            }
+            if let (Some(interval), Some(last)) = (host.limits.interval, host.last) {
+                let elapsed = now.duration_since(last);
+                if elapsed < interval {
+                    let rest = interval - elapsed;
+                    wait = Some(wait.map_or(rest, |it| min!(it, rest)));
+                    continue;
+                }
+            }
+        }
+        found = Some(index);
+        break;
+    }
+
+    found.and_then(|index| state.queued.remove(index)).ok_or(wait)
+}
+
+fn release(thread_id: Tid, request: &Request, state: &mut State) {
+    state.processing.remove(request);
+    state.transfers.lock().unwrap().finish(request.ticket);
+    if let Some(host) = state.hosts.get_mut(&request.host) {
+        host.connections = host.connections.saturating_sub(1);
+    }
+    state.idles.push(thread_id);
+}
+
+fn try_next(app_tx: &Sender<Operation>, main_tx: &Sender<Getter>, state: &mut State, buffers: usize) {
+    flush(app_tx, main_tx, state, buffers);
+
+    if state.idles.len() == state.threads.len() && state.queued.is_empty() && state.retrying.is_empty() {
         app_tx.send(Operation::Fire(mapping::Mapped::Event(EventName::DownloadAll))).unwrap();
     }
 }
@@ -334,10 +708,14 @@ fn log_status(app_tx: &Sender<Operation>, sp: &SP, state: &State, buffers: usize
             puts_event!("remote/process", "url" => url, "queue" => q, "buffer" => b, "idle" => w, "ok" => o, "fail" => f),
         Queue(ref url) =>
             puts_event!("remote/queue", "url" => url, "queue" => q, "buffer" => b, "idle" => w, "ok" => o, "fail" => f),
+        Cancel(ref ticket, ref url) =>
+            puts_event!("remote/cancel", "ticket" => s!(ticket), "url" => url, "queue" => q, "buffer" => b, "idle" => w, "ok" => o, "fail" => f),
         Complete(ref thread_id) =>
             puts_event!("remote/complete", "thread_id" => s!(thread_id), "queue" => q, "buffer" => b, "idle" => w, "ok" => o, "fail" => f),
         Fail(ref thread_id, ref error, ref url) =>
             puts_event!("remote/fail", "thread_id" => s!(thread_id), "reason" => error, "url" => url, "queue" => q, "buffer" => b, "idle" => w, "ok" => o, "fail" => f),
+        Retry(ref retries, ref url, ref delay) =>
+            puts_event!("remote/retry", "retries" => s!(retries), "delay" => s!(duration_to_seconds(delay)), "url" => url, "queue" => q, "buffer" => b, "idle" => w, "ok" => o, "fail" => f),
     }
     env::set_var(env_name("remote_queue"), q);
     env::set_var(env_name("remote_buffer"), b);
@@ -345,6 +723,7 @@ fn log_status(app_tx: &Sender<Operation>, sp: &SP, state: &State, buffers: usize
     env::set_var(env_name("remote_thread"), t);
     env::set_var(env_name("remote_ok"), o);
     env::set_var(env_name("remote_fail"), f);
+    env::set_var(env_name("remote_progress"), state.transfers.lock().unwrap().summary());
 
     app_tx.send(Operation::Update(Updated { remote: true, ..Default::default() })).unwrap();
 }
@@ -363,6 +742,9 @@ fn update_atime<T: AsRef<Path>>(path: &T) -> AppResultU {
 impl State {
     pub fn requests(&self) -> Vec<Request> {
         let mut result: Vec<Request> = self.processing.iter().cloned().collect();
+        for it in &self.retrying {
+            result.push(it.clone());
+        }
         for it in &self.queued {
             result.push(it.clone());
         }
@@ -371,6 +753,29 @@ impl State {
 }
 
 
+impl Failure {
+    fn permanent<T: fmt::Display>(reason: T) -> Self {
+        Failure { cancelled: false, reason: s!(reason), transient: false }
+    }
+}
+
+impl From<curl::Error> for Failure {
+    fn from(error: curl::Error) -> Self {
+        let transient =
+            error.is_operation_timedout() || error.is_couldnt_connect() || error.is_couldnt_resolve_host() ||
+            error.is_couldnt_resolve_proxy() || error.is_recv_error() || error.is_send_error() ||
+            error.is_partial_file() || error.is_got_nothing() || error.is_ssl_connect_error();
+        Failure { cancelled: error.is_aborted_by_callback(), reason: s!(error), transient }
+    }
+}
+
+impl From<io::Error> for Failure {
+    fn from(error: io::Error) -> Self {
+        Failure::permanent(error)
+    }
+}
+
+
 impl Ord for Request {
     fn cmp(&self, other: &Self) -> Ordering {
         self.ticket.cmp(&other.ticket)
@@ -391,3 +796,15 @@ impl PartialEq for Request {
         self.ticket == other.ticket
     }
 }
+
+
+
+#[cfg(test)]#[test]
+fn test_retry_delay() {
+    let base = Duration::from_secs(1);
+    assert_eq!(retry_delay(base, 0), Duration::from_secs(1));
+    assert_eq!(retry_delay(base, 3), Duration::from_secs(8));
+    assert_eq!(retry_delay(base, 20), MAX_RETRY_DELAY);
+    assert_eq!(retry_delay(base, 32), MAX_RETRY_DELAY);
+    assert_eq!(retry_delay(base, usize::max_value()), MAX_RETRY_DELAY);
+}
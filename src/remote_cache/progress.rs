@@ -0,0 +1,147 @@
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::util::time::duration_to_seconds;
+
+
+
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/** Shared between the main loop and the processor threads **/
+#[derive(Default)]
+pub struct Transfers {
+    pub progresses: BTreeMap<usize, Progress>, /* ticket => progress */
+    cancelled: HashSet<usize>,
+    dirty: bool,
+    reported: Option<Instant>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Progress {
+    pub url: String,
+    pub offset: f64, /* Resumed from */
+    pub received: f64,
+    pub started: Instant,
+    pub total: f64,
+}
+
+
+impl Transfers {
+    pub fn start(&mut self, ticket: usize, url: String) {
+        self.progresses.insert(ticket, Progress { url, offset: 0.0, received: 0.0, started: Instant::now(), total: 0.0 });
+        self.dirty = true;
+    }
+
+    pub fn finish(&mut self, ticket: usize) {
+        self.progresses.remove(&ticket);
+        self.cancelled.remove(&ticket);
+        self.dirty = true;
+    }
+
+    pub fn cancel(&mut self, ticket: usize) {
+        self.cancelled.insert(ticket);
+    }
+
+    /** Returns false, if the transfer has been cancelled **/
+    pub fn update(&mut self, ticket: usize, offset: f64, received: f64, total: f64) -> bool {
+        if self.cancelled.contains(&ticket) {
+            return false;
+        }
+        if let Some(progress) = self.progresses.get_mut(&ticket) {
+            let (received, total) = (offset + received, if 0.0 < total { offset + total } else { 0.0 });
+            if progress.offset != offset || progress.received != received || progress.total != total {
+                progress.offset = offset;
+                progress.received = received;
+                progress.total = total;
+                self.dirty = true;
+            }
+        }
+        true
+    }
+
+    /** Returns true at most once per `PROGRESS_INTERVAL`, only if something has changed since the last report **/
+    pub fn should_report(&mut self) -> bool {
+        if !self.dirty || self.reported.map_or(false, |it| it.elapsed() < PROGRESS_INTERVAL) {
+            return false;
+        }
+        self.dirty = false;
+        self.reported = Some(Instant::now());
+        true
+    }
+
+    /** Summary for the status bar (e.g. "1.2M/80.0M 512.0K/s") **/
+    pub fn summary(&self) -> String {
+        if self.progresses.is_empty() {
+            return o!("");
+        }
+        let received: f64 = self.progresses.values().map(|it| it.received).sum();
+        let total: f64 = self.progresses.values().map(|it| it.total).sum();
+        let speed: f64 = self.progresses.values().map(Progress::speed).sum();
+        if 0.0 < total {
+            format!("{}/{} {}/s", human_size(received), human_size(total), human_size(speed))
+        } else {
+            format!("{} {}/s", human_size(received), human_size(speed))
+        }
+    }
+}
+
+impl Progress {
+    /** bytes/second **/
+    pub fn speed(&self) -> f64 {
+        let elapsed = duration_to_seconds(&self.started.elapsed());
+        if 0.0 < elapsed {
+            (self.received - self.offset) / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+
+pub fn human_size(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+
+    if bytes < 1024.0 {
+        return format!("{}B", bytes as u64);
+    }
+
+    let mut value = bytes;
+    for unit in &UNITS {
+        value /= 1024.0;
+        if value < 1024.0 {
+            return format!("{:.1}{}", value, unit);
+        }
+    }
+    format!("{:.1}{}", value, UNITS[UNITS.len() - 1])
+}
+
+
+#[cfg(test)]#[test]
+fn test_human_size() {
+    assert_eq!(human_size(0.0), "0B");
+    assert_eq!(human_size(1023.0), "1023B");
+    assert_eq!(human_size(1536.0), "1.5K");
+    assert_eq!(human_size(80.0 * 1024.0 * 1024.0), "80.0M");
+    assert_eq!(human_size(3.0 * 1024.0 * 1024.0 * 1024.0), "3.0G");
+}
+
+
+
+#[cfg(test)]#[test]
+fn test_should_report() {
+    let mut transfers = Transfers::default();
+    assert!(!transfers.should_report());
+
+    transfers.start(1, o!("http://example.com/a.png"));
+    assert!(transfers.should_report());
+
+    transfers.update(1, 0.0, 10.0, 100.0);
+    assert!(!transfers.should_report()); // Too early
+
+    transfers.reported = None;
+    assert!(transfers.should_report());
+    transfers.reported = None;
+    transfers.update(1, 0.0, 10.0, 100.0);
+    assert!(!transfers.should_report()); // Not changed
+}
@@ -0,0 +1,242 @@
+
+use std::collections::HashSet;
+use std::fs;
+use std::ffi::OsStr;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use globset::{Glob, GlobMatcher};
+
+use crate::app_path;
+use crate::errors::{AppError, AppResult};
+use crate::util::path::path_to_str;
+
+
+
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub max_age: Option<Duration>,
+    pub max_size: Option<u64>, /* bytes */
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Usage {
+    pub files: usize,
+    pub size: u64,
+    pub deleted_files: usize,
+    pub deleted_size: u64,
+}
+
+struct CacheFile {
+    accessed: SystemTime,
+    path: PathBuf,
+    size: u64,
+    source: Option<String>, /* URL */
+}
+
+const PART_EXTENSION: &str = "part";
+const SOURCE_EXTENSION: &str = "source";
+
+
+impl Limits {
+    pub fn is_empty(&self) -> bool {
+        self.max_age.is_none() && self.max_size.is_none()
+    }
+}
+
+
+/** Delete the files whose URL matches `patterns`, and then sweep the least recently used files to fit in `limits`.
+    The files in `in_use` and the downloading (`*.part`) files are kept **/
+pub fn sweep(limits: &Limits, patterns: &[String], in_use: &HashSet<PathBuf>) -> AppResult<Usage> {
+    sweep_in(&app_path::cache_dir("remote"), limits, patterns, in_use, SystemTime::now())
+}
+
+/** Record the source URL of the cache file, to match `--delete` patterns against it **/
+pub fn write_source<T: AsRef<Path>>(cache_filepath: &T, url: &str) -> io::Result<()> {
+    fs::write(source_filepath(cache_filepath), url)
+}
+
+fn sweep_in(dir: &Path, limits: &Limits, patterns: &[String], in_use: &HashSet<PathBuf>, now: SystemTime) -> AppResult<Usage> {
+    let matchers = patterns.iter().map(|it| compile_pattern(it)).collect::<AppResult<Vec<GlobMatcher>>>()?;
+
+    let mut files = vec![];
+    collect_files(dir, &mut files)?;
+    files.sort_by(|a, b| a.accessed.cmp(&b.accessed));
+
+    let mut usage = Usage::default();
+    let mut size: u64 = files.iter().map(|it| it.size).sum();
+    let mut rest = vec![];
+
+    for file in files {
+        if in_use.contains(&file.path) {
+            rest.push(file);
+            continue;
+        }
+
+        let relative = file.path.strip_prefix(dir).unwrap_or(&file.path);
+        let matched = match file.source {
+            Some(ref source) => matchers.iter().any(|it| it.is_match(strip_scheme(source))),
+            None => matchers.iter().any(|it| it.is_match(relative)),
+        };
+        let expired = limits.max_age.map(|max_age| {
+            now.duration_since(file.accessed).map(|age| max_age < age).unwrap_or(false)
+        }).unwrap_or(false);
+        let over = limits.max_size.map(|max_size| max_size < size).unwrap_or(false);
+
+        if expired || over || matched {
+            match remove_file(&file.path) {
+                Ok(_) => {
+                    let _ = remove_file(&source_filepath(&file.path));
+                    size -= file.size;
+                    usage.deleted_files += 1;
+                    usage.deleted_size += file.size;
+                },
+                Err(err) => {
+                    puts_error!(err, "at" => "remote_cache/sweep", "for" => path_to_str(&file.path));
+                    rest.push(file);
+                }
+            }
+        } else {
+            rest.push(file);
+        }
+    }
+
+    remove_empty_dirs(dir)?;
+
+    usage.files = rest.len();
+    usage.size = size;
+    Ok(usage)
+}
+
+fn collect_files(dir: &Path, result: &mut Vec<CacheFile>) -> io::Result<()> {
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let meta = match entry.metadata() {
+            Ok(meta) => meta,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        if meta.is_dir() {
+            collect_files(&path, result)?;
+        } else if meta.is_file() {
+            let extension = path.extension().and_then(OsStr::to_str);
+            if extension == Some(PART_EXTENSION) {
+                continue;
+            }
+            if extension == Some(SOURCE_EXTENSION) {
+                if !path.with_extension("").exists() {
+                    let _ = remove_file(&path);
+                }
+                continue;
+            }
+            let accessed = meta.accessed().or_else(|_| meta.modified()).unwrap_or(SystemTime::UNIX_EPOCH);
+            let source = fs::read_to_string(source_filepath(&path)).ok();
+            result.push(CacheFile { accessed, path, size: meta.len(), source });
+        }
+    }
+    Ok(())
+}
+
+/** `pattern` is matched against `HOST/PATH` of URLs (The scheme is ignored) **/
+fn compile_pattern(pattern: &str) -> AppResult<GlobMatcher> {
+    let pattern = strip_scheme(pattern);
+    let glob = Glob::new(pattern).map_err(|it| AppError::InvalidValueWithReason(o!(pattern), s!(it)))?;
+    Ok(glob.compile_matcher())
+}
+
+fn strip_scheme(url: &str) -> &str {
+    url.find("://").map(|index| &url[index + 3..]).unwrap_or(url)
+}
+
+fn source_filepath<T: AsRef<Path>>(cache_filepath: &T) -> PathBuf {
+    let mut result = cache_filepath.as_ref().as_os_str().to_owned();
+    result.push(".");
+    result.push(SOURCE_EXTENSION);
+    PathBuf::from(result)
+}
+
+/** Vanished directories are treated as empty **/
+fn read_dir(dir: &Path) -> io::Result<Vec<io::Result<fs::DirEntry>>> {
+    match dir.read_dir() {
+        Ok(entries) => Ok(entries.collect()),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err),
+    }
+}
+
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/** The downloader recreates the directories before writing, so the races with it are harmless **/
+fn remove_empty_dirs(dir: &Path) -> io::Result<()> {
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            remove_empty_dirs(&path)?;
+            let empty = path.read_dir().map(|mut it| it.next().is_none()).unwrap_or(false);
+            if empty {
+                if let Err(err) = fs::remove_dir(&path) {
+                    if err.kind() != ErrorKind::NotFound {
+                        puts_error!(err, "at" => "remote_cache/sweep", "for" => path_to_str(&path));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]#[test]
+fn test_sweep() {
+    use std::env::temp_dir;
+    use std::fs::{File, create_dir_all};
+    use std::io::Write;
+
+    use filetime::{FileTime, set_file_times};
+
+    let dir = temp_dir().join(format!("chrysoberyl-test-sweep-{}", uuid::Uuid::new_v4()));
+    let now = SystemTime::now();
+
+    let write = |path: &str, size: usize, age: u64| {
+        let path = dir.join(path);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(&path).unwrap().write_all(&vec![0; size]).unwrap();
+        let time = FileTime::from_system_time(now - Duration::from_secs(age));
+        set_file_times(&path, time, time).unwrap();
+    };
+
+    write("example.com/a.png", 100, 30);
+    write("example.com/b.png", 100, 20);
+    write("example.com/c.png", 100, 10);
+    write("example.org/old.png", 10, 1000);
+    write("cdn.example.net/img/x.png", 10, 0);
+    write("example.net/0123456789abcdef0123456789abcdef.png", 10, 0);
+    write_source(&dir.join("example.net/0123456789abcdef0123456789abcdef.png"), "https://example.net/a-very-long-name.png").unwrap();
+    write("example.net/loading.png.part", 10, 1000);
+    write("example.jp/in-use.png", 10, 1000);
+
+    let in_use = hashset!{ dir.join("example.jp/in-use.png") };
+    let limits = Limits { max_age: Some(Duration::from_secs(100)), max_size: Some(250) };
+    let patterns = [o!("https://cdn.example.net/**"), o!("example.net/a-very-*")];
+    let usage = sweep_in(&dir, &limits, &patterns, &in_use, now).unwrap();
+    assert_eq!(usage, Usage { files: 3, size: 210, deleted_files: 4, deleted_size: 130 });
+    assert!(!dir.join("example.com/a.png").exists());
+    assert!(dir.join("example.com/c.png").exists());
+    assert!(!dir.join("example.org").exists());
+    assert!(!dir.join("cdn.example.net").exists());
+    assert!(!dir.join("example.net/0123456789abcdef0123456789abcdef.png.source").exists());
+    assert!(dir.join("example.net/loading.png.part").exists());
+    assert!(dir.join("example.jp/in-use.png").exists());
+
+    let usage = sweep_in(&dir, &Limits::default(), &[], &HashSet::new(), now).unwrap();
+    assert_eq!(usage, Usage { files: 3, size: 210, deleted_files: 0, deleted_size: 0 });
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,329 @@
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::mpsc::Sender;
+use std::thread::spawn;
+
+use globset::{Glob, GlobMatcher};
+use lazy_static::lazy_static;
+use marksman_escape::Unescape;
+use regex::Regex;
+use url::Url;
+
+use crate::entry::{Meta, MetaEntry, new_opt_meta};
+use crate::errors::{AppError, AppResult, AppResultU};
+use crate::operation::Operation;
+use crate::remote_cache::curl_options::CurlOptions;
+use crate::remote_cache::get_text;
+
+
+
+lazy_static! {
+    static ref TAG: Regex = Regex::new(r"<([A-Za-z][A-Za-z0-9-]*)([^>]*)>").unwrap();
+    static ref ATTRIBUTE: Regex = Regex::new(r#"([A-Za-z_:][-A-Za-z0-9_:.]*)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#).unwrap();
+    static ref SELECTOR: Regex = Regex::new(r"^(\*|[A-Za-z0-9-]*)((?:[.#][-A-Za-z0-9_]+|\[[^\]]+\])*)(?:@([-A-Za-z0-9_:]+))?$").unwrap();
+    static ref SELECTOR_PART: Regex = Regex::new(r#"([.#])([-A-Za-z0-9_]+)|\[\s*([-A-Za-z0-9_:]+)\s*(?:=\s*(?:"([^"]*)"|'([^']*)'|([^\]]*?))\s*)?\]"#).unwrap();
+}
+
+
+#[derive(Clone, Default)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Clone)]
+pub struct Rule {
+    pub pattern: String,
+    pub source: String,
+    pub next: bool,
+    pub regex: bool,
+    matcher: GlobMatcher,
+    extractor: Extractor,
+}
+
+#[derive(Clone)]
+enum Extractor {
+    Regex(Regex),
+    Selector(Selector),
+}
+
+/** `TAG.CLASS#ID[ATTR=VALUE]@TARGET_ATTR` (Descendant combinators are not supported) **/
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Selector {
+    attributes: Vec<(String, Option<String>)>,
+    classes: Vec<String>,
+    id: Option<String>,
+    tag: Option<String>,
+    target: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Page {
+    pub images: Vec<String>,
+    pub next: Vec<String>,
+}
+
+
+impl Rules {
+    pub fn set(&mut self, pattern: &str, source: &str, next: bool, regex: bool) -> AppResultU {
+        let matcher = compile_pattern(pattern)?;
+        let extractor = if regex {
+            Extractor::Regex(Regex::new(source).map_err(|it| AppError::InvalidValueWithReason(o!(source), s!(it)))?)
+        } else {
+            Extractor::Selector(source.parse()?)
+        };
+        self.rules.push(Rule { pattern: o!(pattern), source: o!(source), next, regex, matcher, extractor });
+        Ok(())
+    }
+
+    pub fn unset(&mut self, pattern: &str) {
+        self.rules.retain(|it| it.pattern != pattern);
+    }
+
+    /** If no image rule matches `page_url`, all `<img src>` are extracted **/
+    pub fn extract(&self, page_url: &Url, html: &str) -> Page {
+        let target = strip_scheme(page_url.as_str());
+        let rules: Vec<&Rule> = self.rules.iter().filter(|it| it.matcher.is_match(target)).collect();
+
+        let mut page = Page::default();
+        let mut found = HashSet::new();
+
+        if rules.iter().all(|it| it.next) {
+            let selector = Selector { tag: Some(o!("img")), ..Default::default() };
+            push_urls(&mut page.images, &mut found, page_url, selector.extract(html));
+        }
+
+        for rule in rules {
+            let urls = match rule.extractor {
+                Extractor::Regex(ref regex) => extract_by_regex(regex, html),
+                Extractor::Selector(ref selector) => selector.extract(html),
+            };
+            let result = if rule.next { &mut page.next } else { &mut page.images };
+            push_urls(result, &mut found, page_url, urls);
+        }
+
+        page
+    }
+}
+
+impl Selector {
+    fn extract(&self, html: &str) -> Vec<String> {
+        let mut result = vec![];
+
+        for tag in TAG.captures_iter(html) {
+            let name = tag[1].to_lowercase();
+            let attributes = parse_attributes(&tag[2]);
+            if !self.is_match(&name, &attributes) {
+                continue;
+            }
+            let target = self.target.as_ref().map(String::as_str).unwrap_or_else(|| {
+                match name.as_str() {
+                    "a" | "area" | "link" => "href",
+                    _ => "src",
+                }
+            });
+            if let Some((_, value)) = attributes.into_iter().find(|(key, _)| key == target) {
+                result.push(value);
+            }
+        }
+
+        result
+    }
+
+    fn is_match(&self, name: &str, attributes: &[(String, String)]) -> bool {
+        let get = |key: &str| attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+
+        if self.tag.as_ref().map_or(false, |it| it != name) {
+            return false;
+        }
+        if self.id.is_some() && self.id.as_ref().map(String::as_str) != get("id") {
+            return false;
+        }
+        if !self.classes.is_empty() {
+            let classes: Vec<&str> = get("class").map(|it| it.split_whitespace().collect()).unwrap_or_default();
+            if !self.classes.iter().all(|it| classes.contains(&it.as_str())) {
+                return false;
+            }
+        }
+        self.attributes.iter().all(|(key, value)| {
+            match (get(key), value) {
+                (Some(actual), Some(expected)) => actual == expected.as_str(),
+                (actual, None) => actual.is_some(),
+                (None, _) => false,
+            }
+        })
+    }
+}
+
+impl FromStr for Selector {
+    type Err = AppError;
+
+    fn from_str(src: &str) -> AppResult<Self> {
+        let src = src.trim();
+        let captures = SELECTOR.captures(src).filter(|_| !src.is_empty()).ok_or_else(|| AppError::InvalidValue(o!(src)))?;
+        let mut result = Selector::default();
+
+        let tag = &captures[1];
+        if !tag.is_empty() && tag != "*" {
+            result.tag = Some(tag.to_lowercase());
+        }
+
+        for part in SELECTOR_PART.captures_iter(&captures[2]) {
+            if let (Some(kind), Some(name)) = (part.get(1), part.get(2)) {
+                if kind.as_str() == "." {
+                    result.classes.push(o!(name.as_str()));
+                } else {
+                    result.id = Some(o!(name.as_str()));
+                }
+            } else if let Some(name) = part.get(3) {
+                let value = part.get(4).or_else(|| part.get(5)).or_else(|| part.get(6)).map(|it| o!(it.as_str()));
+                result.attributes.push((name.as_str().to_lowercase(), value));
+            }
+        }
+
+        result.target = captures.get(3).map(|it| it.as_str().to_lowercase());
+        Ok(result)
+    }
+}
+
+
+/** Fetch `url`, push the extracted images, and follow the next page links up to `max_pages` **/
+#[allow(clippy::too_many_arguments)]
+pub fn scrape(tx: Sender<Operation>, rules: Rules, url: String, options: CurlOptions, meta: Option<Meta>, force: bool, show: bool, max_pages: usize) {
+    spawn(move || {
+        let mut visited = HashSet::new();
+        let mut pushed = HashSet::new();
+        let mut page_url = Some(url.clone());
+        let mut referer: Option<String> = None;
+        let mut count = 0;
+
+        while let Some(current) = page_url.take() {
+            if max_pages <= visited.len() || !visited.insert(current.clone()) {
+                break;
+            }
+
+            let page = Url::parse(&current).map_err(AppError::from).and_then(|base| {
+                let html = get_text(&current, &options, referer.as_ref().map(String::as_str))?;
+                Ok(rules.extract(&base, &html))
+            });
+            let page = match page {
+                Ok(page) => page,
+                Err(err) => {
+                    puts_error!(err, "at" => "scrape", "for" => o!(&current));
+                    break;
+                }
+            };
+
+            for image in page.images {
+                if !pushed.insert(image.clone()) {
+                    continue;
+                }
+                let meta = page_meta(&meta, &current);
+                tx.send(Operation::PushURL(image, meta, force, show && count == 0, None, Some(current.clone()))).unwrap();
+                count += 1;
+            }
+
+            page_url = page.next.into_iter().next();
+            referer = Some(current);
+        }
+
+        puts_event!("scrape/done", "url" => url, "pages" => s!(visited.len()), "images" => s!(count));
+    });
+}
+
+fn compile_pattern(pattern: &str) -> AppResult<GlobMatcher> {
+    let glob = Glob::new(strip_scheme(pattern)).map_err(|it| AppError::InvalidValueWithReason(o!(pattern), s!(it)))?;
+    Ok(glob.compile_matcher())
+}
+
+fn extract_by_regex(regex: &Regex, html: &str) -> Vec<String> {
+    regex.captures_iter(html).filter_map(|it| it.get(1).or_else(|| it.get(0))).map(|it| o!(it.as_str())).collect()
+}
+
+fn page_meta(base: &Option<Meta>, page_url: &str) -> Option<Meta> {
+    let mut entries: Vec<MetaEntry> = base.as_ref().map(|it| it.to_vec()).unwrap_or_default();
+    entries.push(MetaEntry { key: o!("page"), value: o!(page_url) });
+    new_opt_meta(entries)
+}
+
+fn parse_attributes(source: &str) -> Vec<(String, String)> {
+    ATTRIBUTE.captures_iter(source).map(|it| {
+        let value = it.get(2).or_else(|| it.get(3)).or_else(|| it.get(4)).map(|it| unescape(it.as_str())).unwrap_or_default();
+        (it[1].to_lowercase(), value)
+    }).collect()
+}
+
+fn push_urls(result: &mut Vec<String>, found: &mut HashSet<String>, base: &Url, urls: Vec<String>) {
+    for url in urls {
+        if let Ok(url) = base.join(url.trim()) {
+            let url = s!(url);
+            if found.insert(url.clone()) {
+                result.push(url);
+            }
+        }
+    }
+}
+
+/** Globs are matched against `HOST/PATH?QUERY` (The scheme is ignored) **/
+fn strip_scheme(url: &str) -> &str {
+    url.find("://").map(|index| &url[index + 3..]).unwrap_or(url)
+}
+
+fn unescape(s: &str) -> String {
+    let bytes: Vec<u8> = Unescape::new(s.bytes()).collect();
+    String::from_utf8(bytes).unwrap_or_else(|_| o!(s))
+}
+
+
+#[cfg(test)]#[test]
+fn test_selector() {
+    assert_eq!(
+        "img.photo.large#main[data-x='1'][alt]@data-src".parse::<Selector>().unwrap(),
+        Selector {
+            attributes: vec![(o!("data-x"), Some(o!("1"))), (o!("alt"), None)],
+            classes: vec![o!("photo"), o!("large")],
+            id: Some(o!("main")),
+            tag: Some(o!("img")),
+            target: Some(o!("data-src")),
+        });
+    assert_eq!("*@href".parse::<Selector>().unwrap(), Selector { target: Some(o!("href")), ..Default::default() });
+    assert!("".parse::<Selector>().is_err());
+    assert!("img > a".parse::<Selector>().is_err());
+}
+
+#[cfg(test)]#[test]
+fn test_extract() {
+    let html = r#"
+        <html><body>
+          <img src="/logo.png">
+          <div class="gallery"><IMG class="photo big" SRC="images/1.jpg?a=1&amp;b=2" /></div>
+          <img class="photo" data-src='https://cdn.example.org/2.jpg' src="spacer.gif">
+          <a rel="next" href="?page=2">Next</a>
+          <script>var data = {"full": "https://cdn.example.org/3.jpg"};</script>
+        </body></html>"#;
+    let base = Url::parse("https://example.com/gallery/index.html").unwrap();
+
+    let mut rules = Rules::default();
+    assert_eq!(
+        rules.extract(&base, html).images,
+        vec![o!("https://example.com/logo.png"), o!("https://example.com/gallery/images/1.jpg?a=1&b=2"), o!("https://example.com/gallery/spacer.gif")]);
+
+    rules.set("https://example.com/gallery/*", "img.photo.big", false, false).unwrap();
+    rules.set("https://example.com/gallery/*", "img.photo@data-src", false, false).unwrap();
+    rules.set("example.com/gallery/*", r#""full": "([^"]+)""#, false, true).unwrap();
+    rules.set("example.com/gallery/*", "a[rel=next]", true, false).unwrap();
+    rules.set("example.org/*", "img", false, false).unwrap();
+    assert_eq!(
+        rules.extract(&base, html),
+        Page {
+            images: vec![
+                o!("https://example.com/gallery/images/1.jpg?a=1&b=2"),
+                o!("https://cdn.example.org/2.jpg"),
+                o!("https://cdn.example.org/3.jpg"),
+            ],
+            next: vec![o!("https://example.com/gallery/index.html?page=2")],
+        });
+
+    rules.unset("example.com/gallery/*");
+    assert_eq!(rules.rules.len(), 3);
+}
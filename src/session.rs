@@ -1,5 +1,5 @@
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::env;
 use std::fmt;
 use std::path::PathBuf;
@@ -130,33 +130,60 @@ pub fn generate_option_value(name: &PreDefinedOptionName, st: &States) -> (Strin
         Animation => gen("animation", &b2s(st.drawing.animation)),
         AutoReload => gen("auto-reload", &b2s(st.auto_reload)),
         AutoPaging => gen("auto-paging", &st.auto_paging),
+        Brightness => gen("brightness", &st.drawing.adjustment.brightness),
         Canonicalize => gen("canonicalize", &b2s(st.canonicalize)),
+        CherenkovStore => gen("cherenkov-store", &b2s(st.cherenkov_store)),
         ColorLink => gen("link-color", &st.drawing.link_color),
+        Compare => gen("compare", &st.compare),
+        CompareOperator => gen("compare-operator", &st.compare_operator),
+        CompareRatio => gen("compare-ratio", &st.compare_ratio),
+        Contrast => gen("contrast", &st.drawing.adjustment.contrast),
         CurlConnectTimeout => geno("curl-connect-timeout", &st.curl_options.connect_timeout),
         CurlFollowLocation => gen("curl-follow-location", &b2s(st.curl_options.follow_location)),
         CurlLowSpeedLimit => geno("curl-low-speed-limit", &st.curl_options.low_speed_limit),
         CurlLowSpeedTime => geno("curl-low-speed-time", &st.curl_options.low_speed_time),
+        CurlRetries => gen("curl-retries", &st.curl_options.retries),
+        CurlRetryDelay => gend("curl-retry-delay", &st.curl_options.retry_delay),
         CurlTimeout => geno("curl-timeout", &st.curl_options.connect_timeout),
+        DragToPan => gen("drag-to-pan", &b2s(st.drag_to_pan)),
+        EyeDetector => gen("eye-detector", &st.eye_detector.backend),
+        EyeDetectorCascade => genp("eye-detector-cascade", &st.eye_detector.cascade),
+        EyeDetectorCommand => geno("eye-detector-command", &st.eye_detector.command),
         FitTo => gen("fit-to", &st.drawing.fit_to),
         Freeze => gen("freeze", &b2s(st.freezed)),
+        Gamma => gen("gamma", &st.drawing.adjustment.gamma),
+        Grayscale => gen("grayscale", &b2s(st.drawing.adjustment.grayscale)),
         HistoryFile => genp("history-file", &st.history_file),
         HorizontalFlip => gen("horizontal-flip", &st.drawing.horizontal_flip),
         HorizontalViews => gen("horizontal-views", &st.view.cols),
         IdleTime => gend("idle-time", &st.idle_time),
         IgnoreFailures => gen("ignore-failures", &b2s(st.ignore_failures)),
         InitialPosition => gen("initial-position", &st.initial_position),
+        Invert => gen("invert", &b2s(st.drawing.adjustment.invert)),
         LogFile => gen("log-file", &st.log_file),
+        Loupe => gen("loupe", &b2s(st.loupe.enabled)),
+        LoupeMagnification => gen("loupe-magnification", &st.loupe.magnification),
+        LoupeSize => gen("loupe-size", &st.loupe.size),
         MaskOperator => gen("mask-operator", &st.drawing.mask_operator),
+        Offline => gen("offline", &b2s(st.offline)),
         PathList => gen("path", &st.path_list),
+        PixelInspector => gen("pixel-inspector", &b2s(st.pixel_inspector)),
         PreFetchEnabled => gen("pre-render", &b2s(st.pre_fetch.enabled)),
         PreFetchLimit => gen("pre-render-limit", &st.pre_fetch.limit_of_items),
         PreFetchPageSize => gen("pre-render-pages", &st.pre_fetch.page_size),
         PreFetchStages => gen("pre-render-stages", &st.pre_fetch.cache_stages),
+        RememberZoom => gen("remember-zoom", &b2s(st.remember_zoom)),
+        RemoteCacheMaxAge => geno("remote-cache-max-age", &st.remote_cache_max_age),
+        RemoteCacheMaxSize => geno("remote-cache-max-size", &st.remote_cache_max_size),
         Reverse => gen("reverse", &b2s(st.reverse)),
         Rotation => gen("rotation", &st.drawing.rotation),
+        Saturation => gen("saturation", &st.drawing.adjustment.saturation),
         Screen => gen("screen", &st.screen),
+        SimilarityThreshold => gen("similarity-threshold", &st.similarity_threshold),
         SkipResizeWindow => gen("skip-resize-window", &st.skip_resize_window),
+        SmoothScroll => gen("smooth-scroll", &b2s(st.smooth_scroll)),
         StablePush => gen("stable-push", &b2s(st.stable_push)),
+        Statistics => gen("statistics", &b2s(st.statistics)),
         StatusBar => gen("status-bar", &b2s(st.status_bar)),
         StatusBarAlign => gen("status-bar-align", &st.status_bar_align),
         StatusBarHeight => geno("status-bar-height", &st.status_bar_height),
@@ -167,9 +194,12 @@ pub fn generate_option_value(name: &PreDefinedOptionName, st: &States) -> (Strin
         EmptyStatusFormat => gen("empty-status-format", &st.empty_status_format),
         TimeToHidePointer => geno("time-to-hide-pointer", &st.time_to_hide_pointer),
         TitleFormat => gen("title-format", &st.title_format),
+        UndoDepth => gen("undo-depth", &st.undo_depth),
         UpdateCacheAccessTime => gen("update-cache-atime", &b2s(st.update_cache_atime)),
         VerticalFlip => gen("vertical-flip", &st.drawing.vertical_flip),
         VerticalViews => gen("vertical-views", &st.view.rows),
+        VideoFrameCommand => gen("video-frame-command", &st.video_frame_command),
+        VideoFrames => gen("video-frames", &st.video_frames),
         WatchFiles => gen("watch-files", &b2s(st.watch_files)),
     }
 }
@@ -207,19 +237,45 @@ pub fn write_options(st: &States, gui: &Gui, reading: bool, out: &mut String) {
     } else {
         sprintln!(out, "@unclip");
     }
+
+    if !reading {
+        for host in &st.curl_options.hosts {
+            let pattern = &host.pattern.source;
+            if !host.headers.is_empty() {
+                sprintln!(out, "@unset {}", escape(&format!("http-header@{}", pattern)));
+            }
+            // Credentials are never written. Set them in `config.chry` again
+            for (name, value) in host.values() {
+                sprintln!(out, "@set {} {}", escape(&format!("http-{}@{}", name, pattern)), escape(&value));
+            }
+        }
+        let patterns: BTreeSet<&str> = st.scrape_rules.rules.iter().map(|it| it.pattern.as_str()).collect();
+        for pattern in patterns {
+            sprintln!(out, "@scrape-rule --unset {}", escape(pattern));
+        }
+        for rule in &st.scrape_rules.rules {
+            let next = if rule.next { " --next" } else { "" };
+            let regex = if rule.regex { " --regex" } else { "" };
+            sprintln!(out, "@scrape-rule{}{} {} {}", next, regex, escape(&rule.pattern), escape(&rule.source));
+        }
+    }
 }
 
 pub fn write_queue(state: &Arc<Mutex<crate::remote_cache::State>>, out: &mut String) {
+    let state = state.lock().unwrap();
+    write_requests(&state.requests(), out);
+}
+
+pub fn write_requests(requests: &[crate::remote_cache::Request], out: &mut String) {
     use crate::entry::EntryType::*;
 
-    let state = state.lock().unwrap();
-    let requests = state.requests();
     for request in requests {
         let entry_type = request.entry_type.and_then(|entry_type| {
             let entry_type = match entry_type {
                 PDF => "pdf",
                 Image => "image",
                 Archive => "archive",
+                Video => "video",
                 _ => return None,
             };
             Some(entry_type)
@@ -228,6 +284,9 @@ pub fn write_queue(state: &Arc<Mutex<crate::remote_cache::State>>, out: &mut Str
         if let Some(entry_type) = entry_type {
             sprint!(out, " --as {}", entry_type);
         }
+        if let Some(ref referer) = request.referer {
+            sprint!(out, " --referer {}", escape(referer));
+        }
         sprint!(out, "{}", meta_args(&request.meta));
         sprintln!(out, " {}", escape(&request.url));
     }
@@ -235,6 +294,7 @@ pub fn write_queue(state: &Arc<Mutex<crate::remote_cache::State>>, out: &mut Str
 
 pub fn write_status(app: &App, out: &mut String) {
     app.cache.write_status_text(out);
+    app.undo_history.write_status_text(out);
     app.process_manager.write_status_text(out);
     app.remote_cache.write_status_text(out);
 }
@@ -279,9 +339,13 @@ fn write_entry(entry: &Entry, out: &mut String, previous: &mut Key) {
                 sprintln!(out, "@push-url --as archive{} {}", meta_args(&entry.meta), escape(url)),
             Pdf(_, _) if path_changed =>
                 sprintln!(out, "@push-url --as pdf{} {}", meta_args(&entry.meta), escape(url)),
-            Message(_) =>
-                panic!("WTF"),
-            Archive(_, _) | Pdf(_, _) | Memory(_, _) =>
+            Video(_, _) if path_changed =>
+                sprintln!(out, "@push-url --as video{} {}", meta_args(&entry.meta), escape(url)),
+            Message(_, Some(entry_type)) => // Placeholder for offline mode
+                sprintln!(out, "@push-url --as {}{} {}", entry_type, meta_args(&entry.meta), escape(url)),
+            Message(_, None) =>
+                sprintln!(out, "@push-url{} {}", meta_args(&entry.meta), escape(url)),
+            Archive(_, _) | Pdf(_, _) | Memory(_, _) | Video(_, _) =>
                 (),
         }
     } else {
@@ -292,9 +356,11 @@ fn write_entry(entry: &Entry, out: &mut String, previous: &mut Key) {
                 sprintln!(out, "@push-archive{} {}", meta_args(&entry.meta), escape_pathbuf(path.as_ref())),
             Pdf(ref path, _) if path_changed =>
                 sprintln!(out, "@push-pdf{} {}", meta_args(&entry.meta), escape_pathbuf(path.as_ref())),
-            Message(ref message) =>
+            Video(ref video, _) if path_changed =>
+                sprintln!(out, "@push-video{} {}", meta_args(&entry.meta), escape_pathbuf(&video.path)),
+            Message(ref message, _) =>
                 sprintln!(out, "@push-message{} {}", meta_args(&entry.meta), escape(message)),
-            Archive(_, _) | Pdf(_, _) | Memory(_, _) =>
+            Archive(_, _) | Pdf(_, _) | Memory(_, _) | Video(_, _) =>
                 (),
         }
     }
@@ -329,9 +395,9 @@ fn write_path(entry: &Entry, out: &mut String) {
                 out.push_str(url),
             Archive(_, ref entry) if entry.index == 0 =>
                 out.push_str(url),
-            Pdf(_, 0) =>
+            Pdf(_, 0) | Video(_, 0) =>
                 out.push_str(url),
-            Archive(_, _) | Pdf(_, _) | Memory(_, _) | Message(_) =>
+            Archive(_, _) | Pdf(_, _) | Memory(_, _) | Message(_, _) | Video(_, _) =>
                 return,
         }
     } else {
@@ -342,7 +408,9 @@ fn write_path(entry: &Entry, out: &mut String) {
                 out.push_str(path_to_str(&**path)),
             Pdf(ref path, 0) =>
                 out.push_str(path_to_str(&**path)),
-            Archive(_, _) | Pdf(_, _) | Memory(_, _) | Message(_) =>
+            Video(ref video, 0) =>
+                out.push_str(path_to_str(&video.path)),
+            Archive(_, _) | Pdf(_, _) | Memory(_, _) | Message(_, _) | Video(_, _) =>
                 return,
         }
     }
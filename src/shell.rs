@@ -8,7 +8,7 @@ use std::sync::{Arc, Mutex};
 use std::thread::spawn;
 
 use crate::chainer;
-use crate::errors::AppResultU;
+use crate::errors::{AppError, AppResultU};
 use crate::expandable::Expandable;
 use crate::operation::{Operation, ReadAs};
 use crate::session::StatusText;
@@ -53,6 +53,13 @@ impl ProcessManager {
         };
         call(self.entries.clone(), r#async, command_line, stdin, read_as, tx);
     }
+
+    /** Run the command in background with additional environment variables, and pass the result to `on_complete` **/
+    pub fn call_with_envs<F>(&self, command_line: Vec<String>, envs: Vec<(String, String)>, on_complete: F)
+    where F: FnOnce(AppResultU) + Send + 'static {
+        let entries = self.entries.clone();
+        spawn(move || on_complete(call_and_wait(entries, &command_line, &envs)));
+    }
 }
 
 impl StatusText for ProcessManager {
@@ -90,6 +97,35 @@ impl  Finalizer {
 }
 
 
+fn call_and_wait(entries: Entries, command_line: &[String], envs: &[(String, String)]) -> AppResultU {
+    let mut command = Command::new("setsid");
+    command
+        .args(command_line)
+        .envs(envs.iter().map(|(k, v)| (k, v)));
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = command.spawn()?;
+    let finalizer = Finalizer::new(entries, child.id(), command_line.to_vec());
+
+    puts_event!("shell/open");
+    let stderr = child.stderr.take();
+    spawn(move || pass("stderr", stderr));
+    pass("stdout", child.stdout.take());
+    let status = child.wait();
+    puts_event!("shell/close");
+    finalizer.finalize();
+    let status = status?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(AppError::Standard(format!("Command failed: {} ({})", join(command_line, ','), status)))
+    }
+}
+
 fn call(entries: Entries, r#async: bool, command_line: &[String], stdin: Option<String>, read_as: ReadAs, tx: Option<Sender<Operation>>) {
     let envs = if r#async { Some(get_envs()) } else { None };
 
@@ -14,7 +14,7 @@ use crate::util::num::feq;
 
 
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Coord {
     pub x: f64,
     pub y: f64,
@@ -65,6 +65,35 @@ impl Coord {
     pub fn on_region(&self, region: &Region) -> bool {
         region.left <= self.x && self.x <= region.right && region.top <= self.y && self.y <= region.bottom
     }
+
+    pub fn clipped(&self, clipping: &Region) -> Coord {
+        Coord {
+            x: (self.x - clipping.left) / clipping.width(),
+            y: (self.y - clipping.top) / clipping.height(),
+        }
+    }
+
+    pub fn unclipped(&self, clipping: &Region) -> Coord {
+        Coord {
+            x: clipping.left + self.x * clipping.width(),
+            y: clipping.top + self.y * clipping.height(),
+        }
+    }
+
+    /** Map the position on the rotated and flipped image to the original image **/
+    pub fn on_original_image(&self, drawing: &Drawing) -> Coord {
+        let (x, y) = match drawing.rotation % 4 {
+            1 => (self.y, 1.0 - self.x),
+            2 => (1.0 - self.x, 1.0 - self.y),
+            3 => (1.0 - self.y, self.x),
+            _ => (self.x, self.y),
+        };
+
+        Coord {
+            x: if drawing.vertical_flip { 1.0 - x } else { x },
+            y: if drawing.horizontal_flip { 1.0 - y } else { y },
+        }
+    }
 }
 
 
@@ -321,6 +350,13 @@ impl Region {
             bottom: clipping.top + self.bottom * vp,
         }
     }
+
+    /** Map the region on the rotated and flipped image to the original image **/
+    pub fn on_original_image(&self, drawing: &Drawing) -> Region {
+        let a = Coord { x: self.left, y: self.top }.on_original_image(drawing);
+        let b = Coord { x: self.right, y: self.bottom }.on_original_image(drawing);
+        Region::new(a.x, a.y, b.x, b.y)
+    }
 }
 
 
@@ -361,6 +397,25 @@ impl PartialEq for Region {
     }
 }
 
+impl FromStr for Coord {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, String> {
+        let err = Err(o!("Invalid format (e.g. 0.5x0.5)"));
+
+        let xy: Vec<&str> = src.split_terminator('x').collect();
+        if xy.len() != 2 {
+            return err;
+        }
+
+        if let (Ok(x), Ok(y)) = (xy[0].parse(), xy[1].parse()) {
+            Ok(Coord { x, y })
+        } else {
+            err
+        }
+    }
+}
+
 impl FromStr for Region {
     type Err = String;
 
@@ -436,3 +491,28 @@ fn gcd(x: i32, y: i32) -> i32 {
         gcd(y, x % y)
     }
 }
+
+
+
+#[cfg(test)]#[test]
+fn test_on_original_image() {
+    let region = Region::new(0.1, 0.2, 0.3, 0.6);
+    let drawing = |rotation: u8, horizontal_flip: bool, vertical_flip: bool| Drawing { rotation, horizontal_flip, vertical_flip, ..Drawing::default() };
+    let assert_region = |drawing: Drawing, expected: Region| {
+        let actual = region.on_original_image(&drawing);
+        for (a, e) in [actual.left, actual.top, actual.right, actual.bottom].iter().zip([expected.left, expected.top, expected.right, expected.bottom].iter()) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    };
+
+    assert_region(drawing(0, false, false), region);
+    assert_region(drawing(1, false, false), Region::new(0.2, 0.7, 0.6, 0.9));
+    assert_region(drawing(2, false, false), Region::new(0.7, 0.4, 0.9, 0.8));
+    assert_region(drawing(3, false, false), Region::new(0.4, 0.1, 0.8, 0.3));
+    assert_region(drawing(0, true, false), Region::new(0.1, 0.4, 0.3, 0.8));
+    assert_region(drawing(0, false, true), Region::new(0.7, 0.2, 0.9, 0.6));
+    assert_region(drawing(1, true, false), Region::new(0.2, 0.1, 0.6, 0.3));
+
+    let coord = Coord { x: 0.25, y: 0.5 }.on_original_image(&drawing(1, false, false));
+    assert_eq!(coord, Coord { x: 0.5, y: 0.75 });
+}
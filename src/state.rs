@@ -6,8 +6,10 @@ use std::io::Read;
 use std::path::PathBuf;
 use std::time::Duration;
 
+use crate::adjustment::Adjustment;
 use crate::app_path::{PathList, cache_dir};
 use crate::cherenkov::Operator;
+use crate::cherenkov::eye_detector;
 use crate::color::Color;
 use crate::entry::SearchKey;
 use crate::entry::filter::expression::Expr as FilterExpr;
@@ -17,6 +19,7 @@ use crate::gui::{Position, Screen, Views};
 use crate::logger;
 use crate::option::OptionValue;
 use crate::remote_cache::curl_options::CurlOptions;
+use crate::scraper;
 use crate::size::{FitTo, Region};
 use crate::util::string::remove_linebreaks;
 
@@ -27,9 +30,15 @@ pub struct States {
     pub auto_paging: AutoPaging,
     pub auto_reload: bool,
     pub canonicalize: bool,
+    pub cherenkov_store: bool,
+    pub compare: Compare,
+    pub compare_operator: Operator,
+    pub compare_ratio: u8,
     pub curl_options: CurlOptions,
+    pub drag_to_pan: bool,
     pub drawing: Drawing,
     pub empty_status_format: EmptyStatusFormat,
+    pub eye_detector: eye_detector::Config,
     pub freezed: bool,
     pub go: Option<SearchKey>,
     pub history_file: Option<PathBuf>,
@@ -39,13 +48,23 @@ pub struct States {
     pub last_direction: Direction,
     pub last_filter: Filters,
     pub log_file: logger::file::File,
+    pub loupe: LoupeState,
+    pub offline: bool,
     pub path_list: PathList,
+    pub pixel_inspector: bool,
     pub pre_fetch: PreFetchState,
+    pub remember_zoom: bool,
+    pub remote_cache_max_age: Option<usize>, /* days */
+    pub remote_cache_max_size: Option<usize>, /* megabytes */
     pub reverse: bool,
+    pub scrape_rules: scraper::Rules,
     pub screen: Screen,
+    pub similarity_threshold: u32,
     pub skip_resize_window: usize,
+    pub smooth_scroll: bool,
     pub spawned: bool,
     pub stable_push: bool,
+    pub statistics: bool,
     pub status_bar: bool,
     pub status_bar_align: Alignment,
     pub status_bar_height: Option<usize>,
@@ -55,13 +74,17 @@ pub struct States {
     pub style: Style,
     pub time_to_hide_pointer: Option<u32>,
     pub title_format: TitleFormat,
+    pub undo_depth: usize,
     pub update_cache_atime: bool,
+    pub video_frame_command: VideoFrameCommand,
+    pub video_frames: usize,
     pub view: Views,
     pub watch_files: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Drawing {
+    pub adjustment: Adjustment,
     pub animation: bool,
     pub clipping: Option<Region>,
     pub fit_to: FitTo,
@@ -72,6 +95,13 @@ pub struct Drawing {
     pub vertical_flip: bool,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoupeState {
+    pub enabled: bool,
+    pub magnification: u32, /* percent */
+    pub size: u32, /* pixels */
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct PreFetchState {
     pub cache_stages: usize,
@@ -104,6 +134,17 @@ pub enum AutoPaging {
     Smart,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Default)]
+pub enum Compare {
+    #[default]
+    Off,
+    Synced,
+    Difference,
+    OnionSkin,
+    Slider,
+}
+
 
 impl Default for States {
     fn default() -> Self {
@@ -115,9 +156,15 @@ impl Default for States {
             auto_paging: AutoPaging::default(),
             auto_reload: false,
             canonicalize: true,
+            cherenkov_store: false,
+            compare: Compare::default(),
+            compare_operator: Operator(cairo::Operator::Difference),
+            compare_ratio: 50,
             curl_options: CurlOptions::default(),
+            drag_to_pan: false,
             drawing: Drawing::default(),
             empty_status_format: EmptyStatusFormat::default(),
+            eye_detector: eye_detector::Config::default(),
             freezed: false,
             go: None,
             history_file: Some(history_file),
@@ -127,13 +174,23 @@ impl Default for States {
             last_direction: Direction::Forward,
             last_filter: Filters::default(),
             log_file: logger::file::File::new(),
+            loupe: LoupeState::default(),
+            offline: false,
             path_list: PathList::default(),
+            pixel_inspector: false,
             pre_fetch: PreFetchState::default(),
+            remember_zoom: false,
+            remote_cache_max_age: None,
+            remote_cache_max_size: None,
             reverse: false,
+            scrape_rules: scraper::Rules::default(),
             screen: Screen::Main,
+            similarity_threshold: 10,
             skip_resize_window: 0,
+            smooth_scroll: false,
             spawned: false,
             stable_push: true,
+            statistics: false,
             status_bar: true,
             status_bar_align: Alignment(gtk::Align::Center),
             status_bar_height: None,
@@ -143,7 +200,10 @@ impl Default for States {
             style: Style::default(),
             time_to_hide_pointer: Some(1000),
             title_format: TitleFormat::default(),
+            undo_depth: 100,
             update_cache_atime: false,
+            video_frame_command: VideoFrameCommand::default(),
+            video_frames: 10,
             view: Views::default(),
             watch_files: false,
         }
@@ -155,6 +215,7 @@ impl Default for States {
 impl Default for Drawing {
     fn default() -> Self {
         Drawing {
+            adjustment: Adjustment::default(),
             animation: true,
             clipping: None,
             fit_to: FitTo::Cell,
@@ -168,6 +229,17 @@ impl Default for Drawing {
 }
 
 
+impl Default for LoupeState {
+    fn default() -> Self {
+        LoupeState {
+            enabled: false,
+            magnification: 400,
+            size: 200,
+        }
+    }
+}
+
+
 impl Default for PreFetchState {
     fn default() -> Self {
         PreFetchState {
@@ -201,6 +273,31 @@ impl fmt::Display for AutoPaging {
 }
 
 
+impl Compare {
+    pub fn is_overlay(self) -> bool {
+        match self {
+            Compare::Difference | Compare::OnionSkin | Compare::Slider => true,
+            Compare::Off | Compare::Synced => false,
+        }
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use crate::state::Compare::*;
+        let result =
+            match *self {
+                Off => "off",
+                Synced => "sync",
+                Difference => "difference",
+                OnionSkin => "onion-skin",
+                Slider => "slider",
+            };
+        write!(f, "{}", result)
+    }
+}
+
+
 macro_rules! gen_includable {
     ($t:tt, $default:expr) => {
 
@@ -270,7 +367,7 @@ macro_rules! gen_format {
 
 gen_format!(
     StatusFormat,
-    "<span background=\"red\">$CHRY_MESSAGE</span><span background=\"#005050\"> $CHRY_PAGING/$CHRY_PAGES </span> $CHRY_ABBREV_PATH <span foreground=\"grey\">$CHRY_FLAGS</span> <span foreground=\"rosybrown\">${CHRY_REMOTE_QUEUE}q${CHRY_REMOTE_BUFFER}b${CHRY_REMOTE_THREAD}t</span>");
+    "<span background=\"red\">$CHRY_MESSAGE</span><span background=\"#005050\"> $CHRY_PAGING/$CHRY_PAGES </span> $CHRY_ABBREV_PATH <span foreground=\"grey\">$CHRY_FLAGS</span> <span foreground=\"khaki\">$CHRY_PIXEL</span> <span foreground=\"rosybrown\">${CHRY_REMOTE_PROGRESS} ${CHRY_REMOTE_QUEUE}q${CHRY_REMOTE_BUFFER}b${CHRY_REMOTE_THREAD}t</span>");
 gen_format!(
     EmptyStatusFormat,
     concat!("<span background=\"red\">$CHRY_MESSAGE</span>", env!("CARGO_PKG_NAME"), " v", env!("CARGO_PKG_VERSION")));
@@ -280,3 +377,16 @@ gen_format!(
 gen_includable!(
     Style,
     include_str!("static/default.css"));
+gen_includable!(
+    VideoFrameCommand,
+    "ffmpeg -v error -y -ss \"$(ffprobe -v error -show_entries format=duration -of csv=p=0 \"$CHRY_VIDEO_FILE\" | awk -v p=\"$CHRY_VIDEO_POSITION\" '{ print $1 * p }')\" -i \"$CHRY_VIDEO_FILE\" -frames:v 1 \"$CHRY_VIDEO_OUTPUT\"");
+
+
+impl VideoFrameCommand {
+    pub fn source(&self) -> &str {
+        match *self {
+            VideoFrameCommand::Script(_, ref source) => source,
+            VideoFrameCommand::Literal(ref s) => s,
+        }
+    }
+}
@@ -0,0 +1,250 @@
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use cairo::{Context, FontSlant, FontWeight, Format, ImageSurface};
+use gdk_pixbuf::{Pixbuf, PixbufExt};
+
+use crate::color::Color;
+use crate::entry::image::{Imaging, get_static_image_buffer};
+use crate::entry::{Entry, Key};
+use crate::errors::AppResult;
+use crate::gtk_utils::new_pixbuf_from_surface;
+use crate::size::Size;
+use crate::state::Drawing;
+
+
+
+const SOURCE_SIZE: i32 = 512;
+const DOMINANT_COLORS: usize = 5;
+const LUMA: usize = 3;
+
+const PADDING: f64 = 8.0;
+const HISTOGRAM_HEIGHT: f64 = 100.0;
+const SWATCH_HEIGHT: f64 = 16.0;
+const LINE_HEIGHT: f64 = 16.0;
+
+
+/** Histograms (red, green, blue and luma) and the dominant colors of an image **/
+#[derive(Clone)]
+pub struct Statistics {
+    pub dominant_colors: Vec<(Color, f64)>, /* color, ratio */
+    pub histograms: [[u32; 256]; 4],
+    pub pixels: u32,
+}
+
+/** The statistics of the entries computed in background **/
+#[derive(Clone, Default)]
+pub struct StatisticsTable {
+    table: Arc<Mutex<HashMap<Key, Arc<Statistics>>>>,
+}
+
+
+impl Statistics {
+    /** Ratio of the pixels of luma 0 **/
+    pub fn black(&self) -> f64 {
+        self.ratio(self.histograms[LUMA][0])
+    }
+
+    /** Ratios of the pixels of the channel 0 and 255 **/
+    pub fn clipped(&self, channel: usize) -> (f64, f64) {
+        (self.ratio(self.histograms[channel][0]), self.ratio(self.histograms[channel][255]))
+    }
+
+    /** Mean luma (0 - 255) **/
+    pub fn brightness(&self) -> f64 {
+        self.mean(LUMA)
+    }
+
+    /** Mean of the channel (0 - 255) **/
+    pub fn mean(&self, channel: usize) -> f64 {
+        let sum: f64 = self.histograms[channel].iter().enumerate().map(|(v, count)| v as f64 * f64!(*count)).sum();
+        self.ratio_f(sum)
+    }
+
+    /** Ratio of the pixels of luma 255 **/
+    pub fn white(&self) -> f64 {
+        self.ratio(self.histograms[LUMA][255])
+    }
+
+    fn ratio(&self, count: u32) -> f64 {
+        self.ratio_f(f64!(count))
+    }
+
+    fn ratio_f(&self, v: f64) -> f64 {
+        if self.pixels == 0 {
+            0.0
+        } else {
+            v / f64!(self.pixels)
+        }
+    }
+}
+
+impl StatisticsTable {
+    pub fn clear(&self) {
+        self.table.lock().unwrap().clear();
+    }
+
+    pub fn get(&self, key: &Key) -> Option<Arc<Statistics>> {
+        let table = self.table.lock().unwrap();
+        table.get(key).cloned()
+    }
+
+    pub fn remove(&self, key: &Key) {
+        self.table.lock().unwrap().remove(key);
+    }
+
+    pub fn compute(&self, entry: &Entry) -> AppResult<Arc<Statistics>> {
+        if let Some(result) = self.get(&entry.key) {
+            return Ok(result);
+        }
+
+        let imaging = Imaging::new(Size::new(SOURCE_SIZE, SOURCE_SIZE), Drawing::default());
+        let buffer = get_static_image_buffer(&entry.content, &imaging)?;
+        let result = Arc::new(compute(&buffer.get_pixbuf()));
+
+        let mut table = self.table.lock().unwrap();
+        table.insert(entry.key.clone(), Arc::clone(&result));
+        Ok(result)
+    }
+}
+
+
+pub fn compute(pixbuf: &Pixbuf) -> Statistics {
+    let (width, height) = (pixbuf.get_width() as usize, pixbuf.get_height() as usize);
+    let channels = pixbuf.get_n_channels() as usize;
+    let rowstride = pixbuf.get_rowstride() as usize;
+    let pixels: &[u8] = unsafe { pixbuf.get_pixels() };
+    compute_from_pixels(pixels, width, height, rowstride, channels)
+}
+
+/** Draw the histograms, the dominant colors and the numbers **/
+pub fn render(statistics: &Statistics) -> Pixbuf {
+    let width = 256.0 + PADDING * 2.0;
+    let height = PADDING * 4.0 + HISTOGRAM_HEIGHT + SWATCH_HEIGHT + LINE_HEIGHT * 3.0;
+
+    let surface = ImageSurface::create(Format::ARgb32, width as i32, height as i32).unwrap();
+    let context = Context::new(&surface);
+
+    context.set_source_rgba(0.0, 0.0, 0.0, 0.7);
+    context.paint();
+
+    // Except the clipped values to keep the scale
+    let max = statistics.histograms.iter().flat_map(|it| it[1 .. 255].iter()).max().cloned().unwrap_or(0);
+    let max = f64!(max!(max, 1));
+    let bottom = PADDING + HISTOGRAM_HEIGHT;
+    let colors = [(1.0, 0.3, 0.3), (0.3, 1.0, 0.3), (0.3, 0.3, 1.0), (1.0, 1.0, 1.0)];
+    context.set_line_width(1.0);
+    for (histogram, &(r, g, b)) in statistics.histograms.iter().zip(colors.iter()) {
+        context.set_source_rgba(r, g, b, 0.8);
+        context.move_to(PADDING, bottom);
+        for (v, count) in histogram.iter().enumerate() {
+            let h = min!(f64!(*count) / max, 1.0) * HISTOGRAM_HEIGHT;
+            context.line_to(PADDING + v as f64 + 0.5, bottom - h);
+        }
+        context.stroke();
+    }
+
+    let mut x = PADDING;
+    let top = bottom + PADDING;
+    for &(color, ratio) in &statistics.dominant_colors {
+        let w = ratio * 256.0;
+        let (r, g, b) = color.tupled3();
+        context.set_source_rgb(r, g, b);
+        context.rectangle(x, top, w, SWATCH_HEIGHT);
+        context.fill();
+        x += w;
+    }
+
+    let lines = [
+        format!(
+            "mean R:{:.1} G:{:.1} B:{:.1} L:{:.1}",
+            statistics.mean(0), statistics.mean(1), statistics.mean(2), statistics.brightness()),
+        format!("black {:.2}%  white {:.2}%", statistics.black() * 100.0, statistics.white() * 100.0),
+        {
+            let clipped: Vec<String> = ["R", "G", "B"].iter().enumerate().map(|(channel, name)| {
+                let (black, white) = statistics.clipped(channel);
+                format!("{}:{:.1}/{:.1}", name, black * 100.0, white * 100.0)
+            }).collect();
+            format!("clipped% {}", clipped.join(" "))
+        },
+    ];
+    context.set_source_rgb(1.0, 1.0, 1.0);
+    context.select_font_face("sans-serif", FontSlant::Normal, FontWeight::Normal);
+    context.set_font_size(12.0);
+    let mut y = top + SWATCH_HEIGHT + PADDING;
+    for line in &lines {
+        y += LINE_HEIGHT;
+        context.move_to(PADDING, y - 4.0);
+        context.show_text(line);
+    }
+
+    new_pixbuf_from_surface(&surface)
+}
+
+
+fn compute_from_pixels(pixels: &[u8], width: usize, height: usize, rowstride: usize, channels: usize) -> Statistics {
+    let mut histograms = [[0; 256]; 4];
+    let mut bins: HashMap<u16, (u32, [u32; 3])> = HashMap::new(); /* 4 bits for each channel => count, sum */
+    let mut n = 0;
+
+    for y in 0 .. height {
+        for x in 0 .. width {
+            let i = y * rowstride + x * channels;
+            if channels == 4 && pixels[i + 3] == 0 {
+                continue;
+            }
+            let (r, g, b) = (pixels[i], pixels[i + 1], pixels[i + 2]);
+            let luma = (u32::from(r) * 299 + u32::from(g) * 587 + u32::from(b) * 114) / 1000;
+            histograms[0][r as usize] += 1;
+            histograms[1][g as usize] += 1;
+            histograms[2][b as usize] += 1;
+            histograms[LUMA][luma as usize] += 1;
+            n += 1;
+
+            let bin = bins.entry((u16::from(r >> 4) << 8) | (u16::from(g >> 4) << 4) | u16::from(b >> 4)).or_insert((0, [0; 3]));
+            bin.0 += 1;
+            bin.1[0] += u32::from(r);
+            bin.1[1] += u32::from(g);
+            bin.1[2] += u32::from(b);
+        }
+    }
+
+    let mut bins: Vec<(u16, (u32, [u32; 3]))> = bins.into_iter().collect();
+    bins.sort_by(|a, b| (b.1).0.cmp(&(a.1).0).then(a.0.cmp(&b.0)));
+    let dominant_colors = bins.iter().take(DOMINANT_COLORS).map(|(_, (count, sum))| {
+        let mean = |v: u32| (v / count) as u8;
+        (Color::new(mean(sum[0]), mean(sum[1]), mean(sum[2])), f64!(*count) / f64!(n))
+    }).collect();
+
+    Statistics { dominant_colors, histograms, pixels: n }
+}
+
+
+
+#[cfg(test)]#[test]
+fn test_compute_from_pixels() {
+    // 3x2 RGBA (rowstride 16) with a transparent pixel
+    let pixels: Vec<u8> = vec![
+        0, 0, 0, 255,  0, 0, 0, 255,  255, 255, 255, 255,  9, 9, 9, 9,
+        0, 0, 0, 255,  200, 0, 0, 255,  100, 100, 100, 0,  9, 9, 9, 9,
+    ];
+    let statistics = compute_from_pixels(&pixels, 3, 2, 16, 4);
+
+    assert_eq!(statistics.pixels, 5);
+    assert_eq!(statistics.histograms[0][0], 3);
+    assert_eq!(statistics.histograms[0][200], 1);
+    assert_eq!(statistics.histograms[LUMA][59], 1);
+    assert!((statistics.black() - 0.6).abs() < 0.0001);
+    assert!((statistics.white() - 0.2).abs() < 0.0001);
+    let (black, white) = statistics.clipped(0);
+    assert!((black - 0.6).abs() < 0.0001);
+    assert!((white - 0.2).abs() < 0.0001);
+    let (black, white) = statistics.clipped(1);
+    assert!((black - 0.8).abs() < 0.0001);
+    assert!((white - 0.2).abs() < 0.0001);
+    assert!((statistics.mean(0) - 91.0).abs() < 0.0001);
+    assert!((statistics.brightness() - 62.8).abs() < 0.0001);
+    assert_eq!(statistics.dominant_colors[0], (Color::black(), 0.6));
+    assert_eq!(statistics.dominant_colors.len(), 3);
+}
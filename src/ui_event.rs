@@ -13,7 +13,7 @@ use std::time::Duration;
 
 use closet::clone_army;
 use enum_primitive::FromPrimitive;
-use gdk::ScrollDirection;
+use gdk::{ModifierType, ScrollDirection};
 use gtk::prelude::*;
 use gtk::{Inhibit, SelectionData};
 use libc::c_void;
@@ -52,9 +52,12 @@ pub enum Event {
     ButtonRelease(Key, (f64, f64)),
     Configure((u32, u32)),
     Delete,
-    Motion,
-    Scroll(Key, ScrollDirection),
+    Motion((f64, f64), bool), /* position, dragging */
+    Scroll(Key, ScrollDirection, (f64, f64)),
     UIKeyPress(Key),
+    UpdateDragToPan(bool),
+    UpdateLoupe(bool),
+    UpdatePixelInspector(bool),
     UpdateScreen(Screen),
     UpdateTimeToHidePointer(Option<u32>),
     WindowKeyPress(Key, u32),
@@ -66,6 +69,18 @@ impl UIEvent {
         UIEvent { tx: register(gui, skip, app_tx) }
     }
 
+    pub fn update_drag_to_pan(&self, enabled: bool) {
+        self.tx.send(Event::UpdateDragToPan(enabled)).unwrap();
+    }
+
+    pub fn update_loupe(&self, enabled: bool) {
+        self.tx.send(Event::UpdateLoupe(enabled)).unwrap();
+    }
+
+    pub fn update_pixel_inspector(&self, enabled: bool) {
+        self.tx.send(Event::UpdatePixelInspector(enabled)).unwrap();
+    }
+
     pub fn update_screen(&self, screen: Screen) {
         self.tx.send(Event::UpdateScreen(screen)).unwrap();
     }
@@ -95,8 +110,9 @@ fn register(gui: &Gui, skip: usize, app_tx: &Sender<Operation>) -> Sender<Event>
         Inhibit(true)
     }));
 
-    gui.event_box.connect_motion_notify_event(clone_army!([tx] move |_, _| {
-        tx.send(Motion).unwrap();
+    gui.event_box.connect_motion_notify_event(clone_army!([tx] move |_, motion| {
+        let dragging = motion.get_state().contains(ModifierType::BUTTON1_MASK);
+        tx.send(Motion(motion.get_position(), dragging)).unwrap();
         Inhibit(false)
     }));
 
@@ -121,7 +137,7 @@ fn register(gui: &Gui, skip: usize, app_tx: &Sender<Operation>) -> Sender<Event>
     }));
 
     gui.window.connect_scroll_event(clone_army!([tx] move |_, scroll| {
-        tx.send(Scroll(Key::from(scroll), scroll.get_direction())).unwrap();
+        tx.send(Scroll(Key::from(scroll), scroll.get_direction(), scroll.get_position())).unwrap();
         Inhibit(true)
     }));
 
@@ -146,6 +162,10 @@ fn main(app_tx: &Sender<Operation>, rx: &Receiver<Event>, skip: usize) {
     let mut pressed_at = None;
     let mut screen = S::Main;
     let mut ignore_motion = false;
+    let mut drag_to_pan = false;
+    let mut loupe = false;
+    let mut pixel_inspector = false;
+    let mut panned_at = None;
 
     while let Ok(event) = rx.recv() {
         match event {
@@ -160,6 +180,9 @@ fn main(app_tx: &Sender<Operation>, rx: &Receiver<Event>, skip: usize) {
                 pressed_at = Some((x, y)),
             ButtonRelease(key, (x, y)) =>
                 match screen {
+                    S::Main if panned_at.take().is_some() =>
+                        pressed_at = None,
+                    S::Main if pixel_inspector && key.as_str() == "button-1" => on_button_release_on_inspector(app_tx, key, x, y, &mut pressed_at, &mut conf),
                     S::Main => on_button_release(app_tx, key, x, y, &mut pressed_at, &mut conf),
                     S::UserUI => on_button_release_on_user_ui(app_tx),
                     _ => (),
@@ -182,12 +205,27 @@ fn main(app_tx: &Sender<Operation>, rx: &Receiver<Event>, skip: usize) {
                 on_motion(&mut on_motion_sender);
                 ignore_motion = false;
             },
+            UpdateDragToPan(enabled) =>
+                drag_to_pan = enabled,
+            UpdateLoupe(enabled) =>
+                loupe = enabled,
+            UpdatePixelInspector(enabled) =>
+                pixel_inspector = enabled,
             UpdateScreen(new_screen) =>
                 screen = new_screen,
-            Scroll(key, direction) =>
-                if screen == S::Main { on_scroll(app_tx, key, direction) },
-            Motion if !ignore_motion =>
-                on_motion(&mut on_motion_sender),
+            Scroll(key, direction, (x, y)) =>
+                if screen == S::Main { on_scroll(app_tx, key, direction, x, y, &conf) },
+            Motion((x, y), dragging) => {
+                if drag_to_pan && dragging && screen == S::Main {
+                    on_pan(app_tx, x, y, pressed_at, &mut panned_at);
+                }
+                if (loupe || pixel_inspector) && screen == S::Main {
+                    app_tx.send(Operation::PointerMotion(Some(CoordPx { x: x as i32, y: y as i32, width: conf.width, height: conf.height }))).unwrap();
+                }
+                if !ignore_motion {
+                    on_motion(&mut on_motion_sender);
+                }
+            },
             _ => (),
         }
     }
@@ -227,6 +265,20 @@ fn on_button_release(tx: &Sender<Operation>, key: Key, x: f64, y: f64, pressed_a
     }
 }
 
+fn on_button_release_on_inspector(tx: &Sender<Operation>, key: Key, x: f64, y: f64, pressed_at: &mut Option<(f64, f64)>, conf: &mut Conf) {
+    use crate::inspector::ColorFormat;
+    use crate::operation::ClipboardSelection;
+
+    if_let_some!((px, py) = *pressed_at);
+
+    if feq(x, px, 10.0) && feq(y, py, 10.0) {
+        let coord = CoordPx { x: x as i32, y: y as i32, width: conf.width, height: conf.height };
+        tx.send(Operation::PickColor(Some(coord), ColorFormat::Hex, ClipboardSelection::Clipboard)).unwrap();
+    } else {
+        on_button_release(tx, key, x, y, pressed_at, conf);
+    }
+}
+
 fn on_button_release_on_user_ui(tx: &Sender<Operation>) {
     use crate::operation::option::OptionName::PreDefined;
     use crate::operation::option::OptionUpdater::Unset;
@@ -262,6 +314,11 @@ fn on_drag_data_received(tx: &Sender<Operation>, selection: &SelectionData, drop
     match *drop_item_type {
         DropItemType::Path => {
             for uri in &selection.get_uris() {
+                // Browsers drop `http(s)://` and `data:` URIs as `text/uri-list`
+                if !uri.starts_with("file:") {
+                    tx.send(Operation::PushURL(uri.clone(), None, false, false, None, None)).unwrap();
+                    continue;
+                }
                 match uri_to_path(uri) {
                     Ok(path) => tx.send(Operation::Push(Expandable::expanded(path), None, false, false)).unwrap(),
                     Err(err) => puts_error!(err),
@@ -269,16 +326,30 @@ fn on_drag_data_received(tx: &Sender<Operation>, selection: &SelectionData, drop
             }
         },
         DropItemType::URI => {
-            if let Some(url) = selection.get_text() {
-                tx.send(Operation::PushURL(url, None, false, false, None)).unwrap();
+            if let Some(text) = selection.get_text() {
+                for url in text.lines().map(str::trim).filter(|it| !it.is_empty() && !it.starts_with('#')) {
+                    tx.send(Operation::PushURL(o!(url), None, false, false, None, None)).unwrap();
+                }
             }
         }
     }
 }
 
-fn on_scroll(app_tx: &Sender<Operation>, key: Key, direction: ScrollDirection) {
+fn on_pan(app_tx: &Sender<Operation>, x: f64, y: f64, pressed_at: Option<(f64, f64)>, panned_at: &mut Option<(f64, f64)>) {
+    if_let_some!((px, py) = panned_at.or(pressed_at));
+
+    if panned_at.is_none() && feq(x, px, 10.0) && feq(y, py, 10.0) {
+        return;
+    }
+
+    app_tx.send(Operation::Pan(x - px, y - py)).unwrap();
+    *panned_at = Some((x, y));
+}
+
+fn on_scroll(app_tx: &Sender<Operation>, key: Key, direction: ScrollDirection, x: f64, y: f64, conf: &Conf) {
     if direction != ScrollDirection::Smooth {
-        app_tx.send(Operation::Fire(Mapped::Input(CoordPx::default(), key))).unwrap();
+        let coord = CoordPx { x: x as i32, y: y as i32, width: conf.width, height: conf.height };
+        app_tx.send(Operation::Fire(Mapped::Input(coord, key))).unwrap();
     }
 }
 
@@ -288,6 +359,12 @@ fn uri_to_path(uri: &str) -> Result<String, Box<dyn Error>> {
     unsafe {
         let g_file = g_file_new_for_uri(uri.into_raw());
         let c_path = g_file_get_path(g_file);
+        if c_path.is_null() {
+            #[allow(clippy::transmute_ptr_to_ptr)]
+            let ptr = transmute::<*const GFile, *mut GObject>(g_file);
+            g_object_unref(ptr);
+            return Err(From::from("Not a local file"));
+        }
         let path = CStr::from_ptr(c_path);
         let path = path.to_str()?.to_string();
 
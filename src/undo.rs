@@ -0,0 +1,189 @@
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::adjustment::Adjustment;
+use crate::cherenkov::Modifier;
+use crate::entry::Key;
+use crate::session::StatusText;
+use crate::size::{FitTo, Region};
+use crate::state::Drawing;
+
+
+
+/** Undo/Redo history of the modifiers and the drawing states for each entry **/
+#[derive(Default)]
+pub struct UndoHistory {
+    entries: HashMap<Key, Steps>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub drawing: EntryDrawing,
+    pub modifiers: Vec<Modifier>,
+}
+
+/** The drawing states for the entry. `fit_to` is the remembered zoom of the entry if it exists **/
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryDrawing {
+    pub adjustment: Adjustment,
+    pub clipping: Option<Region>,
+    pub fit_to: FitTo,
+    pub horizontal_flip: bool,
+    pub rotation: u8,
+    pub vertical_flip: bool,
+}
+
+#[derive(Default)]
+struct Steps {
+    redos: Vec<Step>,
+    trimmed: bool, /* Some steps were dropped by `depth` */
+    undos: VecDeque<Step>,
+}
+
+struct Step {
+    label: String,
+    snapshot: Snapshot,
+}
+
+
+impl UndoHistory {
+    /** `before` is the snapshot before the operation named `label` **/
+    pub fn record(&mut self, key: Key, label: &str, before: Snapshot, depth: usize) {
+        let steps = self.entries.entry(key).or_insert_with(Steps::default);
+        steps.undos.push_back(Step { label: o!(label), snapshot: before });
+        while depth < steps.undos.len() {
+            steps.undos.pop_front();
+            steps.trimmed = true;
+        }
+        steps.redos.clear();
+    }
+
+    /** Returns the snapshot to restore **/
+    pub fn undo(&mut self, key: &Key, current: Snapshot, depth: usize) -> Option<Snapshot> {
+        let steps = self.entries.entry(key.clone()).or_insert_with(Steps::default);
+
+        if let Some(step) = steps.undos.pop_back() {
+            steps.redos.push(Step { label: step.label, snapshot: current });
+            return Some(step.snapshot);
+        }
+
+        // For the modifiers restored from the store
+        if current.modifiers.is_empty() || steps.trimmed || depth <= steps.redos.len() {
+            return None;
+        }
+        let mut previous = current.clone();
+        previous.modifiers.pop();
+        steps.redos.push(Step { label: o!("Modifier"), snapshot: current });
+        Some(previous)
+    }
+
+    /** Returns the snapshot to restore **/
+    pub fn redo(&mut self, key: &Key, current: Snapshot) -> Option<Snapshot> {
+        let steps = self.entries.get_mut(key)?;
+        let step = steps.redos.pop()?;
+        steps.undos.push_back(Step { label: step.label, snapshot: current });
+        Some(step.snapshot)
+    }
+}
+
+
+impl EntryDrawing {
+    pub fn new(drawing: &Drawing, zoom: Option<FitTo>) -> Self {
+        EntryDrawing {
+            adjustment: drawing.adjustment,
+            clipping: drawing.clipping,
+            fit_to: zoom.unwrap_or(drawing.fit_to),
+            horizontal_flip: drawing.horizontal_flip,
+            rotation: drawing.rotation,
+            vertical_flip: drawing.vertical_flip,
+        }
+    }
+}
+
+
+impl StatusText for UndoHistory {
+    fn write_status_text(&self, out: &mut String) {
+        let mut keys: Vec<&Key> = self.entries.iter().filter(|(_, it)| !it.undos.is_empty() || !it.redos.is_empty()).map(|it| it.0).collect();
+        keys.sort_by(|a, b| (&a.1, a.2).cmp(&(&b.1, b.2)));
+
+        for key in keys {
+            let steps = &self.entries[key];
+            sprintln!(
+                out,
+                "undo: path={} index={} undo={} redo={}",
+                key.1,
+                key.2 + 1,
+                labels(steps.undos.iter()),
+                labels(steps.redos.iter().rev()));
+        }
+    }
+}
+
+
+fn labels<'a, T: Iterator<Item = &'a Step>>(steps: T) -> String {
+    steps.map(|it| it.label.as_str()).collect::<Vec<&str>>().join(",")
+}
+
+
+
+#[cfg(test)]#[test]
+fn test_undo_history() {
+    use crate::entry::EntryType;
+
+    let key: Key = (EntryType::Image, o!("a.png"), 0);
+    let snapshot = |clipping: f64| Snapshot {
+        drawing: EntryDrawing::new(&Drawing { clipping: Some(Region::new(0.0, 0.0, clipping, clipping)), ..Drawing::default() }, None),
+        modifiers: vec![],
+    };
+
+    let mut history = UndoHistory::default();
+    history.record(key.clone(), "Clip", snapshot(1.0), 2);
+    history.record(key.clone(), "Clip", snapshot(0.9), 2);
+    history.record(key.clone(), "Clip", snapshot(0.8), 2);
+
+    assert_eq!(history.undo(&key, snapshot(0.7), 2), Some(snapshot(0.8)));
+    assert_eq!(history.undo(&key, snapshot(0.8), 2), Some(snapshot(0.9)));
+    assert_eq!(history.undo(&key, snapshot(0.9), 2), None);
+
+    let mut status = o!("");
+    history.write_status_text(&mut status);
+    assert_eq!(status, "undo: path=a.png index=1 undo= redo=Clip,Clip\n");
+
+    assert_eq!(history.redo(&key, snapshot(0.9)), Some(snapshot(0.8)));
+    assert_eq!(history.redo(&key, snapshot(0.8)), Some(snapshot(0.7)));
+    assert_eq!(history.redo(&key, snapshot(0.7)), None);
+
+    assert_eq!(history.undo(&key, snapshot(0.7), 2), Some(snapshot(0.8)));
+    history.record(key.clone(), "Clip", snapshot(0.8), 2);
+    assert_eq!(history.redo(&key, snapshot(0.6)), None);
+}
+
+#[cfg(test)]#[test]
+fn test_undo_restored_modifiers() {
+    use crate::cherenkov::{Che, Modifier};
+    use crate::cherenkov::fill::Shape;
+    use crate::color::Color;
+    use crate::entry::EntryType;
+
+    let key: Key = (EntryType::Image, o!("a.png"), 0);
+    let fill = Modifier { che: Che::Fill(Shape::Rectangle, Region::full(), Color::black(), None, false), search_highlight: false };
+    let snapshot = |n: usize| Snapshot {
+        drawing: EntryDrawing::new(&Drawing::default(), None),
+        modifiers: vec![fill.clone(); n],
+    };
+
+    // Restored from the store
+    let mut history = UndoHistory::default();
+    assert_eq!(history.undo(&key, snapshot(3), 2), Some(snapshot(2)));
+    assert_eq!(history.undo(&key, snapshot(2), 2), Some(snapshot(1)));
+    assert_eq!(history.undo(&key, snapshot(1), 2), None);
+
+    // Trimmed by the depth
+    let mut history = UndoHistory::default();
+    history.record(key.clone(), "Fill", snapshot(0), 2);
+    history.record(key.clone(), "Fill", snapshot(1), 2);
+    history.record(key.clone(), "Fill", snapshot(2), 2);
+    assert_eq!(history.undo(&key, snapshot(3), 2), Some(snapshot(2)));
+    assert_eq!(history.undo(&key, snapshot(2), 2), Some(snapshot(1)));
+    assert_eq!(history.undo(&key, snapshot(1), 2), None);
+}
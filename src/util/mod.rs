@@ -7,3 +7,4 @@ pub mod pom;
 pub mod shell;
 pub mod string;
 pub mod time;
+pub mod uri;
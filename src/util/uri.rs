@@ -0,0 +1,93 @@
+
+use std::path::PathBuf;
+
+use url::Url;
+use url::percent_encoding::percent_decode;
+
+use crate::errors::{AppError, AppResult};
+
+
+
+pub fn is_data_uri(uri: &str) -> bool {
+    uri.starts_with("data:")
+}
+
+/** `<SCHEME>://...` **/
+pub fn is_url(path: &str) -> bool {
+    if_let_some!(index = path.find("://"), false);
+    index < 10
+}
+
+/** `file:///foo/bar%20baz.png` => `/foo/bar baz.png` **/
+pub fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    if !uri.starts_with("file:") {
+        return None;
+    }
+    Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+/** `data:[<MEDIA_TYPE>][;base64],<DATA>` **/
+pub fn decode_data_uri(uri: &str) -> AppResult<Vec<u8>> {
+    let invalid = || AppError::InvalidValue(uri.chars().take(64).collect());
+
+    if !is_data_uri(uri) {
+        return Err(invalid());
+    }
+    let comma = uri.find(',').ok_or_else(invalid)?;
+    let header = &uri[5..comma];
+    let data = percent_decode(uri[comma + 1..].as_bytes()).collect::<Vec<u8>>();
+
+    if header.split(';').any(|it| it.trim().eq_ignore_ascii_case("base64")) {
+        decode_base64(&data).ok_or_else(invalid)
+    } else {
+        Ok(data)
+    }
+}
+
+/** Whitespaces are ignored. Accepts the URL safe alphabet and the missing paddings **/
+fn decode_base64(source: &[u8]) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some(u32::from(c - b'A')),
+            b'a'..=b'z' => Some(u32::from(c - b'a') + 26),
+            b'0'..=b'9' => Some(u32::from(c - b'0') + 52),
+            b'+' | b'-' => Some(62),
+            b'/' | b'_' => Some(63),
+            _ => None,
+        }
+    }
+
+    let mut result = Vec::with_capacity(source.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &c in source.iter().filter(|it| !it.is_ascii_whitespace()) {
+        if c == b'=' {
+            break;
+        }
+        buffer = (buffer << 6) | value(c)?;
+        bits += 6;
+        if 8 <= bits {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(result)
+}
+
+
+#[cfg(test)]#[test]
+fn test_decode_data_uri() {
+    assert_eq!(decode_data_uri("data:image/png;base64,iVBORw0KGgo=").unwrap(), b"\x89PNG\r\n\x1a\n");
+    assert_eq!(decode_data_uri("data:;base64,aGVsbG8").unwrap(), b"hello");
+    assert_eq!(decode_data_uri("data:,hello%20world").unwrap(), b"hello world");
+    assert_eq!(decode_data_uri("data:text/plain;charset=utf-8;base64,aGVs\nbG8=").unwrap(), b"hello");
+    assert!(decode_data_uri("data:image/png;base64").is_err());
+    assert!(decode_data_uri("data:image/png;base64,!!!!").is_err());
+    assert!(decode_data_uri("http://example.com/").is_err());
+
+    assert_eq!(file_uri_to_path("file:///tmp/a%20b.png"), Some(PathBuf::from("/tmp/a b.png")));
+    assert_eq!(file_uri_to_path("http://example.com/a.png"), None);
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, rename};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use uuid::Uuid;
+
+use crate::app_path::cache_dir;
+use crate::constant::env_name;
+use crate::errors::{AppError, AppResult, AppResultU};
+use crate::shell::ProcessManager;
+use crate::util::path::path_to_str;
+
+
+
+pub struct Video {
+    pub path: PathBuf,
+    pub frames: usize,
+    command: String,
+    extractions: Mutex<HashMap<usize, Extraction>>,
+}
+
+enum Extraction {
+    Running,
+    Failed(String),
+}
+
+
+impl Video {
+    pub fn new<T: AsRef<Path>>(path: &T, frames: usize, command: String) -> Video {
+        Video { path: path.as_ref().to_path_buf(), frames: max!(frames, 1), command, extractions: Mutex::default() }
+    }
+
+    /** The cached file path of the `index`th frame (0 origin), or `None` if it is not extracted yet **/
+    pub fn frame(&self, index: usize) -> AppResult<Option<PathBuf>> {
+        if let Some(Extraction::Failed(ref error)) = self.extractions.lock().unwrap().get(&index) {
+            return Err(AppError::Standard(o!(error)));
+        }
+
+        let output = self.frame_path(index)?;
+        Ok(if output.exists() { Some(output) } else { None })
+    }
+
+    /** Extract the `index`th frame in background, then `on_complete` is called **/
+    pub fn extract<F>(self: &Arc<Self>, index: usize, process_manager: &ProcessManager, on_complete: F) -> AppResultU
+    where F: FnOnce() + Send + 'static {
+        let output = self.frame_path(index)?;
+        if output.exists() {
+            return Ok(());
+        }
+
+        {
+            let mut extractions = self.extractions.lock().unwrap();
+            if extractions.contains_key(&index) {
+                return Ok(());
+            }
+            extractions.insert(index, Extraction::Running);
+        }
+
+        let temporary = output.with_extension(format!("{}.png", Uuid::new_v4()));
+        let position = (f64!(index) + 0.5) / f64!(self.frames);
+        let envs = vec![
+            (env_name("video_file"), o!(path_to_str(&self.path))),
+            (env_name("video_frame"), s!(index + 1)),
+            (env_name("video_frames"), s!(self.frames)),
+            (env_name("video_position"), s!(position)),
+            (env_name("video_output"), o!(path_to_str(&temporary))),
+        ];
+
+        let video = Arc::clone(self);
+        process_manager.call_with_envs(vec![o!("sh"), o!("-c"), self.command.clone()], envs, move |result| {
+            let result = result.and_then(|_| {
+                if !temporary.exists() {
+                    return Err(AppError::Fixed("Frame extractor did not output any file"));
+                }
+                rename(&temporary, &output)?;
+                Ok(())
+            });
+
+            let mut extractions = video.extractions.lock().unwrap();
+            if let Err(err) = result {
+                puts_error!(err, "at" => "video/extract", "for" => path_to_str(&video.path), "frame" => s!(index + 1));
+                extractions.insert(index, Extraction::Failed(s!(err)));
+            } else {
+                extractions.remove(&index);
+            }
+            on_complete();
+        });
+
+        Ok(())
+    }
+
+    fn frame_path(&self, index: usize) -> AppResult<PathBuf> {
+        let mut result = self.cache_directory()?;
+        result.push(format!("{:04}.png", index + 1));
+        Ok(result)
+    }
+
+    fn cache_directory(&self) -> AppResult<PathBuf> {
+        let modified = self.path.metadata()?.modified()?;
+        let modified = modified.duration_since(UNIX_EPOCH).map(|it| it.as_secs()).unwrap_or(0);
+        let source = format!("{}\t{}\t{}\t{}", path_to_str(&self.path), modified, self.frames, self.command);
+
+        let mut result = cache_dir("video");
+        result.push(format!("{:x}", md5::compute(source.as_bytes())));
+        create_dir_all(&result)?;
+        Ok(result)
+    }
+}